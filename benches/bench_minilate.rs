@@ -24,6 +24,49 @@ fn minilate_benchmark(c: &mut Criterion) {
     // Add template to engine
     engine.add_template("profile", template_content).unwrap();
 
+    // A template with no tags at all, which can skip walking the AST entirely
+    let constant_content = template_content.replace("{{", "{ {");
+    engine
+        .add_template("constant", constant_content.as_str())
+        .unwrap();
+
+    // A long template that is mostly text, where growing the output dominates
+    let large_text_content = vec![constant_content.as_str(); 20].join("{{ title }}");
+    engine
+        .add_template("large_text", large_text_content.as_str())
+        .unwrap();
+
+    // A loop over many items in a context with many variables, where copying the context
+    // for each loop would dominate
+    engine
+        .add_template(
+            "large_context_loop",
+            "{{% for row in rows %}}{{ loop.index }}. {{ row }} ({{ title }})\n\
+             {{% for cell in cells %}}[{{ row }}:{{ cell }}]{{% endfor %}}\n{{% endfor %}}",
+        )
+        .unwrap();
+    // Working out the variables still needed by templates included in such a loop
+    engine
+        .add_template(
+            "large_context_include",
+            "{{% for row in rows %}}{{<< row_summary }}{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template("row_summary", "{{ row }}: {{ summary }}")
+        .unwrap();
+    let mut large_context = Context::new();
+    for i in 0..1000 {
+        large_context.insert(
+            format!("unused_{i}"),
+            VariableTy::String.with_data(format!("value {i}")),
+        );
+    }
+    large_context.insert("title", VariableTy::String.with_data("Report"));
+    let rows = (0..200).map(|i| format!("row{i}")).collect::<Vec<_>>();
+    large_context.insert("rows", VariableTy::Iterable.with_data(rows.join(",")));
+    large_context.insert("cells", VariableTy::Iterable.with_data("a,b,c,d"));
+
     // Generate 100 random contexts
     let json_contexts = utils::generate_random_contexts(100);
 
@@ -49,6 +92,36 @@ fn minilate_benchmark(c: &mut Criterion) {
         });
     });
 
+    // Benchmark rendering a template that contains only constant text
+    group.bench_function("minilate_render_constant", |b| {
+        b.iter(|| {
+            for context in &contexts {
+                black_box(engine.render("constant", Some(context)).unwrap());
+            }
+        });
+    });
+
+    // Benchmark a long template that is mostly text
+    group.bench_function("minilate_render_large_text", |b| {
+        b.iter(|| black_box(engine.render("large_text", Some(&large_context)).unwrap()));
+    });
+
+    // Benchmark nested loops in a context with many variables
+    group.bench_function("minilate_render_large_context_loop", |b| {
+        b.iter(|| {
+            black_box(
+                engine
+                    .render("large_context_loop", Some(&large_context))
+                    .unwrap(),
+            )
+        });
+    });
+
+    // Benchmark collecting the variables required by includes in a loop over many items
+    group.bench_function("minilate_context_large_context_loop", |b| {
+        b.iter(|| black_box(engine.context("large_context_include", &large_context)));
+    });
+
     group.finish();
 }
 
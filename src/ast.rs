@@ -4,69 +4,418 @@
 //! structure and components like constant text, variables, control flow (if/for),
 //! and template inclusions.
 //!
-//! The [`crate::parser`] module is responsible for generating these AST nodes
-//! from a template string. The [`crate::engine::MinilateEngine`] then uses this
-//! AST to render the template with a given [`crate::interface::Context`].
+//! The parser is responsible for generating these AST nodes from a template
+//! string. The [`crate::MinilateEngine`] then uses this AST to render the
+//! template with a given [`crate::Context`]. A parsed template's AST is available
+//! through [`crate::Template::ast`], and can be traversed with a [`crate::Visitor`].
 //!
 //! # Key Structures
 //!
 //! - [`AstNode`]: The core enum representing different types of nodes in the template.
 //!   - `AstNode::Root`: The top-level node of a parsed template.
-//!   - `AstNode::Constant`: Represents a block of static text.
+//!   - `AstNode::Constant`: Represents a block of static text, along with any whitespace trimmed from it.
 //!   - `AstNode::Variable`: Represents a `{{ variable }}` substitution.
-//!   - `AstNode::For`: Represents a `{{% for item in items %}}` loop.
+//!   - `AstNode::Filter`: Represents a filter applied to a value, as in `{{ variable | trim }}`.
+//!   - `AstNode::For`: Represents a `{{% for item in items %}}` or `{{% for k, v in map %}}` loop,
+//!     optionally with an `{{% empty %}}` branch.
+//!   - `AstNode::Repeat`: Represents a `{{% repeat 3 %}}` block, rendering its body a fixed number of times.
+//!   - `AstNode::Ternary`: Represents a `(condition ? a : b)` choice between two for-loop iterables.
 //!   - `AstNode::If`: Represents an `{{% if condition %}}` block, potentially with `else` or `else if` branches.
-//!   - `AstNode::Not`, `AstNode::And`, `AstNode::Or`: Represent logical operations within conditions.
+//!   - `AstNode::Not`, `AstNode::And`, `AstNode::Or`, `AstNode::Xor`: Represent logical operations within conditions.
+//!   - `AstNode::Compare`: Represents a comparison such as `==` or `>=` within a condition, against another variable, an `AstNode::StringLiteral`, an `AstNode::NumberLiteral` or an `AstNode::BoolLiteral`.
+//!   - `AstNode::Coalesce`: Represents a `status ?? "pending"` fallback for a missing variable within a condition.
 //!   - `AstNode::TemplateInclude`: Represents a `{{<< sub_template.tmpl }}` inclusion.
+//!   - `AstNode::DebugDump`: Represents a `{{% debug %}}` dump of the current context.
+//!   - `AstNode::DebugOnly`: Represents an `{{% ifdebug %}}` block, rendered only when debugging is enabled.
+//!   - `AstNode::DynamicInclude`: Represents a `{{<< "partials/{{ kind }}" }}` inclusion whose name is built at render time.
+//!   - `AstNode::Set`: Represents a `{{% set name = value %}}` assignment, in scope until the end of the enclosing block.
+//!   - `AstNode::Alias`: Represents an `{{% alias name = long.path %}}` short name, in scope until the end of the enclosing block.
+//!   - `AstNode::Break`, `AstNode::Continue`: Represent `{{% break %}}` and `{{% continue %}}` within a for loop or repeat block.
 //!
 //! The structure of the AST allows for efficient traversal during rendering and
 //! context analysis (e.g., determining required variables).
 
 use std::borrow::Cow;
+use std::fmt::Write;
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Clone)] // Added PartialEq and Clone for easier testing
 pub enum AstNode<'a> {
-    Root(Vec<AstNode<'a>>),
+    /// The top-level node of a template, or of a nested block such as an else branch.
+    Root(Vec<Self>),
     /// A constant block of text from the template, with all escapes processed.
     ///
     /// If there were no escapes in the given text this will be Borrowed -
     /// otherwise we are forced to allocate.
     Constant {
         data: Cow<'a, str>,
+        /// Whitespace trimmed by the `-` markers of the neighbouring tags, as in `{{- name -}}`.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Trim::is_none")
+        )]
+        trim: Trim,
     },
     /// A variable.
-    Variable {
+    Variable { name: &'a str },
+    /// A filter applied to the rendered output of `inner`, e.g. `{{ name | trim }}`.
+    Filter {
         name: &'a str,
+        /// The quoted argument following a colon, e.g. `anon` in `{{ name | default:"anon" }}`.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        argument: Option<&'a str>,
+        inner: Box<Self>,
     },
     /// A For loop.
     For {
-        iterable: &'a str,
+        /// Either an `AstNode::Variable` or an `AstNode::Ternary` selecting one.
+        iterable: Box<Self>,
         variable: &'a str,
-        body: Vec<AstNode<'a>>,
+        /// The second loop variable of `{{% for k, v in map %}}`, bound to each entry's value
+        /// while `variable` is bound to its key.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Option::is_none")
+        )]
+        value_variable: Option<&'a str>,
+        body: Vec<Self>,
+        /// Rendered instead of the body when the iterable has no non-empty items.
+        empty_branch: Option<Box<Self>>, // This will typically be an AstNode::Root
+    },
+    /// A block rendered `count` times, e.g. `{{% repeat 3 %}}...{{% endrepeat %}}`.
+    ///
+    /// The body sees the same `loop.*` metadata as a for loop, e.g. the 1-based `loop.index`.
+    Repeat { count: usize, body: Vec<Self> },
+    /// A conditional choice between two values, e.g. `(admin ? all_items : public_items)`.
+    Ternary {
+        condition: Box<Self>,
+        if_true: Box<Self>,
+        if_false: Box<Self>,
     },
     /// A If statement.
     If {
-        condition: Box<AstNode<'a>>,
-        body: Vec<AstNode<'a>>,
-        else_branch: Option<Box<AstNode<'a>>>, // This will typically be an AstNode::Root for else branches
+        condition: Box<Self>,
+        body: Vec<Self>,
+        else_branch: Option<Box<Self>>, // This will typically be an AstNode::Root for else branches
     },
     /// Conditional NOT
-    Not {
-        condition: Box<AstNode<'a>>,
-    },
+    Not { condition: Box<Self> },
     /// Conditional AND
-    And {
-        left: Box<AstNode<'a>>,
-        right: Box<AstNode<'a>>,
-    },
+    And { left: Box<Self>, right: Box<Self> },
     /// Conditional OR
-    Or {
-        left: Box<AstNode<'a>>,
-        right: Box<AstNode<'a>>,
+    Or { left: Box<Self>, right: Box<Self> },
+    /// Conditional XOR
+    Xor { left: Box<Self>, right: Box<Self> },
+    /// A comparison between two operands of a condition, e.g. `status == "active"` or `age >= 18`.
+    Compare {
+        left: Box<Self>,
+        op: CompareOp,
+        right: Box<Self>,
     },
-    /// Template inclusion
+    /// A quoted string literal in a condition, e.g. `"active"`.
+    StringLiteral { value: &'a str },
+    /// A number literal in a condition, e.g. `18`, kept as written.
+    NumberLiteral { value: &'a str },
+    /// A `true` or `false` literal in a condition.
+    BoolLiteral { value: bool },
+    /// An operand of a condition with a fallback, e.g. `status ?? "pending"`, which stands
+    /// for `fallback` when `value` is a variable that is missing or has no data.
+    Coalesce {
+        value: Box<Self>,
+        fallback: Box<Self>,
+    },
+    /// Template inclusion, e.g. `{{<< greeting name=full_name, polite=true }}`
+    ///
+    /// Without `bindings`, the included template is rendered with the current context.
+    /// Otherwise it's rendered with a fresh context holding only the bound variables, each
+    /// set from a variable in the current context or a literal.
     TemplateInclude {
         template_name: &'a str,
+        #[cfg_attr(
+            feature = "serde",
+            serde(borrow, default, skip_serializing_if = "Vec::is_empty")
+        )]
+        bindings: Vec<(&'a str, Self)>,
     },
+    /// Dumps every variable in the current context, when debugging is enabled
+    DebugDump,
+    /// A block rendered only when debugging is enabled, e.g. `{{% ifdebug %}}...{{% endifdebug %}}`
+    DebugOnly { body: Vec<Self> },
+    /// Template inclusion where the template name is interpolated from the context
+    DynamicInclude {
+        /// `AstNode::Constant` and `AstNode::Variable` parts, concatenated to form the name.
+        name: Vec<Self>,
+    },
+    /// Assigns a variable for the rest of the enclosing block or template, e.g.
+    /// `{{% set title = name | upper %}}`.
+    Set {
+        name: &'a str,
+        /// An `AstNode::Variable`, `AstNode::StringLiteral` or `AstNode::NumberLiteral`,
+        /// possibly wrapped in `AstNode::Filter`s.
+        value: Box<Self>,
+    },
+    /// Lets `name` stand for the variable `target` for the rest of the enclosing block or
+    /// template, e.g. `{{% alias name = user.profile.name %}}`.
+    Alias { name: &'a str, target: &'a str },
+    /// Stops the innermost for loop or repeat block, e.g. `{{% break %}}`.
+    Break,
+    /// Skips the rest of the current iteration of the innermost for loop or repeat block,
+    /// e.g. `{{% continue %}}`.
+    Continue,
+}
+
+/// The operator of an [`AstNode::Compare`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompareOp {
+    /// `==`, true if both operands are equal.
+    Eq,
+    /// `!=`, true if the operands differ.
+    Ne,
+    /// `<`, true if the left operand is numerically less than the right.
+    Lt,
+    /// `>`, true if the left operand is numerically greater than the right.
+    Gt,
+    /// `<=`, true if the left operand is numerically less than or equal to the right.
+    Le,
+    /// `>=`, true if the left operand is numerically greater than or equal to the right.
+    Ge,
+    /// `contains`, true if the left operand is an iterable with an item equal to the right.
+    Contains,
+}
+
+impl CompareOp {
+    /// Returns true for the numeric operators `<`, `>`, `<=` and `>=`, as opposed to the
+    /// string operators `==`, `!=` and `contains`.
+    pub const fn is_relational(self) -> bool {
+        matches!(self, Self::Lt | Self::Gt | Self::Le | Self::Ge)
+    }
+
+    /// Returns the operator as written in a template.
+    const fn symbol(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::Le => "<=",
+            Self::Ge => ">=",
+            Self::Contains => "contains",
+        }
+    }
+}
+
+impl AstNode<'_> {
+    /// Writes this node and everything below it to `output` as an indented tree, one node
+    /// per line, starting `depth` levels deep. See [`crate::Template::dump_ast`].
+    pub(crate) fn write_tree(&self, output: &mut String, depth: usize) {
+        let child_depth = depth.saturating_add(1);
+        match self {
+            Self::Root(children) => {
+                write_line(output, depth, "Root");
+                for child in children {
+                    child.write_tree(output, child_depth);
+                }
+            }
+            Self::Constant { data, .. } => {
+                write_line(output, depth, format_args!("Constant {:?}", data));
+            }
+            Self::Variable { name } => write_line(output, depth, format_args!("Variable {name}")),
+            Self::Filter {
+                name,
+                argument,
+                inner,
+            } => {
+                match argument {
+                    Some(argument) => {
+                        write_line(output, depth, format_args!("Filter {name}:{argument:?}"));
+                    }
+                    None => write_line(output, depth, format_args!("Filter {name}")),
+                }
+                inner.write_tree(output, child_depth);
+            }
+            Self::For {
+                iterable,
+                variable,
+                value_variable,
+                body,
+                empty_branch,
+            } => {
+                match value_variable {
+                    Some(value_variable) => write_line(
+                        output,
+                        depth,
+                        format_args!("For {variable}, {value_variable}"),
+                    ),
+                    None => write_line(output, depth, format_args!("For {variable}")),
+                }
+                write_branch(output, child_depth, "in", [&**iterable]);
+                write_branch(output, child_depth, "body", body);
+                if let Some(empty_branch) = empty_branch {
+                    write_branch(output, child_depth, "empty", [&**empty_branch]);
+                }
+            }
+            Self::Repeat { count, body } => {
+                write_line(output, depth, format_args!("Repeat {count}"));
+                for child in body {
+                    child.write_tree(output, child_depth);
+                }
+            }
+            Self::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                write_line(output, depth, "Ternary");
+                write_branch(output, child_depth, "condition", [&**condition]);
+                write_branch(output, child_depth, "then", [&**if_true]);
+                write_branch(output, child_depth, "else", [&**if_false]);
+            }
+            Self::If {
+                condition,
+                body,
+                else_branch,
+            } => {
+                write_line(output, depth, "If");
+                write_branch(output, child_depth, "condition", [&**condition]);
+                write_branch(output, child_depth, "body", body);
+                if let Some(else_branch) = else_branch {
+                    write_branch(output, child_depth, "else", [&**else_branch]);
+                }
+            }
+            Self::Not { condition } => {
+                write_line(output, depth, "Not");
+                condition.write_tree(output, child_depth);
+            }
+            Self::And { left, right } => write_operands(output, depth, "And", [left, right]),
+            Self::Or { left, right } => write_operands(output, depth, "Or", [left, right]),
+            Self::Xor { left, right } => write_operands(output, depth, "Xor", [left, right]),
+            Self::Compare { left, op, right } => {
+                write_line(output, depth, format_args!("Compare {}", op.symbol()));
+                left.write_tree(output, child_depth);
+                right.write_tree(output, child_depth);
+            }
+            Self::StringLiteral { value } => {
+                write_line(output, depth, format_args!("StringLiteral {value:?}"));
+            }
+            Self::NumberLiteral { value } => {
+                write_line(output, depth, format_args!("NumberLiteral {value}"));
+            }
+            Self::BoolLiteral { value } => {
+                write_line(output, depth, format_args!("BoolLiteral {value}"));
+            }
+            Self::Coalesce { value, fallback } => {
+                write_line(output, depth, "Coalesce");
+                value.write_tree(output, child_depth);
+                fallback.write_tree(output, child_depth);
+            }
+            Self::TemplateInclude {
+                template_name,
+                bindings,
+            } => {
+                write_line(
+                    output,
+                    depth,
+                    format_args!("TemplateInclude {template_name}"),
+                );
+                for (name, value) in bindings {
+                    write_line(output, child_depth, format_args!("Binding {name}"));
+                    value.write_tree(output, child_depth.saturating_add(1));
+                }
+            }
+            Self::DebugDump => write_line(output, depth, "DebugDump"),
+            Self::DebugOnly { body } => {
+                write_line(output, depth, "DebugOnly");
+                for child in body {
+                    child.write_tree(output, child_depth);
+                }
+            }
+            Self::Break => write_line(output, depth, "Break"),
+            Self::Continue => write_line(output, depth, "Continue"),
+            Self::Set { name, value } => {
+                write_line(output, depth, format_args!("Set {name}"));
+                value.write_tree(output, child_depth);
+            }
+            Self::Alias { name, target } => {
+                write_line(output, depth, format_args!("Alias {name} = {target}"));
+            }
+            Self::DynamicInclude { name } => {
+                write_line(output, depth, "DynamicInclude");
+                for part in name {
+                    part.write_tree(output, child_depth);
+                }
+            }
+        }
+    }
+}
+
+/// Writes `text` to `output` as a line of an AST dump, indented `depth` levels deep.
+fn write_line(output: &mut String, depth: usize, text: impl std::fmt::Display) {
+    // Writing to a `String` can't fail
+    writeln!(
+        output,
+        "{:indent$}{text}",
+        "",
+        indent = depth.saturating_mul(2)
+    )
+    .ok();
+}
+
+/// Writes a node named `name` with its two `operands` to `output` as part of an AST dump.
+fn write_operands(output: &mut String, depth: usize, name: &str, operands: [&AstNode<'_>; 2]) {
+    write_line(output, depth, name);
+    for operand in operands {
+        operand.write_tree(output, depth.saturating_add(1));
+    }
+}
+
+/// Writes a labelled group of child nodes to `output` as part of an AST dump.
+fn write_branch<'n, 'a: 'n>(
+    output: &mut String,
+    depth: usize,
+    label: &str,
+    nodes: impl IntoIterator<Item = &'n AstNode<'a>>,
+) {
+    write_line(output, depth, format_args!("{label}:"));
+    for node in nodes {
+        node.write_tree(output, depth.saturating_add(1));
+    }
+}
+
+/// Which whitespace to trim from a constant block of text.
+///
+/// A `-` just inside the opening delimiter of a tag, as in `{{- name }}` or `{{-% if x %}}`,
+/// trims all whitespace from the `end` of the preceding text. A `-` just inside the closing
+/// delimiter, as in `{{ name -}}` or `{{% endfor -%}}`, trims whitespace from the `start` of
+/// the following text, up to and including the first newline.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Trim {
+    /// Trim whitespace up to and including the first newline from the start of the text.
+    pub start: bool,
+    /// Trim all whitespace from the end of the text.
+    pub end: bool,
+}
+
+impl Trim {
+    /// Returns true if no whitespace is trimmed on either side.
+    pub const fn is_none(&self) -> bool {
+        !self.start && !self.end
+    }
+
+    /// Returns `data` with the configured whitespace removed.
+    pub(crate) fn apply(self, data: &str) -> &str {
+        let mut data = data;
+        if self.start {
+            data = data.trim_start_matches(|c: char| c.is_whitespace() && c != '\n');
+            data = data.strip_prefix('\n').unwrap_or(data);
+        }
+        if self.end {
+            data = data.trim_end();
+        }
+        data
+    }
 }
@@ -0,0 +1,184 @@
+//! Line-based diffs between expected and actual output.
+//!
+//! Used by [`crate::MinilateEngine::diff_render`] to explain why a render didn't match a
+//! golden file. The diff is computed from the longest common subsequence of lines, which
+//! is quadratic in the number of lines but more than fast enough for test fixtures.
+
+use std::fmt::Write;
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// A single line of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'s> {
+    /// A line present in both inputs.
+    Same(&'s str),
+    /// A line only present in the expected input.
+    Removed(&'s str),
+    /// A line only present in the actual input.
+    Added(&'s str),
+}
+
+impl Line<'_> {
+    const fn is_change(&self) -> bool {
+        !matches!(self, Self::Same(_))
+    }
+}
+
+/// Returns a unified diff turning `expected` into `actual`, or an empty string if they are
+/// equal.
+///
+/// Lines are split on `\n`, so a missing or extra trailing newline shows up as a change to
+/// an empty last line.
+pub fn unified_diff(expected: &str, actual: &str) -> String {
+    if expected == actual {
+        return String::new();
+    }
+
+    let expected_lines: Vec<&str> = expected.split('\n').collect();
+    let actual_lines: Vec<&str> = actual.split('\n').collect();
+    let lines = diff_lines(&expected_lines, &actual_lines);
+
+    let mut output = String::from("--- expected\n+++ actual\n");
+    let mut start = 0;
+    while let Some(hunk) = next_hunk(&lines, start) {
+        start = hunk.end;
+        write_hunk(&lines, hunk, &mut output);
+    }
+    output
+}
+
+/// Pairs up the lines of `expected` and `actual` along their longest common subsequence.
+fn diff_lines<'s>(expected: &[&'s str], actual: &[&'s str]) -> Vec<Line<'s>> {
+    // `table[i * width + j]` is the length of the longest common subsequence of
+    // `expected[i..]` and `actual[j..]`
+    let width = actual.len().saturating_add(1);
+    let mut table = vec![0_usize; expected.len().saturating_add(1).saturating_mul(width)];
+    let at = |table: &[usize], i: usize, j: usize| {
+        table
+            .get(i.saturating_mul(width).saturating_add(j))
+            .copied()
+            .unwrap_or(0)
+    };
+    for (i, expected_line) in expected.iter().enumerate().rev() {
+        for (j, actual_line) in actual.iter().enumerate().rev() {
+            let length = if expected_line == actual_line {
+                at(&table, i.saturating_add(1), j.saturating_add(1)).saturating_add(1)
+            } else {
+                at(&table, i.saturating_add(1), j).max(at(&table, i, j.saturating_add(1)))
+            };
+            if let Some(cell) = table.get_mut(i.saturating_mul(width).saturating_add(j)) {
+                *cell = length;
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    loop {
+        match (expected.get(i), actual.get(j)) {
+            (Some(expected_line), Some(actual_line)) if expected_line == actual_line => {
+                lines.push(Line::Same(expected_line));
+                i = i.saturating_add(1);
+                j = j.saturating_add(1);
+            }
+            (Some(expected_line), Some(_))
+                if at(&table, i.saturating_add(1), j) >= at(&table, i, j.saturating_add(1)) =>
+            {
+                lines.push(Line::Removed(expected_line));
+                i = i.saturating_add(1);
+            }
+            (Some(expected_line), None) => {
+                lines.push(Line::Removed(expected_line));
+                i = i.saturating_add(1);
+            }
+            (_, Some(actual_line)) => {
+                lines.push(Line::Added(actual_line));
+                j = j.saturating_add(1);
+            }
+            (None, None) => break,
+        }
+    }
+    lines
+}
+
+/// Returns the range of `lines` making up the first hunk at or after `start`: a run of
+/// changes, merged with any changes close enough to share context, plus that context.
+fn next_hunk(lines: &[Line<'_>], start: usize) -> Option<std::ops::Range<usize>> {
+    let first_change = lines
+        .iter()
+        .skip(start)
+        .position(Line::is_change)?
+        .saturating_add(start);
+
+    let mut last_change = first_change;
+    for (index, line) in lines.iter().enumerate().skip(first_change) {
+        if index.saturating_sub(last_change) > CONTEXT_LINES.saturating_mul(2) {
+            break;
+        }
+        if line.is_change() {
+            last_change = index;
+        }
+    }
+
+    let hunk_start = first_change.saturating_sub(CONTEXT_LINES).max(start);
+    let hunk_end = last_change
+        .saturating_add(CONTEXT_LINES)
+        .saturating_add(1)
+        .min(lines.len());
+    Some(hunk_start..hunk_end)
+}
+
+/// Writes the `@@` header and lines of the hunk spanning `range` to `output`.
+fn write_hunk(lines: &[Line<'_>], range: std::ops::Range<usize>, output: &mut String) {
+    let before = lines.get(..range.start).unwrap_or_default();
+    let hunk = lines.get(range).unwrap_or_default();
+
+    let expected_start = before
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count();
+    let actual_start = before
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count();
+    let expected_len = hunk
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count();
+    let actual_len = hunk
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count();
+
+    // Line numbers are 1-based, except that an empty side names the line before it
+    let line_number = |start: usize, len: usize| {
+        if len == 0 {
+            start
+        } else {
+            start.saturating_add(1)
+        }
+    };
+    // Writing to a `String` can't fail
+    writeln!(
+        output,
+        "@@ -{},{} +{},{} @@",
+        line_number(expected_start, expected_len),
+        expected_len,
+        line_number(actual_start, actual_len),
+        actual_len
+    )
+    .ok();
+
+    for line in hunk {
+        let (prefix, text) = match line {
+            Line::Same(text) => (' ', text),
+            Line::Removed(text) => ('-', text),
+            Line::Added(text) => ('+', text),
+        };
+        output.push(prefix);
+        output.push_str(text);
+        output.push('\n');
+    }
+}
@@ -37,12 +37,21 @@
 //! the public API for interacting with templating engines in Minilate.
 
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
 
 use crate::ast::AstNode;
 use crate::error::{MinilateError, MinilateResult};
-use crate::interface::{Context, MinilateInterface};
+use crate::escape::Escaper;
+use crate::interface::{Context, MinilateInterface, Variable, VariableTy};
+use crate::lint::{self, LintWarning};
+use crate::missing::{MissingPolicy, RenderMode};
+use crate::parser::DelimiterConfig;
+use crate::rendered::RenderedDoc;
 use crate::template::Template;
+use crate::visit::{Visitor, walk};
 
 /// `MinilateEngine` is the primary implementation of the `MinilateInterface` trait,
 /// providing a complete templating engine for the Minilate system
@@ -72,6 +81,70 @@ use crate::template::Template;
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MinilateEngine<'a> {
     templates: HashMap<String, Template<'a>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    escaper: Escaper,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    debug: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bool_labels: Option<(String, String)>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    dotted_fallback: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    missing_policy: MissingPolicy,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    filters: HashMap<String, Box<dyn Fn(&str) -> String + Send + Sync>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    renderers: HashMap<String, Box<dyn Fn(&Variable<'_>) -> String + Send + Sync>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    type_renderers: HashMap<VariableTy, Box<dyn Fn(&Variable<'_>) -> String + Send + Sync>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    preserve_unknown_directives: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    case_insensitive_vars: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    iterable_separator: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    delimiters: DelimiterConfig,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    max_template_length: Option<usize>,
+}
+
+/// Normalizes path separators in a template name so that `admin\header` and
+/// `admin/header` refer to the same template, and drops any `.tmpl` extension so that
+/// `{{<< header.tmpl }}` includes the template loaded from `header.tmpl`.
+fn normalize_name(name: &str) -> Cow<'_, str> {
+    let name = name.strip_suffix(".tmpl").unwrap_or(name);
+    if name.contains('\\') {
+        Cow::Owned(name.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Rejects a template named `name` that directly includes itself, as it would recurse
+/// forever when rendered. Dynamic includes can't be checked until render time.
+fn check_self_include(name: &str, template: &Template<'_>) -> MinilateResult<()> {
+    struct SelfInclude<'n> {
+        name: &'n str,
+        found: bool,
+    }
+
+    impl<'a> Visitor<'a> for SelfInclude<'_> {
+        fn visit_template_include(&mut self, template_name: &'a str) {
+            self.found |= normalize_name(template_name) == self.name;
+        }
+    }
+
+    let mut visitor = SelfInclude { name, found: false };
+    walk(template.ast(), &mut visitor);
+
+    if visitor.found {
+        return Err(MinilateError::SelfInclude {
+            template_name: name.to_string(),
+        });
+    }
+
+    Ok(())
 }
 
 impl<'a> MinilateEngine<'a> {
@@ -86,101 +159,1690 @@ impl<'a> MinilateEngine<'a> {
         match node {
             AstNode::Root(children) => {
                 // Process all children nodes
-                for child in children {
-                    self.collect_inclusion_variables(child, variables, context, visited);
-                }
+                self.collect_block_inclusion_variables(children, variables, context, visited);
             }
             AstNode::If {
                 condition,
                 body,
                 else_branch,
             } => {
-                // Evaluate the condition with the current context
-                let condition_result =
-                    crate::template::evaluate_condition(condition, context).unwrap_or(false);
-
-                if condition_result {
-                    // Process the if body only if condition is true
-                    for child in body {
-                        self.collect_inclusion_variables(child, variables, context, visited);
+                // Evaluate the condition with the current context. Until the context has
+                // enough variables to decide it, neither branch's includes are needed yet
+                match crate::template::evaluate_known_condition(condition, context) {
+                    Some(true) => {
+                        self.collect_block_inclusion_variables(body, variables, context, visited);
                     }
-                } else if let Some(else_node) = else_branch {
-                    // Process the else branch only if condition is false
-                    self.collect_inclusion_variables(else_node, variables, context, visited);
+                    Some(false) => {
+                        if let Some(else_node) = else_branch {
+                            self.collect_inclusion_variables(
+                                else_node, variables, context, visited,
+                            );
+                        }
+                    }
+                    None => {}
                 }
             }
             AstNode::For {
                 variable,
+                value_variable,
                 iterable,
                 body,
+                empty_branch,
             } => {
+                let iterable_var = crate::template::resolve_iterable(iterable, context)
+                    .ok()
+                    .and_then(|name| context.get(name));
+
+                // Structured lists have no data, but bind each item's fields under the loop
+                // variable, as when rendering
+                if let Some(list) = iterable_var.filter(|v| {
+                    v.ty() == crate::interface::VariableTy::List && value_variable.is_none()
+                }) {
+                    let items = list.items();
+                    if items.is_empty() {
+                        if let Some(empty_node) = empty_branch {
+                            self.collect_inclusion_variables(
+                                empty_node, variables, context, visited,
+                            );
+                        }
+                        return;
+                    }
+
+                    for (index, item) in items.iter().enumerate() {
+                        let mut loop_context = Context::scoped(context);
+                        for (field, value) in item.iter() {
+                            loop_context.insert(format!("{variable}.{field}"), value.clone());
+                        }
+                        crate::template::insert_loop_metadata(
+                            &mut loop_context,
+                            index,
+                            items.len(),
+                        );
+
+                        self.collect_block_inclusion_variables(
+                            body,
+                            variables,
+                            &loop_context,
+                            visited,
+                        );
+                    }
+                    return;
+                }
+
                 // Check if the iterable exists and has data
-                let has_items = context
-                    .get(iterable)
-                    .and_then(|v| v.data())
-                    .is_some_and(|d| !d.is_empty());
+                let iterable_data = iterable_var.and_then(|v| v.data());
+                let has_items = iterable_data.is_some_and(|d| !d.is_empty());
+
+                // The empty branch is taken when the iterable is known but has no non-empty items
+                if let Some(empty_node) = empty_branch {
+                    let all_empty = iterable_data.is_some_and(|d| {
+                        d.split(context.separator())
+                            .all(|item| item.trim().is_empty())
+                    });
+                    if all_empty {
+                        self.collect_inclusion_variables(empty_node, variables, context, visited);
+                        return;
+                    }
+                }
+
+                // When the iterable has items in the context, we should process the body
+                // and no longer need to collect the iterable variable (since it's available)
+                if let Some(data) = iterable_data.filter(|_| has_items) {
+                    // Process the body once per item, exactly as it will be rendered, so that
+                    // included templates see the loop variable bound to each item in turn
+                    let length = data.split(context.separator()).count();
+                    for (index, item) in data.split(context.separator()).enumerate() {
+                        let mut loop_context = Context::scoped(context);
+                        if let Some(value_variable) = value_variable {
+                            let (key, value) = crate::template::map_entry(item);
+                            loop_context.insert(
+                                variable,
+                                crate::interface::VariableTy::String.with_data(key),
+                            );
+                            loop_context.insert(
+                                value_variable,
+                                crate::interface::VariableTy::String.with_data(value),
+                            );
+                        } else {
+                            loop_context.insert(
+                                variable,
+                                crate::interface::VariableTy::String.with_data(item.trim()),
+                            );
+                        }
+                        crate::template::insert_loop_metadata(&mut loop_context, index, length);
+
+                        self.collect_block_inclusion_variables(
+                            body,
+                            variables,
+                            &loop_context,
+                            visited,
+                        );
+                    }
+                }
+            }
+            AstNode::Repeat { count, body } => {
+                // Every repetition includes the same templates, so checking the first suffices
+                if *count > 0 {
+                    let mut loop_context = Context::scoped(context);
+                    crate::template::insert_loop_metadata(&mut loop_context, 0, *count);
+                    self.collect_block_inclusion_variables(body, variables, &loop_context, visited);
+                }
+            }
+            AstNode::DebugOnly { body } => {
+                // The body is only rendered, and so only includes anything, when debugging
+                if self.debug {
+                    self.collect_block_inclusion_variables(body, variables, context, visited);
+                }
+            }
+            AstNode::TemplateInclude {
+                template_name,
+                bindings,
+            } => {
+                if bindings.is_empty() {
+                    self.collect_include_variables(template_name, variables, context, visited);
+                } else {
+                    self.collect_bound_include_variables(
+                        template_name,
+                        bindings,
+                        variables,
+                        context,
+                        visited,
+                    );
+                }
+            }
+            AstNode::DynamicInclude { name } => {
+                // We can only follow the include if the context tells us which template it is
+                if let Ok(template_name) =
+                    crate::template::resolve_include_name(name, context, Some(self))
+                {
+                    self.collect_include_variables(&template_name, variables, context, visited);
+                }
+            }
+            // Other node types don't contribute to template inclusion
+            AstNode::Constant { .. }
+            | AstNode::Variable { .. }
+            | AstNode::Filter { .. }
+            | AstNode::Ternary { .. }
+            | AstNode::DebugDump
+            | AstNode::Not { .. }
+            | AstNode::And { .. }
+            | AstNode::Or { .. }
+            | AstNode::Xor { .. }
+            | AstNode::Compare { .. }
+            | AstNode::StringLiteral { .. }
+            | AstNode::Coalesce { .. }
+            | AstNode::BoolLiteral { .. }
+            | AstNode::NumberLiteral { .. }
+            | AstNode::Set { .. }
+            | AstNode::Alias { .. }
+            | AstNode::Break
+            | AstNode::Continue => {}
+        }
+    }
+
+    /// Collects the variables from the template inclusions among the nodes of a block, where
+    /// the nodes after an `{{% alias %}}` see it, as when rendering.
+    fn collect_block_inclusion_variables<'b>(
+        &'b self,
+        children: &'b [AstNode<'a>],
+        variables: &mut Vec<(&'b str, crate::interface::VariableTy)>,
+        context: &Context<'_>,
+        visited: &mut Vec<&'b str>,
+    ) {
+        for (index, child) in children.iter().enumerate() {
+            if let AstNode::Alias { name, target } = child {
+                let mut alias_scope = Context::scoped(context);
+                alias_scope.insert_alias(name, target);
+                let rest = children.get(index.saturating_add(1)..).unwrap_or_default();
+                self.collect_block_inclusion_variables(rest, variables, &alias_scope, visited);
+                return;
+            }
+            self.collect_inclusion_variables(child, variables, context, visited);
+        }
+    }
+
+    // Helper method to collect variables from an included template and its own inclusions
+    fn collect_include_variables<'b>(
+        &'b self,
+        template_name: &str,
+        variables: &mut Vec<(&'b str, crate::interface::VariableTy)>,
+        context: &Context<'_>,
+        visited: &mut Vec<&'b str>,
+    ) {
+        // Only templates that exist can contribute variables
+        let Some((name, included_template)) = self
+            .templates
+            .get_key_value(normalize_name(template_name).as_ref())
+        else {
+            return;
+        };
+
+        // Skip templates that are already being processed further up the include chain to
+        // prevent infinite recursion. Only the current chain is tracked, so the same template
+        // included again elsewhere (e.g. once inside a loop and once outside) is processed
+        // again with the context that applies there.
+        if visited.contains(&name.as_str()) {
+            return;
+        }
+
+        // Mark as visited while processing this template
+        visited.push(name);
+
+        // The template sees the variables in scope where it's included, but not the aliases
+        let unaliased;
+        let context = if context.has_aliases() {
+            unaliased = Context::without_aliases(context);
+            &unaliased
+        } else {
+            context
+        };
+
+        // First collect variables from this template
+        included_template.collect_variables(variables, context);
+
+        // Collect template inclusions through a proper AST traversal
+        self.collect_inclusion_variables(&included_template.ast, variables, context, visited);
+
+        visited.pop();
+    }
+
+    /// Collects the variables required by an include passed `bindings`, whose template
+    /// only sees the bound variables.
+    ///
+    /// A variable the template needs is required from `context` under the name it's bound
+    /// from, as is any bound variable missing from `context`, since the include fails
+    /// without it. Unbound variables the template needs are reported as they are, although
+    /// no context can provide them.
+    fn collect_bound_include_variables<'b>(
+        &'b self,
+        template_name: &str,
+        bindings: &'b [(&'a str, AstNode<'a>)],
+        variables: &mut Vec<(&'b str, crate::interface::VariableTy)>,
+        context: &Context<'_>,
+        visited: &mut Vec<&'b str>,
+    ) {
+        let mut include_context = context.isolated();
+        for (name, value) in bindings {
+            if let Ok(variable) = crate::template::assigned_value(value, context, Some(self)) {
+                include_context.insert(name, variable);
+            }
+        }
+
+        let mut needed = Vec::new();
+        self.collect_include_variables(template_name, &mut needed, &include_context, visited);
+        variables.extend(
+            needed
+                .iter()
+                .filter(|(name, _)| !bindings.iter().any(|(bound, _)| bound == name)),
+        );
+
+        for (name, value) in bindings {
+            if include_context.contains(name) {
+                continue;
+            }
+            if let AstNode::Variable { name: source } = value {
+                let ty = needed
+                    .iter()
+                    .find(|(needed_name, _)| needed_name == name)
+                    .map_or(crate::interface::VariableTy::String, |(_, ty)| *ty);
+                variables.push((source, ty));
+            }
+        }
+    }
+
+    /// Collects the variables required by a template and the templates it includes that
+    /// aren't in `context`, unsorted and possibly with duplicates, or none if the template
+    /// doesn't exist.
+    fn required_variables<'b>(
+        &'b self,
+        template_name: &str,
+        context: &Context<'_>,
+    ) -> Vec<(&'b str, crate::interface::VariableTy)> {
+        let Some(template) = self.templates.get(normalize_name(template_name).as_ref()) else {
+            return Vec::new();
+        };
+
+        let mut variables = Vec::new();
+        let mut visited = Vec::new();
+        let context = crate::template::render_context(context, Some(self));
+        let context = context.as_ref();
+
+        // First collect variables from this template
+        template.collect_variables(&mut variables, context);
+
+        // Collect template inclusions through a proper AST traversal
+        self.collect_inclusion_variables(&template.ast, &mut variables, context, &mut visited);
+
+        variables
+    }
+
+    /// Follows the static includes of `template_name` depth first, returning the first
+    /// include cycle found, starting and ending with the same name.
+    ///
+    /// `chain` holds the templates currently being followed, and `checked` the templates
+    /// already known not to lead to a cycle.
+    fn find_include_cycle<'b>(
+        &'b self,
+        template_name: &str,
+        chain: &mut Vec<&'b str>,
+        checked: &mut Vec<&'b str>,
+    ) -> Option<Vec<String>> {
+        let (name, template) = self
+            .templates
+            .get_key_value(normalize_name(template_name).as_ref())?;
+
+        if let Some(start) = chain.iter().position(|entry| *entry == name) {
+            let mut cycle = chain
+                .get(start..)
+                .unwrap_or_default()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            cycle.push(name.clone());
+            return Some(cycle);
+        }
+        if checked.contains(&name.as_str()) {
+            return None;
+        }
+
+        chain.push(name);
+        for include in template.static_inclusions() {
+            if let Some(cycle) = self.find_include_cycle(include, chain, checked) {
+                return Some(cycle);
+            }
+        }
+        chain.pop();
+        checked.push(name);
+        None
+    }
+
+    /// Parses `content` into a template, honouring
+    /// [`MinilateEngine::set_preserve_unknown_directives`] and
+    /// [`MinilateEngine::set_delimiters`]
+    fn parse_template(&self, content: String) -> MinilateResult<Template<'a>> {
+        Template::parse(
+            content.into(),
+            self.preserve_unknown_directives,
+            &self.delimiters,
+            self.max_template_length,
+        )
+    }
+
+    /// Adds a template, replacing any existing template registered under the same name.
+    ///
+    /// Unlike [`MinilateInterface::add_template`] this never fails with `TemplateExists`,
+    /// and unlike [`MinilateEngine::reload_template`] the template doesn't need to exist
+    /// already. If the content fails to parse, any existing template is left untouched.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(template))` with the replaced template, if one existed
+    /// * `Ok(None)` if the template was newly added
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::Parse)` if the content contains syntax errors
+    /// * `Err(MinilateError::SelfInclude)` if the content includes the template itself
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// assert!(engine.upsert_template("greeting", "Hello!").unwrap().is_none());
+    /// assert!(engine.upsert_template("greeting", "Hi there!").unwrap().is_some());
+    ///
+    /// assert_eq!(engine.render("greeting", None).unwrap(), "Hi there!");
+    /// ```
+    pub fn upsert_template<'b, N: AsRef<str>, C: Into<Cow<'b, str>>>(
+        &mut self,
+        name: N,
+        content: C,
+    ) -> MinilateResult<Option<Template<'a>>> {
+        let name = normalize_name(name.as_ref());
+
+        let mut template = self.parse_template(content.into().to_string())?;
+        check_self_include(&name, &template)?;
+        template.name = Some(name.to_string());
+
+        Ok(self.templates.insert(name.to_string(), template))
+    }
+}
+
+impl MinilateEngine<'_> {
+    /// Creates a new empty `MinilateEngine` instance.
+    ///
+    /// # Returns
+    ///
+    /// A new engine with no templates.
+    pub fn new() -> Self {
+        Self {
+            templates: HashMap::new(),
+            escaper: Escaper::None,
+            debug: false,
+            bool_labels: None,
+            dotted_fallback: false,
+            missing_policy: MissingPolicy::Error,
+            filters: HashMap::new(),
+            renderers: HashMap::new(),
+            type_renderers: HashMap::new(),
+            preserve_unknown_directives: false,
+            case_insensitive_vars: false,
+            iterable_separator: None,
+            delimiters: DelimiterConfig::default(),
+            max_template_length: None,
+        }
+    }
+
+    /// Enables or disables `{{% debug %}}` directives.
+    ///
+    /// When enabled, each `{{% debug %}}` renders a dump of every variable in scope,
+    /// one `name: Type = "value"` per line. When disabled (the default) they render
+    /// as nothing, so they can safely be left in production templates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("page", "{{% debug %}}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("World"));
+    /// assert_eq!(engine.render("page", Some(&context)).unwrap(), "");
+    ///
+    /// engine.set_debug(true);
+    /// assert_eq!(
+    ///     engine.render("page", Some(&context)).unwrap(),
+    ///     "name: String = \"World\"\n"
+    /// );
+    /// ```
+    pub const fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    /// Sets the [`Escaper`] applied to every `{{ variable }}` output, including
+    /// variables in included templates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, Escaper, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.set_escaper(Escaper::Html);
+    /// engine.add_template("bold", "<b>{{ text }}</b>").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("text", VariableTy::String.with_data("1 < 2"));
+    ///
+    /// assert_eq!(engine.render("bold", Some(&context)).unwrap(), "<b>1 &lt; 2</b>");
+    /// ```
+    pub const fn set_escaper(&mut self, escaper: Escaper) {
+        self.escaper = escaper;
+    }
+
+    /// Sets the [`MissingPolicy`] for `{{ variable }}` outputs whose variable is missing
+    /// from the context, including in included templates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface, MissingPolicy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("card", "{{ user.name }} <{{ user.email }}>").unwrap();
+    /// assert!(engine.render("card", None).is_err());
+    ///
+    /// engine.set_missing_policy(MissingPolicy::NamedPlaceholder("[{name}]".to_string()));
+    /// assert_eq!(engine.render("card", None).unwrap(), "[user.name] <[user.email]>");
+    /// ```
+    pub fn set_missing_policy(&mut self, missing_policy: MissingPolicy) {
+        self.missing_policy = missing_policy;
+    }
+
+    /// Returns this engine set to render missing variables according to `mode`.
+    ///
+    /// In [`RenderMode::Lenient`] mode, a `{{ variable }}` that is missing from the
+    /// context, or has no data, renders as an empty string rather than failing. This
+    /// replaces any policy set with [`MinilateEngine::set_missing_policy`], and the
+    /// default is [`RenderMode::Strict`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface, RenderMode};
+    ///
+    /// let mut engine = MinilateEngine::new().with_mode(RenderMode::Lenient);
+    /// engine.add_template("greeting", "Hello, {{ name }}!").unwrap();
+    /// assert_eq!(engine.render("greeting", None).unwrap(), "Hello, !");
+    /// ```
+    #[must_use]
+    pub fn with_mode(mut self, mode: RenderMode) -> Self {
+        self.missing_policy = mode.into();
+        self
+    }
+
+    /// Enables or disables keeping `{{% ... %}}` directives with an unknown keyword as
+    /// literal text in templates added from now on, rather than failing to parse them.
+    ///
+    /// This lets documents mix Minilate with other systems that also use `{{% %}}`
+    /// directives. Templates that were already added are unaffected. Disabled by default,
+    /// so typos such as `{{% fi %}}` are caught.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// assert!(engine.add_template("strict", "{{% block body %}}").is_err());
+    ///
+    /// engine.set_preserve_unknown_directives(true);
+    /// engine.add_template("page", "{{% block body %}}").unwrap();
+    /// assert_eq!(engine.render("page", None).unwrap(), "{{% block body %}}");
+    /// ```
+    pub const fn set_preserve_unknown_directives(&mut self, preserve_unknown_directives: bool) {
+        self.preserve_unknown_directives = preserve_unknown_directives;
+    }
+
+    /// Sets the delimiters that templates added from now on are written with, instead of
+    /// `{{ }}`, `{{% %}}`, `{{<< }}` and `{{# #}}`.
+    ///
+    /// Templates that were already added are unaffected. Adding a template fails with a
+    /// parse error if the delimiters are ambiguous, e.g. because one of them is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, DelimiterConfig, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.set_delimiters(DelimiterConfig {
+    ///     open: "<%".to_string(),
+    ///     close: "%>".to_string(),
+    ///     block: "@".to_string(),
+    ///     ..DelimiterConfig::default()
+    /// });
+    /// engine.add_template("page", "{{ kept }} <%@ if name @%>Hi <% name %><%@ endif @%>").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("Ada"));
+    /// assert_eq!(engine.render("page", Some(&context)).unwrap(), "{{ kept }} Hi Ada");
+    /// ```
+    pub fn set_delimiters(&mut self, delimiters: DelimiterConfig) {
+        self.delimiters = delimiters;
+    }
+
+    /// Sets the maximum length in bytes of templates added from now on, or `None` (the
+    /// default) for no limit.
+    ///
+    /// Longer templates are rejected with [`crate::ParseErrorKind::InputTooLong`] before
+    /// being parsed, guarding services that accept templates from users against huge
+    /// inputs. Templates that were already added are unaffected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateError, MinilateInterface, ParseErrorKind};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.set_max_template_length(Some(16));
+    /// engine.add_template("short", "Hi {{ name }}").unwrap();
+    ///
+    /// let err = engine.add_template("long", "Hello there, {{ name }}!").unwrap_err();
+    /// assert!(matches!(
+    ///     err,
+    ///     MinilateError::Parse(ref e) if matches!(e.kind, ParseErrorKind::InputTooLong { .. })
+    /// ));
+    /// ```
+    pub const fn set_max_template_length(&mut self, max_template_length: Option<usize>) {
+        self.max_template_length = max_template_length;
+    }
+
+    /// Registers a filter applied to the output of `{{ variable | name }}`, alongside the
+    /// built-in `upper`, `lower`, `trim` and `default` filters.
+    ///
+    /// Filters chain left to right, so `{{ name | trim | shout }}` trims before calling
+    /// `shout`. A registered filter takes precedence over a built-in of the same name, and
+    /// registering a name again replaces the earlier filter. Filters must be `Send` and
+    /// `Sync`, so the engine can still be shared between threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.register_filter("shout", Box::new(|value| format!("{value}!")));
+    /// engine.add_template("greeting", "{{ name | upper | shout }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("hello"));
+    ///
+    /// assert_eq!(engine.render("greeting", Some(&context)).unwrap(), "HELLO!");
+    /// ```
+    pub fn register_filter<N: Into<String>>(
+        &mut self,
+        name: N,
+        filter: Box<dyn Fn(&str) -> String + Send + Sync>,
+    ) {
+        self.filters.insert(name.into(), filter);
+    }
+
+    /// Registers a renderer producing the output of `{{ name }}` for the variable `name`,
+    /// in place of its data.
+    ///
+    /// This gives app-specific formatting, such as currency symbols, without changing
+    /// templates. A renderer for a variable's name takes precedence over one for its type
+    /// (see [`MinilateEngine::set_type_renderer`]) and over boolean labels, and its output
+    /// is still escaped. Renderers only apply to plain `{{ name }}` output: filters and
+    /// conditions see the variable's data. Registering a name again replaces the earlier
+    /// renderer. Renderers must be `Send` and `Sync`, so the engine can still be shared
+    /// between threads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.set_renderer("price", |v| format!("${}", v.data().unwrap_or("0")));
+    /// engine.add_template("item", "{{ name }}: {{ price }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("Tea"));
+    /// context.insert("price", VariableTy::Number.with_data("4.50"));
+    /// assert_eq!(engine.render("item", Some(&context)).unwrap(), "Tea: $4.50");
+    /// ```
+    pub fn set_renderer<N, F>(&mut self, name: N, renderer: F)
+    where
+        N: Into<String>,
+        F: Fn(&Variable<'_>) -> String + Send + Sync + 'static,
+    {
+        self.renderers.insert(name.into(), Box::new(renderer));
+    }
+
+    /// Registers a renderer producing the output of `{{ name }}` for every variable of
+    /// type `ty` that has no renderer of its own.
+    ///
+    /// See [`MinilateEngine::set_renderer`] for how renderers apply.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.set_type_renderer(VariableTy::Iterable, |v| {
+    ///     v.data().unwrap_or_default().replace(',', " /")
+    /// });
+    /// engine.add_template("path", "{{ crumbs }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("crumbs", VariableTy::Iterable.with_data("home,docs"));
+    /// assert_eq!(engine.render("path", Some(&context)).unwrap(), "home /docs");
+    /// ```
+    pub fn set_type_renderer<F>(&mut self, ty: VariableTy, renderer: F)
+    where
+        F: Fn(&Variable<'_>) -> String + Send + Sync + 'static,
+    {
+        self.type_renderers.insert(ty, Box::new(renderer));
+    }
+
+    /// Enables or disables falling back to shorter names for missing dotted variables.
+    ///
+    /// When enabled, a `{{ user.profile.name }}` missing from the context is looked up as
+    /// `user.name`, and then as `name`, removing the segments nearest the final one until
+    /// a variable is found. The longest name present always wins. This helps when a
+    /// context is only partially nested. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("card", "{{ user.profile.name }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("user.name", VariableTy::String.with_data("Alice"));
+    /// assert!(engine.render("card", Some(&context)).is_err());
+    ///
+    /// engine.set_dotted_fallback(true);
+    /// assert_eq!(engine.render("card", Some(&context)).unwrap(), "Alice");
+    /// ```
+    pub const fn set_dotted_fallback(&mut self, dotted_fallback: bool) {
+        self.dotted_fallback = dotted_fallback;
+    }
+
+    /// Sets the separator between the items of [`crate::VariableTy::Iterable`] and
+    /// [`crate::VariableTy::Map`] data, in place of the default `","`.
+    ///
+    /// This is useful when items may themselves contain commas, such as addresses, or for
+    /// newline-delimited lists with `"\n"`. The separator is used wherever data is split
+    /// into items: by for loops and their `{{% empty %}}` branches, by `contains` in
+    /// conditions, and by indexing such as `{{ items.0 }}`. Whether an iterable is empty
+    /// in a condition like `{{% if items %}}` doesn't depend on it. Items are trimmed of
+    /// surrounding whitespace as usual. An empty separator restores the default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.set_iterable_separator("\n");
+    /// engine
+    ///     .add_template("list", "{{% for line in lines %}}[{{ line }}]{{% endfor %}}")
+    ///     .unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("lines", VariableTy::Iterable.with_data("1 Main St, Springfield\nElm Rd"));
+    ///
+    /// assert_eq!(
+    ///     engine.render("list", Some(&context)).unwrap(),
+    ///     "[1 Main St, Springfield][Elm Rd]"
+    /// );
+    /// ```
+    pub fn set_iterable_separator<S: Into<String>>(&mut self, separator: S) {
+        self.iterable_separator = Some(separator.into()).filter(|separator| !separator.is_empty());
+    }
+
+    /// Enables or disables matching variable names regardless of case, for data sources
+    /// with inconsistent casing.
+    ///
+    /// When enabled, `{{ UserName }}`, `{{% if UserName %}}` and loops all find a variable
+    /// named `username`, as does [`MinilateInterface::context`] when checking which
+    /// variables are already provided. A variable whose name matches exactly always wins.
+    /// Otherwise, if several names differ only by case, the first in byte order is used,
+    /// so `UserName` is preferred over `username`. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello, {{ UserName }}!").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("username", VariableTy::String.with_data("Ada"));
+    /// assert!(engine.render("greeting", Some(&context)).is_err());
+    ///
+    /// engine.set_case_insensitive_vars(true);
+    /// assert_eq!(engine.render("greeting", Some(&context)).unwrap(), "Hello, Ada!");
+    /// ```
+    pub const fn set_case_insensitive_vars(&mut self, case_insensitive_vars: bool) {
+        self.case_insensitive_vars = case_insensitive_vars;
+    }
+
+    /// Sets the labels output when a [`crate::VariableTy::Boolean`] variable is rendered
+    /// with `{{ variable }}`, instead of its raw data.
+    ///
+    /// A value renders as `true_label` if it would be true in an `{{% if %}}` condition,
+    /// and as `false_label` otherwise. Filtered values such as `{{ flag | trim }}` are
+    /// rendered verbatim.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.set_bool_labels("Yes", "No");
+    /// engine.add_template("status", "Subscribed: {{ subscribed }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("subscribed", VariableTy::Boolean.with_data("true"));
+    ///
+    /// assert_eq!(engine.render("status", Some(&context)).unwrap(), "Subscribed: Yes");
+    /// ```
+    pub fn set_bool_labels<T: Into<String>, F: Into<String>>(
+        &mut self,
+        true_label: T,
+        false_label: F,
+    ) {
+        self.bool_labels = Some((true_label.into(), false_label.into()));
+    }
+
+    /// Adds a new template from raw bytes, validating that they are UTF-8.
+    ///
+    /// This is useful when template content comes straight from a file or the
+    /// network and has not yet been decoded.
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::InvalidUtf8)` if `bytes` is not valid UTF-8
+    /// * Any error returned by [`MinilateInterface::add_template`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateError};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template_bytes("greeting", b"Hello, {{ name }}!").unwrap();
+    ///
+    /// let result = engine.add_template_bytes("broken", &[0x48, 0xff, 0x49]);
+    /// assert!(matches!(result, Err(MinilateError::InvalidUtf8 { valid_up_to: 1, .. })));
+    /// ```
+    pub fn add_template_bytes<N: AsRef<str>>(
+        &mut self,
+        name: N,
+        bytes: &[u8],
+    ) -> MinilateResult<()> {
+        let content = std::str::from_utf8(bytes).map_err(|e| MinilateError::InvalidUtf8 {
+            template_name: name.as_ref().to_string(),
+            valid_up_to: e.valid_up_to(),
+        })?;
+
+        self.add_template(name, content)
+    }
+
+    /// Adds several templates at once, either adding all of them or none.
+    ///
+    /// Every template is parsed and checked before any are inserted, so if one name is
+    /// already taken (including twice within the batch) or one template fails to parse,
+    /// the engine is left exactly as it was.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error encountered, in iteration order:
+    ///
+    /// * `Err(MinilateError::TemplateExists)` if a name is already registered or repeated in the batch
+    /// * `Err(MinilateError::Parse)` if a template's content contains syntax errors
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// let result = engine.add_templates_atomic([
+    ///     ("header", "Welcome!"),
+    ///     ("broken", "{{ unclosed"),
+    /// ]);
+    ///
+    /// assert!(result.is_err());
+    /// assert!(engine.render("header", None).is_err());
+    /// ```
+    pub fn add_templates_atomic<'b, I, N, C>(&mut self, templates: I) -> MinilateResult<()>
+    where
+        I: IntoIterator<Item = (N, C)>,
+        N: AsRef<str>,
+        C: Into<Cow<'b, str>>,
+    {
+        let mut parsed: Vec<(String, Template<'_>)> = Vec::new();
+
+        for (name, content) in templates {
+            let name = normalize_name(name.as_ref()).into_owned();
+
+            if self.templates.contains_key(&name)
+                || parsed.iter().any(|(existing, _)| *existing == name)
+            {
+                return Err(MinilateError::TemplateExists {
+                    template_name: name,
+                });
+            }
+
+            let mut template = self.parse_template(content.into().to_string())?;
+            check_self_include(&name, &template)?;
+            template.name = Some(name.clone());
+            parsed.push((name, template));
+        }
+
+        self.templates.extend(parsed);
+
+        Ok(())
+    }
+
+    /// Adds a template for every `.tmpl` file directly inside the directory at `path`,
+    /// named after the file without its extension.
+    ///
+    /// A file `header.tmpl` is added as `header`, which `{{<< header }}` and
+    /// `{{<< header.tmpl }}` both include, so templates can include each other by file
+    /// name. Subdirectories and files with other extensions are ignored. Like
+    /// [`MinilateEngine::add_templates_atomic`], either every template is added, or none
+    /// are if any fails.
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::Io)` if the directory or one of its files can't be read
+    /// * `Err(MinilateError::TemplateFile)` with the path of the file, wrapping
+    ///   `MinilateError::Parse` if the file contains syntax errors, or
+    ///   `MinilateError::TemplateExists` if a template with its name already exists
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// // With `templates/page.tmpl` containing `{{<< header.tmpl }}...`
+    /// engine.add_templates_from_dir("templates").unwrap();
+    ///
+    /// let output = engine.render("page", None).unwrap();
+    /// ```
+    pub fn add_templates_from_dir<P: AsRef<Path>>(&mut self, path: P) -> MinilateResult<()> {
+        let path = path.as_ref();
+        let io_error = |path: &Path, error: std::io::Error| MinilateError::Io {
+            path: path.display().to_string(),
+            message: error.to_string(),
+        };
+
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path).map_err(|error| io_error(path, error))? {
+            let file = entry.map_err(|error| io_error(path, error))?.path();
+            if file.is_file()
+                && file
+                    .extension()
+                    .is_some_and(|extension| extension == "tmpl")
+            {
+                files.push(file);
+            }
+        }
+        // Read in a consistent order, so the same file is reported for the same problem
+        files.sort();
+
+        let mut parsed: Vec<(String, Template<'_>)> = Vec::new();
+        for file in files {
+            let in_file = |error: MinilateError| MinilateError::TemplateFile {
+                path: file.display().to_string(),
+                error: Box::new(error),
+            };
+            let name = normalize_name(&file.file_stem().unwrap_or_default().to_string_lossy())
+                .into_owned();
+
+            if self.templates.contains_key(&name)
+                || parsed.iter().any(|(existing, _)| *existing == name)
+            {
+                return Err(in_file(MinilateError::TemplateExists {
+                    template_name: name,
+                }));
+            }
+
+            let content = std::fs::read_to_string(&file).map_err(|error| io_error(&file, error))?;
+            let mut template = self.parse_template(content).map_err(in_file)?;
+            check_self_include(&name, &template).map_err(in_file)?;
+            template.name = Some(name.clone());
+            parsed.push((name, template));
+        }
+
+        self.templates.extend(parsed);
+
+        Ok(())
+    }
+
+    /// Renames a registered template from `old` to `new`.
+    ///
+    /// Note that this only changes the name the template is registered under.
+    /// Other templates that include it via `{{<< old }}` are *not* rewritten, and
+    /// will fail to render with `MissingTemplate` until they are updated.
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::MissingTemplate)` if no template named `old` exists
+    /// * `Err(MinilateError::TemplateExists)` if another template named `new` already
+    ///   exists
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello!").unwrap();
+    /// engine.rename_template("greeting", "welcome").unwrap();
+    ///
+    /// assert_eq!(engine.render("welcome", None).unwrap(), "Hello!");
+    /// assert!(engine.render("greeting", None).is_err());
+    /// ```
+    pub fn rename_template<O: AsRef<str>, N: AsRef<str>>(
+        &mut self,
+        old: O,
+        new: N,
+    ) -> MinilateResult<()> {
+        let old = normalize_name(old.as_ref());
+        let new = normalize_name(new.as_ref());
+
+        if !self.templates.contains_key(old.as_ref()) {
+            return Err(MinilateError::MissingTemplate {
+                template_name: old.to_string(),
+            });
+        }
+        // Renaming a template to its own name leaves it as it is
+        if old == new {
+            return Ok(());
+        }
+        if self.templates.contains_key(new.as_ref()) {
+            return Err(MinilateError::TemplateExists {
+                template_name: new.to_string(),
+            });
+        }
+
+        let mut template =
+            self.templates
+                .remove(old.as_ref())
+                .ok_or_else(|| MinilateError::MissingTemplate {
+                    template_name: old.to_string(),
+                })?;
+        template.name = Some(new.to_string());
+
+        self.templates.insert(new.to_string(), template);
+
+        Ok(())
+    }
+
+    /// Re-parses an existing template from `content`, keeping the name it is registered under.
+    ///
+    /// This is the counterpart to [`MinilateInterface::add_template`] for hot-reload workflows:
+    /// it fails if the template does *not* already exist. If the new content fails to parse,
+    /// the previously registered template is left untouched.
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::MissingTemplate)` if no template with the given name exists
+    /// * `Err(MinilateError::Parse)` if the new content contains syntax errors
+    /// * `Err(MinilateError::SelfInclude)` if the new content includes the template itself
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello!").unwrap();
+    /// engine.reload_template("greeting", "Hi there!").unwrap();
+    ///
+    /// assert_eq!(engine.render("greeting", None).unwrap(), "Hi there!");
+    /// ```
+    pub fn reload_template<'b, N: AsRef<str>, C: Into<Cow<'b, str>>>(
+        &mut self,
+        name: N,
+        content: C,
+    ) -> MinilateResult<()> {
+        let name = normalize_name(name.as_ref());
+
+        if !self.templates.contains_key(name.as_ref()) {
+            return Err(MinilateError::MissingTemplate {
+                template_name: name.to_string(),
+            });
+        }
+
+        let mut template = self.parse_template(content.into().to_string())?;
+        check_self_include(&name, &template)?;
+        template.name = Some(name.to_string());
+        self.templates.insert(name.to_string(), template);
+
+        Ok(())
+    }
+
+    /// Reports whether rendering a template with `new` would produce different output
+    /// than rendering it with `old`.
+    ///
+    /// Both contexts are rendered in full and the results compared, so a change in
+    /// whether rendering succeeds (or which error it fails with) also counts as a change.
+    /// Variables the template never reads have no effect on the result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello, {{ name }}!").unwrap();
+    ///
+    /// let mut old = Context::new();
+    /// old.insert("name", VariableTy::String.with_data("World"));
+    ///
+    /// let mut new = old.clone();
+    /// new.insert("unused", VariableTy::String.with_data("ignored"));
+    /// assert!(!engine.would_change("greeting", &old, &new));
+    ///
+    /// new.insert("name", VariableTy::String.with_data("Everyone"));
+    /// assert!(engine.would_change("greeting", &old, &new));
+    /// ```
+    pub fn would_change<N: AsRef<str>>(
+        &self,
+        template_name: N,
+        old: &Context<'_>,
+        new: &Context<'_>,
+    ) -> bool {
+        let name = template_name.as_ref();
+
+        self.render(name, Some(old)) != self.render(name, Some(new))
+    }
+
+    /// Validates every registered template by rendering it with `context`.
+    ///
+    /// Unlike rendering templates one at a time, this doesn't stop at the first failure:
+    /// every template is checked, and all failures are reported together as
+    /// `(template name, error)` pairs, sorted by template name. This makes it a convenient
+    /// check for a whole repository of templates, e.g. in CI.
+    ///
+    /// # Errors
+    ///
+    /// Returns every template that fails to render, with the error it failed with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateError, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello, {{ name }}!").unwrap();
+    /// engine.add_template("farewell", "Bye, {{ nickname }}!").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("World"));
+    ///
+    /// let failures = engine.validate_all(&context).unwrap_err();
+    /// assert_eq!(failures.len(), 1);
+    /// assert_eq!(failures[0].0, "farewell");
+    /// ```
+    pub fn validate_all(&self, context: &Context<'_>) -> Result<(), Vec<(String, MinilateError)>> {
+        let mut failures = self
+            .templates
+            .keys()
+            .filter_map(|name| {
+                self.render(name, Some(context))
+                    .err()
+                    .map(|error| (name.clone(), error))
+            })
+            .collect::<Vec<_>>();
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        failures.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Err(failures)
+    }
+
+    /// Iterates over the variables required by a template that aren't already in
+    /// `context`, as [`MinilateInterface::context`] does, but without sorting them.
+    ///
+    /// Variables are yielded in the order they are found, each once with the type inferred
+    /// from its first use, which suits callers that only need to visit each variable.
+    /// Yields nothing if the template doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine
+    ///     .add_template("greeting", "{{ name }}, {{% if vip %}}{{ name }}{{% endif %}}")
+    ///     .unwrap();
+    ///
+    /// for (name, ty) in engine.context_iter("greeting", &Context::new()) {
+    ///     println!("{name}: {ty:?}");
+    /// }
+    /// assert_eq!(engine.context_iter("greeting", &Context::new()).count(), 2);
+    /// ```
+    pub fn context_iter<'b, N: AsRef<str>>(
+        &'b self,
+        template_name: N,
+        context: &Context<'_>,
+    ) -> impl Iterator<Item = (&'b str, crate::interface::VariableTy)> + 'b {
+        let mut seen = HashSet::new();
+        self.required_variables(template_name.as_ref(), context)
+            .into_iter()
+            .filter(move |(name, _)| seen.insert(*name))
+    }
+
+    /// Checks that every template only includes templates that exist, and that no
+    /// templates include each other in a cycle.
+    ///
+    /// Only includes written as a name, such as `{{<< footer }}`, are checked, in every
+    /// branch and loop regardless of context. Dynamic includes can't be checked, as their
+    /// names depend on the context.
+    ///
+    /// # Errors
+    ///
+    /// * `MinilateError::MissingIncludes` listing every include of a missing template,
+    ///   sorted by the including template
+    /// * `MinilateError::RecursionLimit` with the first include cycle found
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateError, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("page", "{{<< header }} Hello {{<< footer }}").unwrap();
+    /// engine.add_template("header", "# Title").unwrap();
+    ///
+    /// assert_eq!(
+    ///     engine.validate(),
+    ///     Err(MinilateError::MissingIncludes {
+    ///         includes: vec![("page".to_string(), "footer".to_string())]
+    ///     })
+    /// );
+    ///
+    /// engine.add_template("footer", "Bye").unwrap();
+    /// assert_eq!(engine.validate(), Ok(()));
+    /// ```
+    pub fn validate(&self) -> MinilateResult<()> {
+        let mut templates = self.templates.iter().collect::<Vec<_>>();
+        templates.sort_by_key(|(name, _)| *name);
+
+        let mut missing = Vec::new();
+        for (name, template) in &templates {
+            for include in template.static_inclusions() {
+                if !self
+                    .templates
+                    .contains_key(normalize_name(include).as_ref())
+                {
+                    missing.push(((*name).clone(), include.to_string()));
+                }
+            }
+        }
+        if !missing.is_empty() {
+            return Err(MinilateError::MissingIncludes { includes: missing });
+        }
+
+        let mut checked = Vec::new();
+        for (name, _) in templates {
+            if let Some(chain) = self.find_include_cycle(name, &mut Vec::new(), &mut checked) {
+                return Err(MinilateError::RecursionLimit { chain });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the source of the template with the given name, exactly as it was added,
+    /// or `None` if no such template exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello, {{ name }}!").unwrap();
+    ///
+    /// assert_eq!(engine.template_source("greeting"), Some("Hello, {{ name }}!"));
+    /// assert_eq!(engine.template_source("farewell"), None);
+    /// ```
+    pub fn template_source<N: AsRef<str>>(&self, template_name: N) -> Option<&str> {
+        self.templates
+            .get(normalize_name(template_name.as_ref()).as_ref())
+            .map(Template::source)
+    }
+
+    /// Renders a template, or the `fallback_name` template if no template named
+    /// `template_name` exists.
+    ///
+    /// Only a missing `template_name` triggers the fallback; any other error, including
+    /// a template it includes being missing, is returned as is.
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::MissingTemplate)` if neither template exists
+    /// * Any error from [`MinilateInterface::render`] for the template being rendered
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("not_found", "Page not found").unwrap();
+    ///
+    /// assert_eq!(engine.render_or("about", "not_found", None).unwrap(), "Page not found");
+    /// ```
+    pub fn render_or<N: AsRef<str>, F: AsRef<str>>(
+        &self,
+        template_name: N,
+        fallback_name: F,
+        context: Option<&Context<'_>>,
+    ) -> MinilateResult<String> {
+        let name = normalize_name(template_name.as_ref());
+        if self.templates.contains_key(name.as_ref()) {
+            self.render(name, context)
+        } else {
+            self.render(fallback_name, context)
+        }
+    }
 
-                // When the iterable has items in the context, we should process the body
-                // and no longer need to collect the iterable variable (since it's available)
-                if has_items {
-                    // When the loop has items, we create a simulated context
-                    // This creates a simulated context that assumes the loop variable exists
-                    let mut loop_context = context.clone();
-                    loop_context.insert(
-                        variable,
-                        crate::interface::VariableTy::String.with_data("dummy"),
-                    );
+    /// Renders a template like [`MinilateInterface::render`], but returns a [`RenderedDoc`]
+    /// that also records the variables and included templates the output was built from.
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::MissingTemplate)` if the template doesn't exist
+    /// * Any error from [`MinilateInterface::render`] for the template
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("footer", "-- {{ author }}").unwrap();
+    /// engine.add_template("post", "{{ text }}\n{{<< footer }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("text", VariableTy::String.with_data("Hi"));
+    /// context.insert("author", VariableTy::String.with_data("Ada"));
+    ///
+    /// let doc = engine.render_capture("post", &context).unwrap();
+    /// assert_eq!(doc.output, "Hi\n-- Ada");
+    /// assert_eq!(doc.variables, ["author", "text"]);
+    /// assert_eq!(doc.includes, ["footer"]);
+    /// ```
+    pub fn render_capture<N: AsRef<str>>(
+        &self,
+        template_name: N,
+        context: &Context<'_>,
+    ) -> MinilateResult<RenderedDoc> {
+        let name = normalize_name(template_name.as_ref());
+        let template =
+            self.templates
+                .get(name.as_ref())
+                .ok_or_else(|| MinilateError::MissingTemplate {
+                    template_name: name.to_string(),
+                })?;
 
-                    // Process the body with the augmented context
-                    for child in body {
-                        self.collect_inclusion_variables(child, variables, &loop_context, visited);
-                    }
-                }
-            }
-            AstNode::TemplateInclude { template_name } => {
-                // Skip if already visited to prevent infinite recursion
-                // XXX: What about if the context changes between two different includes (e.g. one in a loop)?
-                if visited.contains(template_name) {
-                    return;
-                }
+        template.render_capture(context, Some(self))
+    }
+
+    /// Renders a template with variables drawn from several contexts, such as those of a
+    /// request, a session and the whole site.
+    ///
+    /// Each variable is looked up in `contexts` in order, so a variable in an earlier
+    /// context hides one with the same name in a later context. The contexts are searched
+    /// in place rather than merged into a single context first.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`MinilateInterface::render`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "{{ greeting }}, {{ name }}!").unwrap();
+    ///
+    /// let mut request = Context::new();
+    /// request.insert("name", VariableTy::String.with_data("Ada"));
+    /// let mut site = Context::new();
+    /// site.insert("greeting", VariableTy::String.with_data("Welcome"))
+    ///     .insert("name", VariableTy::String.with_data("guest"));
+    ///
+    /// assert_eq!(
+    ///     engine.render_layered("greeting", &[&request, &site]).unwrap(),
+    ///     "Welcome, Ada!"
+    /// );
+    /// ```
+    pub fn render_layered<N: AsRef<str>>(
+        &self,
+        template_name: N,
+        contexts: &[&Context<'_>],
+    ) -> MinilateResult<String> {
+        let context = Context::layered(contexts);
+        self.render(template_name, Some(&context))
+    }
 
-                // Mark as visited
-                visited.push(template_name);
+    /// Renders a template like [`MinilateInterface::render`], but abandons the render with
+    /// `MinilateError::Cancelled` as soon as `cancel` is set.
+    ///
+    /// This lets a server give up on a slow render, e.g. by setting the flag from another
+    /// thread once a deadline passes. See [`Template::render_with_cancel`].
+    ///
+    /// # Errors
+    ///
+    /// * `Err(MinilateError::Cancelled)` if `cancel` was set before the render finished
+    /// * Any error from [`MinilateInterface::render`] for the template
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::AtomicBool;
+    /// use minilate::{MinilateEngine, MinilateError, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello!").unwrap();
+    ///
+    /// let cancel = AtomicBool::new(false);
+    /// assert_eq!(engine.render_with_cancel("greeting", None, &cancel).unwrap(), "Hello!");
+    ///
+    /// let cancel = AtomicBool::new(true);
+    /// assert!(matches!(
+    ///     engine.render_with_cancel("greeting", None, &cancel),
+    ///     Err(MinilateError::Cancelled)
+    /// ));
+    /// ```
+    pub fn render_with_cancel<N: AsRef<str>>(
+        &self,
+        template_name: N,
+        context: Option<&Context<'_>>,
+        cancel: &AtomicBool,
+    ) -> MinilateResult<String> {
+        let name = normalize_name(template_name.as_ref());
+        let template =
+            self.templates
+                .get(name.as_ref())
+                .ok_or_else(|| MinilateError::MissingTemplate {
+                    template_name: name.to_string(),
+                })?;
 
-                // If template exists, collect variables from it recursively
-                if let Some(included_template) = self.templates.get(*template_name) {
-                    // First collect variables from this template
-                    included_template.collect_variables(variables, context);
+        let default_context = Context::default();
+        let context = context.unwrap_or(&default_context);
 
-                    // Collect template inclusions through a proper AST traversal
-                    self.collect_inclusion_variables(
-                        &included_template.ast,
-                        variables,
-                        context,
-                        visited,
-                    );
+        template.render_with_cancel(context, Some(self), cancel)
+    }
+
+    /// Renders a template and compares the output against `expected`, as in golden tests.
+    ///
+    /// # Errors
+    ///
+    /// Returns a human-readable message if rendering fails, or if the output differs from
+    /// `expected`, in which case the message is a unified diff from `expected` to the
+    /// actual output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("list", "Items:\n{{ first }}\n{{ second }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("first", VariableTy::String.with_data("apple"));
+    /// context.insert("second", VariableTy::String.with_data("pear"));
+    ///
+    /// assert!(engine.diff_render("list", Some(&context), "Items:\napple\npear").is_ok());
+    /// assert_eq!(
+    ///     engine.diff_render("list", Some(&context), "Items:\napple\nplum").unwrap_err(),
+    ///     "--- expected\n+++ actual\n@@ -1,3 +1,3 @@\n Items:\n apple\n-plum\n+pear\n"
+    /// );
+    /// ```
+    pub fn diff_render<N: AsRef<str>>(
+        &self,
+        template_name: N,
+        context: Option<&Context<'_>>,
+        expected: &str,
+    ) -> Result<(), String> {
+        let actual = self
+            .render(template_name, context)
+            .map_err(|error| error.to_string())?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(crate::diff::unified_diff(expected, &actual))
+        }
+    }
+
+    /// Renders a template and writes the output to the file at `path`.
+    ///
+    /// The output is first written to a temporary file alongside `path`, which is then
+    /// renamed over it. Readers of `path` therefore see either the old contents or the
+    /// complete new output, never a partially written file.
+    ///
+    /// # Errors
+    ///
+    /// * Any error from [`MinilateInterface::render`], in which case `path` is not touched
+    /// * `Err(MinilateError::Io)` if writing or renaming the file fails
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("readme", "# {{ project }}").unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("project", VariableTy::String.with_data("Minilate"));
+    ///
+    /// engine.render_to_file("readme", Some(&context), "README.md").unwrap();
+    /// ```
+    pub fn render_to_file<N: AsRef<str>, P: AsRef<Path>>(
+        &self,
+        template_name: N,
+        context: Option<&Context<'_>>,
+        path: P,
+    ) -> MinilateResult<()> {
+        let path = path.as_ref();
+        let output = self.render(template_name, context)?;
+
+        let mut temp_name = OsString::from(".");
+        temp_name.push(path.file_name().unwrap_or_default());
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        std::fs::write(&temp_path, output)
+            .and_then(|()| std::fs::rename(&temp_path, path))
+            .map_err(|error| {
+                // Don't leave a partially written temporary file behind
+                std::fs::remove_file(&temp_path).ok();
+                MinilateError::Io {
+                    path: path.display().to_string(),
+                    message: error.to_string(),
                 }
+            })
+    }
+
+    /// Checks a template for likely mistakes, such as a loop variable that is never used.
+    ///
+    /// Lints don't prevent a template from rendering; each [`LintWarning`] points out
+    /// something that is valid but probably unintended. Templates included by the
+    /// template are followed, so a loop variable used only by an included template is not
+    /// reported.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(MinilateError::MissingTemplate)` if no template with the given name exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{LintWarning, MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine
+    ///     .add_template("list", "{{% for item in items %}}-{{% endfor %}}")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     engine.lint("list").unwrap(),
+    ///     vec![LintWarning::UnusedLoopVariable {
+    ///         variable: "item".to_string()
+    ///     }]
+    /// );
+    /// ```
+    pub fn lint<N: AsRef<str>>(&self, template_name: N) -> MinilateResult<Vec<LintWarning>> {
+        let name = normalize_name(template_name.as_ref());
+        let template =
+            self.templates
+                .get(name.as_ref())
+                .ok_or_else(|| MinilateError::MissingTemplate {
+                    template_name: name.to_string(),
+                })?;
+
+        let lookup = |include: &str| {
+            self.templates
+                .get(normalize_name(include).as_ref())
+                .map(Template::ast)
+        };
+        Ok(lint::lint(template.ast(), &lookup))
+    }
+
+    /// Lists every variable that a template, or any template it could include, might read.
+    ///
+    /// Unlike [`MinilateInterface::context`], this ignores any context: every branch of
+    /// every condition is followed, as is every static include, giving the complete
+    /// schema for a tree of templates. Dynamic includes can't be followed, as their names
+    /// depend on the context. Variables are sorted by name, and each has the type inferred
+    /// from its first use.
+    ///
+    /// Returns an empty vector if the template doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface, VariableTy};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("admin", "Admin {{ name }}").unwrap();
+    /// engine
+    ///     .add_template("page", "{{% if is_admin %}}{{<< admin }}{{% endif %}}")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     engine.transitive_variables("page"),
+    ///     vec![
+    ///         ("is_admin".to_string(), VariableTy::Boolean),
+    ///         ("name".to_string(), VariableTy::String),
+    ///     ]
+    /// );
+    /// ```
+    pub fn transitive_variables<N: AsRef<str>>(
+        &self,
+        template_name: N,
+    ) -> Vec<(String, crate::interface::VariableTy)> {
+        struct Includes<'t> {
+            names: Vec<&'t str>,
+        }
+
+        impl<'t> Visitor<'t> for Includes<'t> {
+            fn visit_template_include(&mut self, template_name: &'t str) {
+                self.names.push(template_name);
             }
-            // Other node types don't contribute to template inclusion
-            AstNode::Constant { .. }
-            | AstNode::Variable { .. }
-            | AstNode::Not { .. }
-            | AstNode::And { .. }
-            | AstNode::Or { .. } => {}
         }
+
+        let context = Context::new();
+        let mut variables = Vec::new();
+        let mut visited: Vec<&str> = Vec::new();
+        let mut pending = vec![normalize_name(template_name.as_ref()).into_owned()];
+
+        while let Some(name) = pending.pop() {
+            let Some((name, template)) = self.templates.get_key_value(&name) else {
+                continue;
+            };
+            if visited.contains(&name.as_str()) {
+                continue;
+            }
+            visited.push(name);
+
+            template.collect_variables(&mut variables, &context);
+
+            let mut includes = Includes { names: Vec::new() };
+            walk(template.ast(), &mut includes);
+            pending.extend(
+                includes
+                    .names
+                    .into_iter()
+                    .map(|name| normalize_name(name).into_owned()),
+            );
+        }
+
+        let mut variables = variables
+            .into_iter()
+            .map(|(name, ty)| (name.to_string(), ty))
+            .collect::<Vec<_>>();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        variables
     }
-}
 
-impl MinilateEngine<'_> {
-    /// Creates a new empty `MinilateEngine` instance.
+    /// Describes the variables required by a template as a JSON object.
     ///
-    /// # Returns
+    /// Each key is a variable name and each value is its inferred [`crate::VariableTy`],
+    /// as reported by [`MinilateInterface::context`] against an empty context. This is
+    /// handy for driving a form or other UI that needs to know what a template expects.
     ///
-    /// A new engine with no templates.
-    pub fn new() -> Self {
-        Self {
-            templates: HashMap::new(),
-        }
+    /// Returns an empty object if the template doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello, {{ name }}!").unwrap();
+    ///
+    /// let schema = engine.context_schema("greeting");
+    /// assert_eq!(schema, serde_json::json!({ "name": "String" }));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn context_schema<N: AsRef<str>>(&self, template_name: N) -> serde_json::Value {
+        let context = Context::new();
+        let schema = self
+            .context(template_name, &context)
+            .into_iter()
+            .map(|(name, ty)| (name.to_string(), serde_json::json!(ty)))
+            .collect::<serde_json::Map<_, _>>();
+
+        serde_json::Value::Object(schema)
     }
 }
 
@@ -204,6 +1866,7 @@ impl MinilateInterface for MinilateEngine<'_> {
     /// * `Ok(())` if the template was successfully added
     /// * `Err(MinilateError::TemplateExists)` if a template with the given name already exists
     /// * `Err(MinilateError::Parse)` if the template content contains syntax errors
+    /// * `Err(MinilateError::SelfInclude)` if the template includes itself
     ///
     /// # Examples
     ///
@@ -218,9 +1881,9 @@ impl MinilateInterface for MinilateEngine<'_> {
         name: N,
         content: C,
     ) -> MinilateResult<()> {
-        let name = name.as_ref();
+        let name = normalize_name(name.as_ref());
 
-        if self.templates.contains_key(name) {
+        if self.templates.contains_key(name.as_ref()) {
             return Err(MinilateError::TemplateExists {
                 template_name: name.to_string(),
             });
@@ -229,7 +1892,8 @@ impl MinilateInterface for MinilateEngine<'_> {
         let content_str: String = content.into().to_string();
 
         // Parse the template content into an AST using the Template implementation
-        let mut template = Template::new(content_str)?;
+        let mut template = self.parse_template(content_str)?;
+        check_self_include(&name, &template)?;
         template.name = Some(name.to_string());
 
         self.templates.insert(name.to_string(), template);
@@ -237,6 +1901,40 @@ impl MinilateInterface for MinilateEngine<'_> {
         Ok(())
     }
 
+    /// Removes the template with the given name from the engine.
+    ///
+    /// Templates that include the removed template are kept, but fail to render with
+    /// `MinilateError::MissingTemplate` until a template with that name is added again.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the template to remove
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` if the template was successfully removed
+    /// * `Err(MinilateError::MissingTemplate)` if no template with the given name exists
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello, {{ name }}!").unwrap();
+    /// engine.remove_template("greeting").unwrap();
+    /// engine.add_template("greeting", "Hi, {{ name }}!").unwrap();
+    /// ```
+    fn remove_template<N: AsRef<str>>(&mut self, name: N) -> MinilateResult<()> {
+        let name = normalize_name(name.as_ref());
+        self.templates
+            .remove(name.as_ref())
+            .map(|_| ())
+            .ok_or_else(|| MinilateError::MissingTemplate {
+                template_name: name.to_string(),
+            })
+    }
+
     /// Renders a template with the given name using the provided context.
     ///
     /// # Arguments
@@ -269,13 +1967,13 @@ impl MinilateInterface for MinilateEngine<'_> {
         template_name: N,
         context: Option<&'a Context<'a>>,
     ) -> MinilateResult<String> {
-        let name = template_name.as_ref();
-        let template = self
-            .templates
-            .get(name)
-            .ok_or_else(|| MinilateError::MissingTemplate {
-                template_name: name.to_string(),
-            })?;
+        let name = normalize_name(template_name.as_ref());
+        let template =
+            self.templates
+                .get(name.as_ref())
+                .ok_or_else(|| MinilateError::MissingTemplate {
+                    template_name: name.to_string(),
+                })?;
 
         let default_context = Context::default();
         let context = context.unwrap_or(&default_context);
@@ -283,6 +1981,60 @@ impl MinilateInterface for MinilateEngine<'_> {
         template.render(context, Some(self))
     }
 
+    /// Returns the template with the given name, or `None` if no such template exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("greeting", "Hello!").unwrap();
+    ///
+    /// assert!(engine.get_template("greeting").is_some());
+    /// assert!(engine.get_template("farewell").is_none());
+    /// ```
+    fn get_template<N: AsRef<str>>(&self, name: N) -> Option<&Template<'_>> {
+        self.templates.get(normalize_name(name.as_ref()).as_ref())
+    }
+
+    /// Returns the names of every template in the engine, sorted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("page", "{{<< header }}").unwrap();
+    /// engine.add_template("header", "# Title").unwrap();
+    ///
+    /// assert_eq!(engine.template_names(), ["header", "page"]);
+    /// ```
+    fn template_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.templates.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Reports whether a template with the given name exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::{MinilateEngine, MinilateInterface};
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine.add_template("admin/header", "# Admin").unwrap();
+    ///
+    /// assert!(engine.has_template("admin/header"));
+    /// assert!(!engine.has_template("header"));
+    /// ```
+    fn has_template<N: AsRef<str>>(&self, name: N) -> bool {
+        self.templates
+            .contains_key(normalize_name(name.as_ref()).as_ref())
+    }
+
     /// Analyzes a template and returns a list of required variables that aren't already in the context.
     ///
     /// This method identifies all variables used in the template and their expected types,
@@ -319,28 +2071,66 @@ impl MinilateInterface for MinilateEngine<'_> {
         template_name: T,
         context: &'a Context<'a>,
     ) -> Vec<(&'b str, crate::interface::VariableTy)> {
-        let name = template_name.as_ref();
+        let mut variables = self.required_variables(template_name.as_ref(), context);
 
-        // If template doesn't exist, return empty vec
-        let template = match self.templates.get(name) {
-            Some(t) => t,
-            None => return vec![],
-        };
+        // Remove duplicates from the variables list
+        variables.sort_by_key(|(a, _)| *a);
+        variables.dedup_by(|(a, _), (b, _)| a == b);
 
-        // Collect variables
-        let mut variables = Vec::new();
-        let mut visited = Vec::new();
+        variables
+    }
 
-        // First collect variables from this template
-        template.collect_variables(&mut variables, context);
+    /// Returns the policy configured with [`MinilateEngine::set_missing_policy`].
+    fn missing_policy(&self) -> &MissingPolicy {
+        &self.missing_policy
+    }
 
-        // Collect template inclusions through a proper AST traversal
-        self.collect_inclusion_variables(&template.ast, &mut variables, context, &mut visited);
+    /// Returns the escaper configured with [`MinilateEngine::set_escaper`].
+    fn escaper(&self) -> Escaper {
+        self.escaper
+    }
 
-        // Remove duplicates from the variables list
-        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
-        variables.dedup_by(|(a, _), (b, _)| a == b);
+    /// Returns whether debugging was enabled with [`MinilateEngine::set_debug`].
+    fn debug(&self) -> bool {
+        self.debug
+    }
 
-        variables
+    /// Returns the filter registered with [`MinilateEngine::register_filter`], if any.
+    fn filter(&self, name: &str) -> Option<&dyn Fn(&str) -> String> {
+        let filter = self.filters.get(name)?;
+        Some(filter.as_ref())
+    }
+
+    /// Returns the renderer registered with [`MinilateEngine::set_renderer`] for `name`,
+    /// or else with [`MinilateEngine::set_type_renderer`] for `ty`.
+    fn renderer(&self, name: &str, ty: VariableTy) -> Option<&dyn Fn(&Variable<'_>) -> String> {
+        let renderer = self
+            .renderers
+            .get(name)
+            .or_else(|| self.type_renderers.get(&ty))?;
+        Some(renderer.as_ref())
+    }
+
+    /// Returns the separator configured with [`MinilateEngine::set_iterable_separator`].
+    fn iterable_separator(&self) -> &str {
+        self.iterable_separator.as_deref().unwrap_or(",")
+    }
+
+    /// Returns whether case-insensitive lookup was enabled with
+    /// [`MinilateEngine::set_case_insensitive_vars`].
+    fn case_insensitive_vars(&self) -> bool {
+        self.case_insensitive_vars
+    }
+
+    /// Returns whether dotted fallback was enabled with [`MinilateEngine::set_dotted_fallback`].
+    fn dotted_fallback(&self) -> bool {
+        self.dotted_fallback
+    }
+
+    /// Returns the labels configured with [`MinilateEngine::set_bool_labels`].
+    fn bool_labels(&self) -> Option<(&str, &str)> {
+        self.bool_labels
+            .as_ref()
+            .map(|(true_label, false_label)| (true_label.as_str(), false_label.as_str()))
     }
 }
@@ -62,6 +62,16 @@ pub enum ParseErrorKind {
     UnknownKeyword { keyword: String },
     /// A generic expected error, used for cases where the parser expects something specific.
     Expected { description: String },
+    /// The input is longer than the configured maximum, so it wasn't parsed at all.
+    InputTooLong {
+        /// The length of the input in bytes.
+        length: usize,
+        /// The maximum length allowed, in bytes.
+        max_length: usize,
+    },
+    /// A `{{% break %}}` or `{{% continue %}}` was found outside the body of a for loop or
+    /// repeat block.
+    OutsideLoop { keyword: String },
     /// A generic message for parser errors that don't fit into the other categories.
     Message(String),
 }
@@ -84,6 +94,20 @@ impl std::fmt::Display for ParseErrorKind {
             Self::Expected { description } => {
                 write!(f, "Expected {}", description)
             }
+            Self::InputTooLong { length, max_length } => {
+                write!(
+                    f,
+                    "Input is {} bytes long, exceeding the maximum of {}",
+                    length, max_length
+                )
+            }
+            Self::OutsideLoop { keyword } => {
+                write!(
+                    f,
+                    "'{}' can only be used inside a for loop or repeat block",
+                    keyword
+                )
+            }
             Self::Message(msg) => {
                 write!(f, "Parser error: {}", msg)
             }
@@ -108,6 +132,13 @@ pub struct ParseError {
     pub line: usize,
     pub column: usize,
     pub kind: ParseErrorKind,
+    /// The line of the template the error occurred on, shown with a caret under the
+    /// column when the error is displayed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub source_line: Option<String>,
 }
 
 impl std::fmt::Display for ParseError {
@@ -116,7 +147,26 @@ impl std::fmt::Display for ParseError {
             f,
             "Parse error at line {}, column {}: {}",
             self.line, self.column, self.kind
-        )
+        )?;
+
+        if let Some(source_line) = &self.source_line {
+            // The column counts bytes, but the caret is placed by characters, keeping any
+            // tabs so it lines up however wide they are shown
+            let before = source_line
+                .get(..self.column.saturating_sub(1))
+                .unwrap_or(source_line);
+            let padding: String = before
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let gutter = " ".repeat(self.line.to_string().len());
+            write!(
+                f,
+                "\n{} | {}\n{} | {}^",
+                self.line, source_line, gutter, padding
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -135,12 +185,22 @@ pub enum MinilateError {
     /// The requested template was not found.
     MissingTemplate { template_name: String },
     /// A variable was referenced but not found in the context.
-    MissingVariable { variable_name: String },
+    MissingVariable {
+        variable_name: String,
+        /// The template referencing the variable, if it was added to an engine.
+        template_name: Option<String>,
+    },
     /// A variable was referenced but its data was not provided.
-    MissingVariableData { variable_name: String },
+    MissingVariableData {
+        variable_name: String,
+        /// The template referencing the variable, if it was added to an engine.
+        template_name: Option<String>,
+    },
     /// A variable was found, but its type did not match the expected type.
     TypeMismatch {
         variable_name: String,
+        /// The template using the variable, if it was added to an engine.
+        template_name: Option<String>,
         expected: crate::interface::VariableTy,
         found: crate::interface::VariableTy,
     },
@@ -148,6 +208,41 @@ pub enum MinilateError {
     RenderError { message: String },
     /// A parsing error occurred, containing the details of the error.
     Parse(ParseError),
+    /// A template includes itself, which would recurse forever when rendered.
+    SelfInclude { template_name: String },
+    /// Rendering a template led back to itself through its includes, e.g. `a` includes
+    /// `b` which includes `a` again.
+    RecursionLimit {
+        /// The names of the templates in the cycle, starting and ending with the same name.
+        chain: Vec<String>,
+    },
+    /// Reading or writing a file failed.
+    Io {
+        /// The path of the file being accessed.
+        path: String,
+        /// The underlying IO error, as text.
+        message: String,
+    },
+    /// The raw bytes supplied for a template were not valid UTF-8.
+    InvalidUtf8 {
+        template_name: String,
+        /// The byte offset up to which the input was valid UTF-8.
+        valid_up_to: usize,
+    },
+    /// Rendering was abandoned because its cancel flag was set.
+    Cancelled,
+    /// Templates include other templates that don't exist.
+    MissingIncludes {
+        /// Each template with a missing include, paired with the name it includes.
+        includes: Vec<(String, String)>,
+    },
+    /// Loading a template from a file failed.
+    TemplateFile {
+        /// The path of the file the template was loaded from.
+        path: String,
+        /// Why the template couldn't be loaded, such as a syntax error.
+        error: Box<Self>,
+    },
 }
 
 impl std::fmt::Display for MinilateError {
@@ -159,14 +254,23 @@ impl std::fmt::Display for MinilateError {
             Self::MissingTemplate { template_name } => {
                 write!(f, "Template not found: {}", template_name)
             }
-            Self::MissingVariable { variable_name } => {
-                write!(f, "Variable not found: {}", variable_name)
+            Self::MissingVariable {
+                variable_name,
+                template_name,
+            } => {
+                write!(f, "Variable not found: {}", variable_name)?;
+                write_template_name(f, template_name.as_deref())
             }
-            Self::MissingVariableData { variable_name } => {
-                write!(f, "Variable data missing: {}", variable_name)
+            Self::MissingVariableData {
+                variable_name,
+                template_name,
+            } => {
+                write!(f, "Variable data missing: {}", variable_name)?;
+                write_template_name(f, template_name.as_deref())
             }
             Self::TypeMismatch {
                 variable_name,
+                template_name,
                 expected,
                 found,
             } => {
@@ -174,7 +278,8 @@ impl std::fmt::Display for MinilateError {
                     f,
                     "Type mismatch for variable {}: expected {:?}, found {:?}",
                     variable_name, expected, found
-                )
+                )?;
+                write_template_name(f, template_name.as_deref())
             }
             Self::RenderError { message } => {
                 write!(f, "Rendering error: {}", message)
@@ -182,20 +287,84 @@ impl std::fmt::Display for MinilateError {
             Self::Parse(parse_error) => {
                 write!(f, "{}", parse_error)
             }
+            Self::SelfInclude { template_name } => {
+                write!(f, "Template includes itself: {}", template_name)
+            }
+            Self::RecursionLimit { chain } => {
+                write!(f, "Template include cycle: {}", chain.join(" -> "))
+            }
+            Self::Io { path, message } => {
+                write!(f, "IO error for {}: {}", path, message)
+            }
+            Self::InvalidUtf8 {
+                template_name,
+                valid_up_to,
+            } => {
+                write!(
+                    f,
+                    "Template {} is not valid UTF-8 (invalid byte at offset {})",
+                    template_name, valid_up_to
+                )
+            }
+            Self::Cancelled => write!(f, "Rendering was cancelled"),
+            Self::MissingIncludes { includes } => {
+                write!(f, "Included templates not found:")?;
+                for (template_name, include) in includes {
+                    write!(f, " {} (in {})", include, template_name)?;
+                }
+                Ok(())
+            }
+            Self::TemplateFile { path, error } => {
+                write!(f, "{}: {}", path, error)
+            }
         }
     }
 }
 
+/// Writes the template an error occurred in, if it is known.
+fn write_template_name(
+    f: &mut std::fmt::Formatter<'_>,
+    template_name: Option<&str>,
+) -> std::fmt::Result {
+    match template_name {
+        Some(template_name) => write!(f, " (in template {})", template_name),
+        None => Ok(()),
+    }
+}
+
+impl MinilateError {
+    /// Records that this error occurred while rendering the template `name`, unless it
+    /// already names the template it occurred in, which may be one `name` includes.
+    pub(crate) fn in_template(mut self, name: Option<&str>) -> Self {
+        if let Self::MissingVariable { template_name, .. }
+        | Self::MissingVariableData { template_name, .. }
+        | Self::TypeMismatch { template_name, .. } = &mut self
+        {
+            if template_name.is_none() {
+                *template_name = name.map(str::to_string);
+            }
+        }
+        self
+    }
+}
+
 impl std::error::Error for MinilateError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Self::Parse(parse_error) => Some(parse_error),
+            Self::TemplateFile { error, .. } => Some(error.as_ref()),
             Self::TemplateExists { .. }
             | Self::MissingTemplate { .. }
             | Self::MissingVariable { .. }
             | Self::MissingVariableData { .. }
             | Self::TypeMismatch { .. }
-            | Self::RenderError { .. } => None,
+            | Self::RenderError { .. }
+            | Self::SelfInclude { .. }
+            | Self::RecursionLimit { .. }
+            | Self::Io { .. }
+            | Self::InvalidUtf8 { .. }
+            | Self::Cancelled
+            | Self::MissingIncludes { .. } => None,
         }
     }
 }
@@ -205,3 +374,11 @@ impl From<ParseError> for MinilateError {
         Self::Parse(error)
     }
 }
+
+impl From<std::fmt::Error> for MinilateError {
+    fn from(_: std::fmt::Error) -> Self {
+        Self::RenderError {
+            message: "Failed to write the rendered output".to_string(),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Output escaping for rendered variables.
+//!
+//! Templates are often used to generate something other than plain text, in which case
+//! variable values must be escaped so they can't break out of their surrounding syntax.
+//! The [`Escaper`] enum selects how `{{ variable }}` output is escaped; constant text in
+//! the template itself is never escaped, and neither is trusted content output with the
+//! `safe` filter, as in `{{ bio_html | safe }}`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use minilate::{Context, Escaper, MinilateEngine, MinilateInterface, VariableTy};
+//!
+//! let mut engine = MinilateEngine::new();
+//! engine.set_escaper(Escaper::Json);
+//! engine.add_template("json", r#"{"name": "{{ name }}"}"#).unwrap();
+//!
+//! let mut context = Context::new();
+//! context.insert("name", VariableTy::String.with_data(r#"Jane "JJ" Doe"#));
+//!
+//! let output = engine.render("json", Some(&context)).unwrap();
+//! assert_eq!(output, r#"{"name": "Jane \"JJ\" Doe"}"#);
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Selects how variable output is escaped during rendering.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Escaper {
+    /// Output values verbatim.
+    #[default]
+    None,
+    /// Escape `&`, `<`, `>`, `"` and `'` as HTML entities.
+    Html,
+    /// Escape as the contents of a JSON string, without the surrounding quotes.
+    Json,
+    /// Quote the value as a single POSIX shell word.
+    Shell,
+    /// Escape with a user supplied function.
+    Custom(fn(&str) -> String),
+}
+
+impl Escaper {
+    /// Escapes `value` for this target, borrowing it when nothing needs to change.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::Escaper;
+    ///
+    /// assert_eq!(Escaper::Html.escape("<b>"), "&lt;b&gt;");
+    /// assert_eq!(Escaper::Shell.escape("it's"), r"'it'\''s'");
+    /// assert_eq!(Escaper::Custom(|v| v.to_uppercase()).escape("loud"), "LOUD");
+    /// ```
+    pub fn escape<'s>(&self, value: &'s str) -> Cow<'s, str> {
+        match self {
+            Self::None => Cow::Borrowed(value),
+            Self::Html => escape_html(value),
+            Self::Json => escape_json(value),
+            Self::Shell => Cow::Owned(format!("'{}'", value.replace('\'', r"'\''"))),
+            Self::Custom(escape) => Cow::Owned(escape(value)),
+        }
+    }
+}
+
+fn escape_html(value: &str) -> Cow<'_, str> {
+    if !value.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+fn escape_json(value: &str) -> Cow<'_, str> {
+    if !value.contains(|c: char| c == '"' || c == '\\' || c.is_ascii_control()) {
+        return Cow::Borrowed(value);
+    }
+
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_ascii_control() => {
+                write!(escaped, "\\u{:04x}", u32::from(c))
+                    .expect("Writing to a String cannot fail");
+            }
+            _ => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
@@ -57,6 +57,8 @@
 
 use std::{borrow::Cow, collections::BTreeMap};
 
+use crate::escape::Escaper;
+
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Specifies the type of a variable within a [`Context`].
@@ -79,6 +81,21 @@ pub enum VariableTy {
     /// Represents a collection of items that can be iterated over in a `{{% for %}}` loop.
     /// The string data for an iterable is typically a comma-separated list of values.
     Iterable,
+    /// Represents a number, such as `"18"`, `"-3"` or `"2.5"`.
+    /// The string data is rendered as given, except that a fractional part of only zeros
+    /// is dropped so `"5.0"` renders as `5`, and rendering fails if it isn't a number. It is
+    /// parsed as a number by relational comparisons (`<`, `>`, `<=`, `>=`) in conditions,
+    /// where non-zero is truthy.
+    Number,
+    /// Represents a collection of key/value pairs iterated over by a two-variable loop,
+    /// e.g. `{{% for k, v in map %}}`.
+    /// The string data is a comma-separated list of `key=value` entries, such as
+    /// `"name=Alice, role=admin"`. An entry without `=` has an empty value.
+    Map,
+    /// Represents a list of structured items iterated over by a `{{% for %}}` loop, where
+    /// each item is a [`Context`] of fields, e.g. `{{ item.name }}`.
+    /// Lists have no string data; they are built with [`Variable::list`].
+    List,
 }
 
 impl VariableTy {
@@ -115,6 +132,7 @@ impl VariableTy {
         Variable {
             ty: self,
             data: Some(data.into()),
+            items: Vec::new(),
         }
     }
 }
@@ -124,6 +142,52 @@ impl VariableTy {
 pub struct Variable<'a> {
     ty: VariableTy,
     data: Option<Cow<'a, str>>,
+    /// The items of a [`VariableTy::List`], empty for every other type
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    items: Vec<Context<'a>>,
+}
+
+impl<'a> Variable<'a> {
+    /// Creates a [`VariableTy::List`] variable from structured items, each a context of
+    /// fields.
+    ///
+    /// Looping over the list with `{{% for item in list %}}` binds each field of an item
+    /// under the loop variable, so `{{ item.name }}` outputs the item's `name` field.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, MinilateInterface, Variable, VariableTy};
+    ///
+    /// let people = ["Ada", "Grace"].map(|name| {
+    ///     Context::new()
+    ///         .insert("name", VariableTy::String.with_data(name))
+    ///         .to_owned()
+    /// });
+    /// let mut context = Context::new();
+    /// context.insert("people", Variable::list(people));
+    ///
+    /// let mut engine = MinilateEngine::new();
+    /// engine
+    ///     .add_template("names", "{{% for p in people %}}<{{ p.name }}>{{% endfor %}}")
+    ///     .unwrap();
+    /// assert_eq!(engine.render("names", Some(&context)).unwrap(), "<Ada><Grace>");
+    /// ```
+    pub fn list<I: IntoIterator<Item = Context<'a>>>(items: I) -> Self {
+        Self {
+            ty: VariableTy::List,
+            data: None,
+            items: items.into_iter().collect(),
+        }
+    }
+
+    /// Returns the items of a [`VariableTy::List`], or nothing for any other type.
+    pub fn items(&self) -> &[Context<'a>] {
+        &self.items
+    }
 }
 
 impl Variable<'_> {
@@ -137,7 +201,7 @@ impl Variable<'_> {
 }
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[derive(Debug, Clone, Default)]
 /// Holds the data (variables) available during template rendering.
 ///
 /// A `Context` is essentially a map where keys are variable names (strings)
@@ -179,6 +243,67 @@ impl Variable<'_> {
 /// ```
 pub struct Context<'a> {
     data: BTreeMap<String, Variable<'a>>,
+    /// Whether names that only differ from a variable's name by case find that variable,
+    /// see [`crate::MinilateEngine::set_case_insensitive_vars`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    case_insensitive: bool,
+    /// The separator between the items of an iterable, if not a comma, see
+    /// [`crate::MinilateEngine::set_iterable_separator`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    separator: Option<String>,
+    /// Contexts searched in order for variables this context doesn't have, see
+    /// [`crate::MinilateEngine::render_layered`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    layers: Vec<&'a Self>,
+    /// The `{{% alias short = long.path %}}` names declared in this scope, innermost last,
+    /// along with the variable each stands for
+    #[cfg_attr(feature = "serde", serde(skip))]
+    aliases: Vec<(String, String)>,
+    /// Whether any alias is in scope, here or in the contexts this one is layered on
+    #[cfg_attr(feature = "serde", serde(skip))]
+    aliased: bool,
+}
+
+/// Contexts are compared by the variables they hold, not by how they look them up, so a
+/// layered context equals one holding the same variables directly.
+///
+/// The iterable separator and whether names ignore case aren't compared either, so two
+/// equal contexts may still render a template differently.
+impl PartialEq for Context<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.layers.is_empty() && other.layers.is_empty() {
+            return self.data == other.data;
+        }
+        self.variables() == other.variables()
+    }
+}
+
+impl Eq for Context<'_> {}
+
+impl PartialOrd for Context<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Context<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self.layers.is_empty() && other.layers.is_empty() {
+            return self.data.cmp(&other.data);
+        }
+        self.variables().cmp(&other.variables())
+    }
+}
+
+impl std::hash::Hash for Context<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Hashes the same name and variable pairs as the merged view of a layered context
+        if self.layers.is_empty() {
+            self.data.hash(state);
+        } else {
+            self.variables().hash(state);
+        }
+    }
 }
 
 impl Context<'_> {
@@ -192,6 +317,133 @@ impl Context<'_> {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Builds a context from `name`/`value` pairs, inferring each variable's type from
+    /// its value.
+    ///
+    /// This is intended for command line tools accepting `--set name=value` style
+    /// arguments, where no type information is available. The type is inferred as:
+    ///
+    /// - `"true"` or `"false"` → [`VariableTy::Boolean`]
+    /// - any value containing a comma → [`VariableTy::Iterable`]
+    /// - anything else → [`VariableTy::String`]
+    ///
+    /// Later pairs replace earlier pairs with the same name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, VariableTy};
+    ///
+    /// let ctx = Context::from_kv_pairs([
+    ///     ("name".to_string(), "Alice".to_string()),
+    ///     ("admin".to_string(), "true".to_string()),
+    ///     ("roles".to_string(), "dev,ops".to_string()),
+    /// ]);
+    ///
+    /// assert_eq!(ctx.get("name").unwrap().ty(), VariableTy::String);
+    /// assert_eq!(ctx.get("admin").unwrap().ty(), VariableTy::Boolean);
+    /// assert_eq!(ctx.get("roles").unwrap().ty(), VariableTy::Iterable);
+    /// ```
+    pub fn from_kv_pairs<I: IntoIterator<Item = (String, String)>>(pairs: I) -> Self {
+        let mut context = Self::new();
+        for (name, value) in pairs {
+            let ty = if value == "true" || value == "false" {
+                VariableTy::Boolean
+            } else if value.contains(',') {
+                VariableTy::Iterable
+            } else {
+                VariableTy::String
+            };
+            context.insert(name, ty.with_data(value));
+        }
+        context
+    }
+
+    /// Builds a context from a JSON object, mapping each field to a variable.
+    ///
+    /// Fields are mapped by the type of their value:
+    ///
+    /// - strings → [`VariableTy::String`]
+    /// - booleans → [`VariableTy::Boolean`]
+    /// - numbers → [`VariableTy::Number`]
+    /// - arrays of strings, numbers and booleans → [`VariableTy::Iterable`], with the
+    ///   items joined by commas
+    /// - objects → flattened, with each nested field named by its dotted path, e.g.
+    ///   `user.name`
+    ///
+    /// The flat model of a context can't represent anything else, so `null` fields and
+    /// arrays containing `null`s, arrays or objects are skipped. A `value` that isn't an
+    /// object gives an empty context.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, VariableTy};
+    ///
+    /// let ctx = Context::from_json(&serde_json::json!({
+    ///     "user": { "name": "Alice", "admin": true },
+    ///     "roles": ["dev", "ops"],
+    ///     "teams": [{ "name": "core" }],
+    /// }));
+    ///
+    /// assert_eq!(ctx.get("user.name").unwrap().data(), Some("Alice"));
+    /// assert_eq!(ctx.get("user.admin").unwrap().ty(), VariableTy::Boolean);
+    /// assert_eq!(ctx.get("roles").unwrap().data(), Some("dev,ops"));
+    /// assert!(!ctx.contains("teams"));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn from_json(value: &serde_json::Value) -> Context<'static> {
+        let mut context = Context::new();
+        if let serde_json::Value::Object(fields) = value {
+            insert_json_fields(&mut context, None, fields);
+        }
+        context
+    }
+}
+
+/// Inserts the fields of a JSON object into `context` for [`Context::from_json`], naming
+/// each by its dotted path below `prefix`
+#[cfg(feature = "serde")]
+fn insert_json_fields(
+    context: &mut Context<'static>,
+    prefix: Option<&str>,
+    fields: &serde_json::Map<String, serde_json::Value>,
+) {
+    use serde_json::Value;
+
+    /// The data of a scalar JSON value, or `None` for anything else
+    fn scalar_data(value: &Value) -> Option<String> {
+        match value {
+            Value::String(data) => Some(data.clone()),
+            Value::Bool(data) => Some(data.to_string()),
+            Value::Number(data) => Some(data.to_string()),
+            Value::Null | Value::Array(_) | Value::Object(_) => None,
+        }
+    }
+
+    for (key, value) in fields {
+        let name = prefix.map_or_else(|| key.clone(), |prefix| format!("{prefix}.{key}"));
+        match value {
+            Value::String(data) => {
+                context.insert(name, VariableTy::String.with_data(data.clone()));
+            }
+            Value::Bool(data) => {
+                context.insert(name, VariableTy::Boolean.with_data(data.to_string()));
+            }
+            Value::Number(data) => {
+                context.insert(name, VariableTy::Number.with_data(data.to_string()));
+            }
+            Value::Array(items) => {
+                // Only arrays of scalars fit in an iterable
+                if let Some(items) = items.iter().map(scalar_data).collect::<Option<Vec<_>>>() {
+                    context.insert(name, VariableTy::Iterable.with_data(items.join(",")));
+                }
+            }
+            Value::Object(fields) => insert_json_fields(context, Some(&name), fields),
+            Value::Null => {}
+        }
+    }
 }
 
 impl<'a> Context<'a> {
@@ -231,6 +483,30 @@ impl<'a> Context<'a> {
         self
     }
 
+    /// Applies `f` to the context and returns it, for updating a context within an
+    /// expression chain.
+    ///
+    /// This is convenient for inserting variables conditionally while building a context.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, VariableTy};
+    ///
+    /// let is_admin = true;
+    /// let ctx = Context::new().apply(|ctx| {
+    ///     if is_admin {
+    ///         ctx.insert("role", VariableTy::String.with_data("admin"));
+    ///     }
+    /// });
+    ///
+    /// assert!(ctx.contains("role"));
+    /// ```
+    pub fn apply<F: FnOnce(&mut Self)>(mut self, f: F) -> Self {
+        f(&mut self);
+        self
+    }
+
     /// Retrieves a reference to a variable from the context.
     ///
     /// # Arguments
@@ -256,7 +532,226 @@ impl<'a> Context<'a> {
     /// assert!(ctx.get("country").is_none());
     /// ```
     pub fn get<T: AsRef<str>>(&self, name: T) -> Option<&Variable<'a>> {
-        self.data.get(name.as_ref())
+        let name = self.resolve_alias(name.as_ref());
+        let variable = self.get_exact(name);
+        if variable.is_some() || !self.case_insensitive {
+            return variable;
+        }
+
+        // Without an exact match, the first name in order matching regardless of case wins
+        self.get_ignoring_case(name)
+    }
+
+    /// Returns the variable called exactly `name` in this context or its layers.
+    fn get_exact(&self, name: &str) -> Option<&Variable<'a>> {
+        self.data
+            .get(name)
+            .or_else(|| self.layers.iter().find_map(|layer| layer.get_exact(name)))
+    }
+
+    /// Returns the first variable whose name matches `name` regardless of case in this
+    /// context or its layers.
+    fn get_ignoring_case(&self, name: &str) -> Option<&Variable<'a>> {
+        self.data
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, variable)| variable)
+            .or_else(|| {
+                self.layers
+                    .iter()
+                    .find_map(|layer| layer.get_ignoring_case(name))
+            })
+    }
+
+    /// Returns the variable `name` stands for if it is an alias in scope, or `name` itself.
+    pub(crate) fn resolve_alias<'n>(&'n self, name: &'n str) -> &'n str {
+        if !self.aliased {
+            return name;
+        }
+        self.find_alias(name).flatten().unwrap_or(name)
+    }
+
+    /// Searches this context and then its layers for an alias called `name`, returning
+    /// `Some(None)` if a variable of the same name in a nearer scope hides any alias.
+    fn find_alias(&self, name: &str) -> Option<Option<&str>> {
+        if !self.aliased {
+            return None;
+        }
+        if self.data.contains_key(name) {
+            return Some(None);
+        }
+        if let Some((_, target)) = self.aliases.iter().rev().find(|(alias, _)| alias == name) {
+            return Some(Some(target));
+        }
+        self.layers.iter().find_map(|layer| layer.find_alias(name))
+    }
+
+    /// Returns true if any alias is in scope in this context.
+    pub(crate) const fn has_aliases(&self) -> bool {
+        self.aliased
+    }
+
+    /// Brings the alias `name` for the variable `target` into this scope, resolving
+    /// `target` in turn if it is itself an alias.
+    pub(crate) fn insert_alias(&mut self, name: &str, target: &str) {
+        let target = self.resolve_alias(target).to_string();
+        self.aliases.push((name.to_string(), target));
+        self.aliased = true;
+    }
+
+    /// Returns this context followed by its layers, each followed by its own layers in
+    /// turn, in the order [`Context::get`] searches them.
+    fn search_order(&self) -> Vec<&Self> {
+        let mut order = vec![self];
+        for layer in &self.layers {
+            order.extend(layer.search_order());
+        }
+        order
+    }
+
+    /// Creates an empty context that finds variables by searching `layers` in order, the
+    /// first to have a variable providing it.
+    ///
+    /// Variables inserted into the returned context take precedence over every layer.
+    pub(crate) fn layered(layers: &[&'a Self]) -> Self {
+        Self {
+            layers: layers.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    /// Creates an empty scope on top of `parent`, such as for the body of a loop.
+    ///
+    /// Variables inserted into the scope shadow those of `parent` without copying or
+    /// changing it, and the scope looks up and splits variables as `parent` does.
+    pub(crate) fn scoped(parent: &'a Self) -> Self {
+        Self {
+            data: BTreeMap::new(),
+            case_insensitive: parent.case_insensitive,
+            separator: parent.separator.clone(),
+            layers: vec![parent],
+            aliases: Vec::new(),
+            aliased: parent.aliased,
+        }
+    }
+
+    /// Creates an empty scope on top of `parent` as [`Context::scoped`] does, but without
+    /// the aliases in scope in `parent`, such as for an included template.
+    pub(crate) fn without_aliases(parent: &'a Self) -> Self {
+        Self {
+            aliased: false,
+            ..Self::scoped(parent)
+        }
+    }
+
+    /// Creates an empty context that looks up and splits variables as `self` does, but
+    /// sees none of its variables, such as for an include passed explicit bindings.
+    pub(crate) fn isolated(&self) -> Self {
+        Self {
+            data: BTreeMap::new(),
+            case_insensitive: self.case_insensitive,
+            separator: self.separator.clone(),
+            layers: Vec::new(),
+            aliases: Vec::new(),
+            aliased: false,
+        }
+    }
+
+    /// Makes [`Context::get`] and [`Context::contains`] ignore the case of names.
+    pub(crate) const fn set_case_insensitive(&mut self) {
+        self.case_insensitive = true;
+    }
+
+    /// Returns the separator between the items of the iterables in this context.
+    pub(crate) fn separator(&self) -> &str {
+        self.separator.as_deref().unwrap_or(",")
+    }
+
+    /// Sets the separator between the items of the iterables in this context.
+    pub(crate) fn set_separator(&mut self, separator: &str) {
+        self.separator = Some(separator.to_string());
+    }
+
+    /// Iterates over every variable in the context, ordered by name.
+    ///
+    /// For a context searching several layers, each layer's variables are ordered by name
+    /// in turn, skipping any hidden by an earlier layer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, VariableTy};
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.insert("name", VariableTy::String.with_data("Bob"))
+    ///    .insert("age", VariableTy::Number.with_data("30"));
+    ///
+    /// let names: Vec<&str> = ctx.iter().map(|(name, _)| name).collect();
+    /// assert_eq!(names, ["age", "name"]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Variable<'a>)> {
+        let layers = self.search_order();
+        layers
+            .clone()
+            .into_iter()
+            .enumerate()
+            .flat_map(move |(index, layer)| {
+                let earlier = layers.get(..index).unwrap_or_default().to_vec();
+                layer.data.iter().filter(move |(name, _)| {
+                    !earlier
+                        .iter()
+                        .any(|earlier| earlier.data.contains_key(name.as_str()))
+                })
+            })
+            .map(|(name, variable)| (name.as_str(), variable))
+    }
+
+    /// Returns the number of variables in the context, counting them as
+    /// [`Context::iter`] yields them.
+    pub fn len(&self) -> usize {
+        if self.layers.is_empty() {
+            return self.data.len();
+        }
+        self.iter().count()
+    }
+
+    /// Returns true if the context has no variables, nor any layers with variables.
+    pub fn is_empty(&self) -> bool {
+        if self.layers.is_empty() {
+            return self.data.is_empty();
+        }
+        self.iter().next().is_none()
+    }
+
+    /// Returns every variable in the context, as [`Context::iter`] yields them, sorted by
+    /// name, which is only needed to compare layered contexts.
+    fn variables(&self) -> Vec<(&str, &Variable<'a>)> {
+        let mut variables: Vec<_> = self.iter().collect();
+        variables.sort_unstable_by_key(|(name, _)| *name);
+        variables
+    }
+
+    /// Removes a variable from the context, returning it if it was present.
+    ///
+    /// The name must match exactly, even if the context ignores the case of names.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, VariableTy};
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.insert("user", VariableTy::String.with_data("bob"))
+    ///    .insert("password", VariableTy::String.with_data("hunter2"));
+    ///
+    /// // Drop sensitive fields before logging the context
+    /// let password = ctx.remove("password").unwrap();
+    /// assert_eq!(password.data(), Some("hunter2"));
+    /// assert_eq!(ctx.len(), 1);
+    /// assert!(ctx.remove("password").is_none());
+    /// ```
+    pub fn remove<T: AsRef<str>>(&mut self, name: T) -> Option<Variable<'a>> {
+        self.data.remove(name.as_ref())
     }
 
     /// Checks if the context contains a variable with the given name.
@@ -282,7 +777,7 @@ impl<'a> Context<'a> {
     /// assert!(!ctx.contains("is_moderator"));
     /// ```
     pub fn contains<T: AsRef<str>>(&self, name: T) -> bool {
-        self.data.contains_key(name.as_ref())
+        self.get(name).is_some()
     }
 }
 
@@ -300,6 +795,13 @@ pub trait MinilateInterface {
         content: C,
     ) -> crate::MinilateResult<()>;
 
+    /// `remove_template` tries to remove a template from the engine, freeing its name
+    /// to be added again.
+    ///
+    /// # Errors
+    /// - If the template name is not found.
+    fn remove_template<N: AsRef<str>>(&mut self, name: N) -> crate::MinilateResult<()>;
+
     /// `render` tries to render a template with the given context.
     ///
     /// # Errors
@@ -311,6 +813,20 @@ pub trait MinilateInterface {
         context: Option<&'a Context<'a>>,
     ) -> crate::MinilateResult<String>;
 
+    /// `get_template` returns the template with the given name, which is how included
+    /// templates are found when rendering.
+    fn get_template<N: AsRef<str>>(&self, name: N) -> Option<&crate::Template<'_>>;
+
+    /// `template_names` returns the names of every template in the engine, sorted so
+    /// the order is stable.
+    fn template_names(&self) -> Vec<&str>;
+
+    /// `has_template` reports whether a template with the given name exists, so that
+    /// `{{<< name }}` would find it.
+    fn has_template<N: AsRef<str>>(&self, name: N) -> bool {
+        self.get_template(name).is_some()
+    }
+
     /// `context` will return a Vec<()> of all missing context objects required
     /// to succesffully render the selected template.
     ///
@@ -321,6 +837,131 @@ pub trait MinilateInterface {
         template_name: T,
         context: &'a Context<'a>,
     ) -> Vec<(&'b str, VariableTy)>;
+
+    /// `escaper` returns the [`Escaper`] applied to the output of every
+    /// `{{ variable }}` when rendering.
+    ///
+    /// Defaults to [`Escaper::None`], which outputs values verbatim.
+    fn escaper(&self) -> Escaper {
+        Escaper::None
+    }
+
+    /// `missing_policy` returns the [`crate::MissingPolicy`] applied when a
+    /// `{{ variable }}` being rendered is missing from the context.
+    ///
+    /// Defaults to [`crate::MissingPolicy::Error`], which fails the render.
+    fn missing_policy(&self) -> &crate::MissingPolicy {
+        &crate::MissingPolicy::Error
+    }
+
+    /// `debug` reports whether `{{% debug %}}` directives should dump the
+    /// current context when rendering.
+    ///
+    /// Defaults to `false`, so debug directives render as nothing.
+    fn debug(&self) -> bool {
+        false
+    }
+
+    /// `bool_labels` returns the `(true, false)` labels output in place of a
+    /// [`VariableTy::Boolean`] variable's data when it is rendered with `{{ variable }}`.
+    ///
+    /// Defaults to `None`, which outputs the data verbatim.
+    fn bool_labels(&self) -> Option<(&str, &str)> {
+        None
+    }
+
+    /// `filter` looks up a custom filter, applied to the rendered value of a
+    /// `{{ variable | name }}` output.
+    ///
+    /// Defaults to `None`, so only the built-in `upper`, `lower`, `trim` and `default`
+    /// filters are available.
+    fn filter(&self, _name: &str) -> Option<&dyn Fn(&str) -> String> {
+        None
+    }
+
+    /// `renderer` looks up a custom renderer for the variable `name` of type `ty`, which
+    /// produces the output of a plain `{{ name }}` in place of the variable's data.
+    ///
+    /// Defaults to `None`, so variables output their data verbatim.
+    fn renderer(&self, _name: &str, _ty: VariableTy) -> Option<&dyn Fn(&Variable<'_>) -> String> {
+        None
+    }
+
+    /// `iterable_separator` returns the separator between the items of a
+    /// [`VariableTy::Iterable`] or [`VariableTy::Map`] variable's data.
+    ///
+    /// Defaults to `","`.
+    fn iterable_separator(&self) -> &str {
+        ","
+    }
+
+    /// `case_insensitive_vars` reports whether variables should be looked up ignoring
+    /// the case of their names, so `{{ UserName }}` finds a variable named `username`.
+    ///
+    /// Defaults to `false`, so names must match exactly.
+    fn case_insensitive_vars(&self) -> bool {
+        false
+    }
+
+    /// `dotted_fallback` reports whether a dotted variable such as `user.profile.name`
+    /// that is missing from the context should fall back to shorter names, first
+    /// `user.name` and then `name`, when rendering.
+    ///
+    /// Defaults to `false`, so only the exact name is looked up.
+    fn dotted_fallback(&self) -> bool {
+        false
+    }
 }
 
 // ExampleEngine is moved to engine.rs and replaced with MinilateEngine
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasher, RandomState};
+
+    use super::*;
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_layered_len() {
+        let mut first = Context::new();
+        first.insert("name", VariableTy::String.with_data("Ada"));
+        let mut second = Context::new();
+        second
+            .insert("name", VariableTy::String.with_data("Grace"))
+            .insert("site", VariableTy::String.with_data("Example"));
+
+        let layered = Context::layered(&[&first, &second]);
+        assert_eq!(layered.len(), 2);
+        assert!(!layered.is_empty());
+        assert_eq!(layered.iter().count(), layered.len());
+        assert!(Context::layered(&[&Context::new()]).is_empty());
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_equality_ignores_lookup() {
+        let mut plain = Context::new();
+        plain
+            .insert("name", VariableTy::String.with_data("Ada"))
+            .insert("site", VariableTy::String.with_data("Example"));
+
+        let mut settings = plain.clone();
+        settings.set_case_insensitive();
+        settings.set_separator("|");
+        assert_eq!(settings, plain);
+
+        let mut name = Context::new();
+        name.insert("name", VariableTy::String.with_data("Ada"));
+        let mut site = Context::new();
+        site.insert("site", VariableTy::String.with_data("Example"));
+        let layered = Context::layered(&[&site, &name]);
+        assert_eq!(layered, plain);
+        assert_eq!(layered.cmp(&plain), std::cmp::Ordering::Equal);
+
+        let hasher = RandomState::new();
+        assert_eq!(hasher.hash_one(&layered), hasher.hash_one(&plain));
+
+        assert_ne!(Context::layered(&[&name]), plain);
+    }
+}
@@ -1,16 +1,32 @@
 #![doc = include_str!("../README.md")]
 
 mod ast;
+mod diff;
 mod engine;
 mod error;
+mod escape;
 mod interface;
+mod lint;
+mod missing;
 mod parser;
+mod rendered;
+mod schema;
 mod template;
+mod visit;
 
 // Crate-level imports to make convienent imports for the rest of the library.
 // Public exports.
+pub use ast::{AstNode, CompareOp, Trim};
 pub use engine::MinilateEngine;
 pub use error::MinilateError;
-pub(crate) use error::MinilateResult;
+pub use error::MinilateResult;
+pub use error::{ParseError, ParseErrorKind};
+pub use escape::Escaper;
 pub use interface::{Context, MinilateInterface, Variable, VariableTy};
-pub use template::Template;
+pub use lint::LintWarning;
+pub use missing::{MissingPolicy, RenderMode};
+pub use parser::{DelimiterConfig, strip_comments};
+pub use rendered::RenderedDoc;
+pub use schema::SchemaError;
+pub use template::{InclusionCondition, Template};
+pub use visit::{Visitor, walk};
@@ -0,0 +1,113 @@
+//! Static checks for likely mistakes in templates.
+//!
+//! Lints never stop a template from rendering; they point out constructs that are
+//! valid but probably not what the author intended. Run them with
+//! [`crate::MinilateEngine::lint`].
+
+use crate::ast::AstNode;
+use crate::visit::{Visitor, walk};
+
+/// A likely mistake found in a template by [`crate::MinilateEngine::lint`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LintWarning {
+    /// A for loop binds a variable that is never referenced in its body.
+    UnusedLoopVariable { variable: String },
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnusedLoopVariable { variable } => {
+                write!(f, "Loop variable is never used: {}", variable)
+            }
+        }
+    }
+}
+
+/// Looks up the AST of an included template by name.
+pub type IncludeLookup<'t> = dyn Fn(&str) -> Option<&'t AstNode<'t>> + 't;
+
+/// Runs every lint over `ast`, using `lookup` to follow template inclusions.
+pub fn lint<'t>(ast: &'t AstNode<'t>, lookup: &IncludeLookup<'t>) -> Vec<LintWarning> {
+    let mut visitor = UnusedLoopVariables {
+        lookup,
+        warnings: Vec::new(),
+    };
+    walk(ast, &mut visitor);
+    visitor.warnings
+}
+
+/// Reports loop variables that are never referenced in the body of their loop.
+struct UnusedLoopVariables<'l, 't> {
+    lookup: &'l IncludeLookup<'t>,
+    warnings: Vec<LintWarning>,
+}
+
+impl<'t> Visitor<'t> for UnusedLoopVariables<'_, 't> {
+    fn visit_for(
+        &mut self,
+        iterable: &AstNode<'t>,
+        variable: &'t str,
+        body: &[AstNode<'t>],
+        empty_branch: Option<&AstNode<'t>>,
+    ) {
+        let mut uses = UsesVariable {
+            name: variable,
+            lookup: self.lookup,
+            visited: Vec::new(),
+            found: false,
+        };
+        for node in body {
+            walk(node, &mut uses);
+        }
+        if !uses.found {
+            self.warnings.push(LintWarning::UnusedLoopVariable {
+                variable: variable.to_string(),
+            });
+        }
+
+        walk(iterable, self);
+        for node in body {
+            walk(node, self);
+        }
+        if let Some(empty_branch) = empty_branch {
+            walk(empty_branch, self);
+        }
+    }
+}
+
+/// Checks whether a variable is referenced, including by any included templates, which
+/// see the variables bound by enclosing loops.
+struct UsesVariable<'l, 't> {
+    name: &'t str,
+    lookup: &'l IncludeLookup<'t>,
+    /// Included templates already checked, guarding against include cycles
+    visited: Vec<&'t str>,
+    found: bool,
+}
+
+impl<'t> Visitor<'t> for UsesVariable<'_, 't> {
+    fn visit_variable(&mut self, name: &'t str) {
+        self.found |= name == self.name;
+    }
+
+    fn visit_alias(&mut self, _name: &'t str, target: &'t str) {
+        self.found |= target == self.name;
+    }
+
+    fn visit_template_include(&mut self, template_name: &'t str) {
+        if self.visited.contains(&template_name) {
+            return;
+        }
+        self.visited.push(template_name);
+        if let Some(ast) = (self.lookup)(template_name) {
+            walk(ast, self);
+        }
+    }
+
+    fn visit_dynamic_include(&mut self, _name: &[AstNode<'t>]) {
+        // The included template isn't known until render time, so it may use the variable
+        self.found = true;
+    }
+}
@@ -0,0 +1,76 @@
+//! Handling of variables missing from the context.
+//!
+//! By default, rendering a `{{ variable }}` that isn't in the context fails with
+//! [`crate::MinilateError::MissingVariable`]. The [`MissingPolicy`] enum lets previews
+//! and other tooling render something in its place instead, and [`RenderMode::Lenient`]
+//! renders nothing in its place, as many template engines do. Conditions and loops are
+//! not affected.
+//!
+//! # Example
+//!
+//! ```rust
+//! use minilate::{MinilateEngine, MinilateInterface, MissingPolicy};
+//!
+//! let mut engine = MinilateEngine::new();
+//! engine.set_missing_policy(MissingPolicy::NamedPlaceholder("⟪{name}⟫".to_string()));
+//! engine.add_template("greeting", "Hello, {{ name }}!").unwrap();
+//!
+//! let output = engine.render("greeting", None).unwrap();
+//! assert_eq!(output, "Hello, ⟪name⟫!");
+//! ```
+
+/// Selects what happens when a `{{ variable }}` being rendered is missing from the
+/// context, or has no data.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MissingPolicy {
+    /// Fail to render with `MissingVariable` or `MissingVariableData`.
+    #[default]
+    Error,
+    /// Output the given pattern in place of the variable, with every `{name}` in the
+    /// pattern replaced by the name of the missing variable. The output isn't escaped.
+    NamedPlaceholder(String),
+}
+
+impl MissingPolicy {
+    /// Returns the placeholder output for the missing variable `name`, or `None` if
+    /// rendering should fail instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use minilate::MissingPolicy;
+    ///
+    /// let policy = MissingPolicy::NamedPlaceholder("<{name}?>".to_string());
+    /// assert_eq!(policy.placeholder("user.email").as_deref(), Some("<user.email?>"));
+    /// assert_eq!(MissingPolicy::Error.placeholder("user.email"), None);
+    /// ```
+    pub fn placeholder(&self, name: &str) -> Option<String> {
+        match self {
+            Self::Error => None,
+            Self::NamedPlaceholder(pattern) => Some(pattern.replace("{name}", name)),
+        }
+    }
+}
+
+/// Selects whether rendering a `{{ variable }}` that is missing from the context is an
+/// error, set with [`crate::MinilateEngine::with_mode`].
+///
+/// Each mode is a shorthand for a [`MissingPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Fail to render with `MissingVariable` or `MissingVariableData`, like
+    /// [`MissingPolicy::Error`].
+    #[default]
+    Strict,
+    /// Output nothing in place of a missing variable or one without data.
+    Lenient,
+}
+
+impl From<RenderMode> for MissingPolicy {
+    fn from(mode: RenderMode) -> Self {
+        match mode {
+            RenderMode::Strict => Self::Error,
+            RenderMode::Lenient => Self::NamedPlaceholder(String::new()),
+        }
+    }
+}
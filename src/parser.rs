@@ -4,14 +4,21 @@
 //! Abstract Syntax Tree (AST), represented by [`crate::ast::AstNode`].
 //! It handles the syntax of the Minilate templating language, including:
 //!
-//! - Variable substitutions: `{{ variable_name }}`
-//! - Control flow blocks: `{{% if condition %}}...{{% endif %}}`, `{{% for var in iterable %}}...{{% endfor %}}`
-//! - Template inclusions: `{{<< sub_template.tmpl }}`
-//! - Escaping: `\{{` and `\{{%`
-//! - Line comments: `// this is a comment` (outside of tags)
+//! - Variable substitutions: `{{ variable_name }}`, optionally filtered: `{{ variable_name | trim }}`
+//! - Control flow blocks: `{{% if condition %}}...{{% endif %}}`, `{{% for var in iterable %}}...{{% empty %}}...{{% endfor %}}`
+//! - Repeated blocks: `{{% repeat 3 %}}...{{% endrepeat %}}`
+//! - Conditional iterables: `{{% for var in (condition ? iterable_a : iterable_b) %}}`
+//! - Template inclusions: `{{<< sub_template.tmpl }}` (ignoring any trailing `/` or `.`), or `{{<< "partials/{{ kind }}" }}` to build the name from variables,
+//! - Whitespace trim markers: `{{- variable -}}`, `{{-% if condition -%}}` and `{{<<- sub_template -}}`
+//! - Escaping: `\{{` and `\{{%`, or whole blocks of literal text: `{{% raw %}}{{ not_a_variable }}{{% endraw %}}`
+//! - Line comments inside tags, running to the end of the line: `// this is a comment` (removed by [`strip_comments()`])
+//! - Aliases for long variable names, scoped to the enclosing block: `{{% alias name = user.profile.name %}}`, resolved to the full name while rendering
+//! - Block comments, which may span multiple lines and never reach the output: `{{# this is a note #}}`
+//! - Context dumps for debugging: `{{% debug %}}`, and debug-only blocks: `{{% ifdebug %}}...{{% endifdebug %}}`
 //!
-//! The main entry point for parsing is the [`tokenize()`] function, which takes a
-//! template string as input and returns a `Result<AstNode<'_>, ParseError>`.
+//! The main entry point for parsing is the [`tokenize_with()`] function, which takes a
+//! template string and the [`DelimiterConfig`] it is written with as input, and returns
+//! a `Result<AstNode<'_>, ParseError>`.
 //! If parsing is successful, it yields an [`AstNode::Root`] containing the parsed
 //! structure of the template. If syntax errors are encountered, a [`ParseError`]
 //! is returned, providing details about the error location (line and column) and
@@ -22,36 +29,39 @@
 //! The parser uses a combination of techniques:
 //! - It iteratively consumes the input, distinguishing between constant text,
 //!   variable tags, and control flow tags.
-//! - For conditional expressions within `if` statements (`!`, `&&`, `||`), it employs
-//!   a recursive descent parser to handle operator precedence correctly.
+//! - For conditional expressions within `if` statements (`!`, `&&`, `^^`, `||`, from
+//!   tightest to loosest binding), it employs a recursive descent parser to handle
+//!   operator precedence correctly.
 //!
 //! The parser is designed to borrow the input string where possible (e.g., for
 //! variable names and constant blocks without escapes) to minimize allocations.
 //!
 //! ## Example
 //!
+//! The parser is used through [`crate::Template::new`], which reports any syntax errors
+//! as [`crate::MinilateError::Parse`]:
+//!
 //! ```rust
-//! use minilate::parser::tokenize; // Assuming tokenize is made public or used via a facade
-//! use minilate::ast::AstNode;
-//! use std::borrow::Cow;
+//! use minilate::{Context, MinilateEngine, MinilateError, Template, VariableTy};
+//!
+//! let template =
+//!     Template::new("Hello, {{ name }}! {{% if show_details %}}Details here.{{% endif %}}")
+//!         .unwrap();
 //!
-//! # fn run_example() -> Result<(), minilate::error::ParseError> {
-//! let template_string = "Hello, {{ name }}! {{% if show_details %}}Details here.{{% endif %}}";
-//! let ast_root_result = tokenize(template_string);
+//! let context = Context::new()
+//!     .insert("name", VariableTy::String.with_data("Alice"))
+//!     .insert("show_details", VariableTy::Boolean.with_data("true"))
+//!     .to_owned();
+//! assert_eq!(
+//!     template.render::<MinilateEngine>(&context, None).unwrap(),
+//!     "Hello, Alice! Details here."
+//! );
 //!
-//! match ast_root_result {
-//!     Ok(AstNode::Root(nodes)) => {
-//!         // `nodes` would contain AstNode::Constant, AstNode::Variable, and AstNode::If
-//!         assert!(nodes.len() > 1);
-//!         println!("Successfully parsed template!");
-//!     }
-//!     Ok_ => panic!("Expected AstNode::Root"), // Should be AstNode::Root
-//!     Err(parse_error) => {
-//!         eprintln!("Failed to parse template: {}", parse_error);
-//!     }
-//! }
-//! # Ok(())
-//! # }
+//! // The `if` block is never closed
+//! assert!(matches!(
+//!     Template::new("{{% if show_details %}}Details here."),
+//!     Err(MinilateError::Parse(_))
+//! ));
 //! ```
 //!
 //! This module works in conjunction with [`crate::ast`] (for the tree structure)
@@ -61,28 +71,185 @@
 use std::borrow::Cow;
 
 use crate::{
-    ast::AstNode,
+    ast::{AstNode, CompareOp, Trim},
     error::{ParseError, ParseErrorKind},
 };
 
 type ParseResult<T> = Result<T, ParseError>;
 
-struct Parser<'a> {
+/// The delimiters marking up a template, defaulting to `{{ }}` tags, `{{% %}}` blocks,
+/// `{{<< }}` includes and `{{# #}}` comments.
+///
+/// Switching the delimiters lets Minilate process files that already use `{{` for
+/// something else. Each tag is built from the `open` and `close` delimiters, with a
+/// marker just inside them to distinguish blocks, includes and comments. The `-` trim
+/// marker and the `\` escape are the same for all delimiters.
+///
+/// # Example
+///
+/// ```
+/// use minilate::{Context, DelimiterConfig, MinilateEngine, Template, VariableTy};
+///
+/// let delimiters = DelimiterConfig {
+///     open: "<".to_string(),
+///     close: ">".to_string(),
+///     ..DelimiterConfig::default()
+/// };
+/// let template =
+///     Template::new_with_delimiters("{{ x }}: <% if x %><x><% endif %>", &delimiters).unwrap();
+///
+/// let mut context = Context::new();
+/// context.insert("x", VariableTy::String.with_data("1"));
+/// assert_eq!(template.render::<MinilateEngine>(&context, None).unwrap(), "{{ x }}: 1");
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DelimiterConfig {
+    /// Opens every tag, `{{` by default.
+    pub open: String,
+    /// Closes every tag, `}}` by default.
+    pub close: String,
+    /// Follows `open` and precedes `close` in a block tag, `%` by default.
+    pub block: String,
+    /// Follows `open` in an include tag, `<<` by default.
+    pub include: String,
+    /// Follows `open` and precedes `close` in a comment, `#` by default.
+    pub comment: String,
+}
+
+impl Default for DelimiterConfig {
+    fn default() -> Self {
+        Self {
+            open: "{{".to_string(),
+            close: "}}".to_string(),
+            block: "%".to_string(),
+            include: "<<".to_string(),
+            comment: "#".to_string(),
+        }
+    }
+}
+
+impl DelimiterConfig {
+    /// Returns a description of why these delimiters can't be parsed unambiguously, if so.
+    fn invalid_reason(&self) -> Option<&'static str> {
+        let markers = [&self.block, &self.include, &self.comment];
+        if self.open.is_empty() || self.close.is_empty() || markers.iter().any(|m| m.is_empty()) {
+            return Some("Delimiters and markers must not be empty");
+        }
+        if markers.iter().any(|marker| marker.starts_with('-')) {
+            return Some("Markers must not start with the `-` trim marker");
+        }
+        let overlapping = markers.iter().enumerate().any(|(i, marker)| {
+            markers
+                .iter()
+                .enumerate()
+                .any(|(j, other)| i != j && other.starts_with(marker.as_str()))
+        });
+        if overlapping {
+            return Some("The block, include and comment markers must not start with each other");
+        }
+        None
+    }
+}
+
+/// The tokens of a [`DelimiterConfig`], combined up front so the parser can match them
+/// directly.
+struct Tokens {
+    /// `{{`
+    open: String,
+    /// `}}`
+    close: String,
+    /// `{{-`
+    open_trim: String,
+    /// `{{%`
+    block_open: String,
+    /// `{{-%`
+    block_open_trim: String,
+    /// `%`
+    block: String,
+    /// `%}}`
+    block_close: String,
+    /// `-%}}`
+    block_close_trim: String,
+    /// `<<`
+    include: String,
+    /// `{{<<-`
+    include_open_trim: String,
+    /// `{{#`
+    comment_open: String,
+    /// `#}}`
+    comment_close: String,
+}
+
+impl Tokens {
+    fn new(delimiters: &DelimiterConfig) -> Self {
+        let DelimiterConfig {
+            open,
+            close,
+            block,
+            include,
+            comment,
+        } = delimiters;
+        Self {
+            open: open.clone(),
+            close: close.clone(),
+            open_trim: format!("{open}-"),
+            block_open: format!("{open}{block}"),
+            block_open_trim: format!("{open}-{block}"),
+            block: block.clone(),
+            block_close: format!("{block}{close}"),
+            block_close_trim: format!("-{block}{close}"),
+            include: include.clone(),
+            include_open_trim: format!("{open}{include}-"),
+            comment_open: format!("{open}{comment}"),
+            comment_close: format!("{comment}{close}"),
+        }
+    }
+
+    /// Returns the block tag for `keyword`, such as `{{% endif %}}`, for error messages.
+    fn block_tag(&self, keyword: &str) -> String {
+        format!("{} {} {}", self.block_open, keyword, self.block_close)
+    }
+}
+
+struct Parser<'a, 't> {
     input: &'a str,
+    /// The delimiters being parsed
+    tokens: &'t Tokens,
     pos: usize,
     /// Current line number (1-indexed)
     line: usize,
     /// The starting location of the current line
     line_start_pos: usize,
+    /// Whether the last tag ended with a `-` trim marker, so the start of the following
+    /// constant text should be trimmed
+    trim_next: bool,
+    /// Whether `{{% ... %}}` tags with an unknown keyword are kept as constant text
+    /// rather than rejected
+    preserve_unknown_directives: bool,
+    /// The number of for-loop and repeat bodies being parsed, in which `{{% break %}}` and
+    /// `{{% continue %}}` are allowed
+    loop_depth: usize,
+    /// The number of blocks, such as `{{% if %}}`, opened but not yet closed
+    open_blocks: usize,
+    /// The number of blocks left open by errors recovered from, whose closing tags are
+    /// no longer misplaced, see [`tokenize_all`]
+    interrupted_blocks: usize,
 }
 
-impl<'a> Parser<'a> {
-    const fn new(input: &'a str) -> Self {
+impl<'a, 't> Parser<'a, 't> {
+    const fn new(input: &'a str, tokens: &'t Tokens, preserve_unknown_directives: bool) -> Self {
         Parser {
             input,
+            tokens,
             pos: 0,
             line: 1,
             line_start_pos: 0,
+            trim_next: false,
+            preserve_unknown_directives,
+            loop_depth: 0,
+            open_blocks: 0,
+            interrupted_blocks: 0,
         }
     }
 
@@ -92,12 +259,20 @@ impl<'a> Parser<'a> {
         self.pos - self.line_start_pos + 1
     }
 
-    #[inline]
-    const fn make_error(&self, kind: ParseErrorKind) -> ParseError {
+    /// Returns an error of the given `kind` at the current position, along with the line
+    /// of input it is on.
+    fn make_error(&self, kind: ParseErrorKind) -> ParseError {
+        let source_line = self
+            .input
+            .get(self.line_start_pos..)
+            .and_then(|rest| rest.split('\n').next())
+            .unwrap_or_default()
+            .trim_end_matches('\r');
         ParseError {
             line: self.line,
             column: self.current_column(),
             kind,
+            source_line: Some(source_line.to_string()),
         }
     }
 
@@ -139,30 +314,62 @@ impl<'a> Parser<'a> {
             .is_some_and(|slice| slice.starts_with(s))
     }
 
-    /// Multi-token peek which checks if the remaining input starts with any of the provided tokens, ignoring whitespace between.
+    /// Peek if the remaining input starts with a block tag, `{{%` or `{{-%`
+    #[inline]
+    fn peek_block_start(&self) -> bool {
+        self.peek(&self.tokens.block_open) || self.peek(&self.tokens.block_open_trim)
+    }
+
+    /// Peek if the remaining input starts with a block tag made up of `keywords`, ignoring
+    /// whitespace between, and followed by the end of the tag if `closed`.
+    ///
+    /// The block delimiters `{{%` and `%}}` also match their trim marker forms `{{-%` and `-%}}`.
     #[allow(clippy::arithmetic_side_effects, reason = "usize value used")]
-    fn peek_n<const N: usize>(&self, tokens: [&str; N]) -> bool {
-        if !self.peek(tokens[0]) {
+    fn peek_block_tag(&self, keywords: &[&str], closed: bool) -> bool {
+        let first_len = if self.peek(&self.tokens.block_open_trim) {
+            self.tokens.block_open_trim.len()
+        } else if self.peek(&self.tokens.block_open) {
+            self.tokens.block_open.len()
+        } else {
             return false;
-        }
+        };
 
         let mut parser = Self {
             input: self.input,
-            pos: self.pos + tokens[0].len(),
+            tokens: self.tokens,
+            pos: self.pos + first_len,
             line: self.line,
             line_start_pos: self.line_start_pos,
+            trim_next: false,
+            preserve_unknown_directives: self.preserve_unknown_directives,
+            loop_depth: 0,
+            open_blocks: 0,
+            interrupted_blocks: 0,
         };
 
-        for token in &tokens[1..] {
+        for keyword in keywords {
             parser.consume_whitespace();
-            if !parser.peek(token) {
+            if !parser.consume(keyword) {
                 return false;
             }
-            parser.advance_bytes_no_newline(token.len());
+        }
+        if closed {
+            parser.consume_whitespace();
+            return parser.peek(&parser.tokens.block_close)
+                || parser.peek(&parser.tokens.block_close_trim);
         }
         true
     }
 
+    /// Peek if the remaining input starts with an ASCII digit
+    #[inline]
+    fn peek_digit(&self) -> bool {
+        self.input
+            .get(self.pos..)
+            .and_then(|s| s.chars().next())
+            .is_some_and(|c| c.is_ascii_digit())
+    }
+
     /// Consume `s` if the remaining input starts with it.
     /// Assumes `s` does not contain newlines.
     #[inline]
@@ -248,8 +455,49 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Consume the `{{` opening a tag, along with an optional `-` trim marker.
+    ///
+    /// Any trim marker at the end of the previous tag is dropped, as there is no constant
+    /// text between the two tags for it to trim.
+    fn expect_tag_start(&mut self) -> ParseResult<()> {
+        self.trim_next = false;
+        self.expect(&self.tokens.open)?;
+        self.consume("-");
+        Ok(())
+    }
+
+    /// Consume the `{{%` or `{{-%` opening a block tag.
+    fn expect_block_start(&mut self) -> ParseResult<()> {
+        self.expect_tag_start()?;
+        self.expect(&self.tokens.block)
+    }
+
+    /// Consume the `}}` closing a tag, recording whether it is preceded by a `-` trim marker.
+    fn expect_tag_end(&mut self) -> ParseResult<()> {
+        self.trim_next = self.consume("-");
+        self.expect(&self.tokens.close)
+    }
+
+    /// Consume the `%}}` closing a block tag, recording whether it is preceded by a `-`
+    /// trim marker.
+    fn expect_block_end(&mut self) -> ParseResult<()> {
+        self.trim_next = self.consume("-");
+        self.expect(&self.tokens.block_close)
+    }
+
     /// Consume and return an identifier (alphanumeric + '_' + '.')
     fn consume_identifier(&mut self) -> ParseResult<&'a str> {
+        self.consume_identifier_with(&[])
+    }
+
+    /// Consume and return a template name for an include, which is an identifier that may
+    /// also contain path separators ('/' or '\').
+    fn consume_template_name(&mut self) -> ParseResult<&'a str> {
+        self.consume_identifier_with(&['/', '\\'])
+    }
+
+    /// Consume and return an identifier, additionally allowing any of the `extra` characters.
+    fn consume_identifier_with(&mut self, extra: &[char]) -> ParseResult<&'a str> {
         self.consume_whitespace();
         let start = self.pos;
         while !self.eof() {
@@ -262,6 +510,7 @@ impl<'a> Parser<'a> {
             if current_char.is_ascii_alphanumeric()
                 || current_char == '_'
                 || current_char == '.'
+                || extra.contains(&current_char)
                 || !current_char.is_ascii()
             {
                 // Identifiers cannot span newlines
@@ -285,25 +534,38 @@ impl<'a> Parser<'a> {
     /// Parses a block of constant text until a delimiter or end_tag_hint is found.
     fn parse_constant(&mut self, end_tag_hint: Option<&str>) -> ParseResult<AstNode<'a>> {
         let start_pos = self.pos;
+        let trim_start = std::mem::take(&mut self.trim_next);
+        // Holds the text seen so far once it contains an escape and can't be borrowed
+        let mut escaped: Option<String> = None;
+        let mut segment_start = start_pos;
 
         while !self.eof() {
-            // Handle escaping with \{{ or \{{%
-            if self.peek("\\{{") {
-                // Skip the backslash but include the {{ in the constant
-                self.advance_bytes_no_newline(1); // Skip only the backslash
-                self.advance_bytes_no_newline(2); // Include the {{ in the constant
-                continue;
-            }
-
-            // Check for escaped {{%
-            if self.peek("\\{{%") {
-                // Skip the backslash but include the {{% in the constant
-                self.advance_bytes_no_newline(1); // Skip only the backslash
-                self.advance_bytes_no_newline(3); // Include the {{% in the constant
+            // Handle escaping with \{{%, \{{ or \}}, which drops the backslash. The longest
+            // escape is checked first, so that its `%` is part of the escape
+            let escape_len = self.input.get(self.pos..).and_then(|rest| {
+                let escaped = rest.strip_prefix('\\')?;
+                [
+                    &self.tokens.block_open,
+                    &self.tokens.open,
+                    &self.tokens.close,
+                ]
+                .into_iter()
+                .find(|delimiter| escaped.starts_with(delimiter.as_str()))
+                .map(String::len)
+            });
+            if let Some(escape_len) = escape_len {
+                let literal = self
+                    .input
+                    .get(segment_start..self.pos)
+                    .expect("Valid slice bounds");
+                escaped.get_or_insert_with(String::new).push_str(literal);
+                self.advance_bytes_no_newline(1); // Skip the backslash
+                segment_start = self.pos;
+                self.advance_bytes_no_newline(escape_len); // Include the delimiter in the constant
                 continue;
             }
 
-            if self.peek("{{") {
+            if self.peek(&self.tokens.open) {
                 // Catches both {{ and {{%
                 break;
             }
@@ -323,82 +585,307 @@ impl<'a> Parser<'a> {
 
         // Even if start_pos == self.pos (e.g. immediate delimiter), a Constant node is fine if it's empty.
         // The logic in parse_nodes_until handles whether to keep empty constants.
+        let literal = self
+            .input
+            .get(segment_start..self.pos)
+            .expect("Valid slice bounds");
+        let data = match escaped {
+            Some(mut escaped) => {
+                escaped.push_str(literal);
+                Cow::Owned(escaped)
+            }
+            None => Cow::Borrowed(literal),
+        };
         Ok(AstNode::Constant {
-            data: Cow::Borrowed(
-                self.input
-                    .get(start_pos..self.pos)
-                    .expect("Valid slice bounds"),
-            ),
+            data,
+            trim: Trim {
+                start: trim_start,
+                end: self.peek(&self.tokens.open_trim) || self.peek(&self.tokens.include_open_trim),
+            },
         })
     }
 
     fn parse_variable_or_comment(&mut self) -> ParseResult<AstNode<'a>> {
-        self.expect("{{")?;
+        self.expect_tag_start()?;
 
-        // Check for template inclusion
-        if self.consume("<<") {
+        // Check for template inclusion, which also accepts its trim marker as `{{<<-`
+        if self.consume(&self.tokens.include) {
+            self.consume("-");
             self.consume_whitespace();
-            let template_name = self.consume_identifier()?;
+            if self.peek("\"") {
+                return self.parse_quoted_include();
+            }
+            // Trailing separators and dots, as in `{{<< partial/ }}`, are stray and dropped
+            let template_name = self
+                .consume_template_name()?
+                .trim_end_matches(['/', '\\', '.']);
+            if template_name.is_empty() {
+                return Err(self.make_error(ParseErrorKind::Expected {
+                    description: "template name".to_string(),
+                }));
+            }
             self.consume(".tmpl");
             self.consume_whitespace();
-            self.expect("}}")?;
-            return Ok(AstNode::TemplateInclude { template_name });
+            let bindings = self.parse_include_bindings()?;
+            self.expect_tag_end()?;
+            return Ok(AstNode::TemplateInclude {
+                template_name,
+                bindings,
+            });
         }
 
         self.consume_whitespace();
         let name = self.consume_identifier()?;
+        let node = AstNode::Variable { name };
+        self.consume_whitespace();
+        let node = self.parse_filters(node)?;
+
+        self.expect_tag_end()?;
+        Ok(node)
+    }
+
+    /// Parses the comma-separated `name=value` bindings passed to an include, as in
+    /// `greeting name=full_name, polite=true`, up to the end of the tag. Each value is a
+    /// variable or a quoted string, number or boolean literal.
+    fn parse_include_bindings(&mut self) -> ParseResult<Vec<(&'a str, AstNode<'a>)>> {
+        let mut bindings = Vec::new();
+        if self.peek("-") || self.peek(&self.tokens.close) {
+            return Ok(bindings);
+        }
+
+        loop {
+            let name = self.consume_identifier()?;
+            self.consume_whitespace();
+            self.expect("=")?;
+            self.consume_whitespace();
+            if self.peek("(") {
+                return Err(self.make_error(ParseErrorKind::Expected {
+                    description: "variable or literal".to_string(),
+                }));
+            }
+            bindings.push((name, *self.parse_primary_expression()?));
+            self.consume_whitespace();
+            if !self.consume(",") {
+                return Ok(bindings);
+            }
+            self.consume_whitespace();
+        }
+    }
+
+    /// Parses any filters following `node`, as in `name | trim | default:"anon"`, applied
+    /// left to right.
+    fn parse_filters(&mut self, mut node: AstNode<'a>) -> ParseResult<AstNode<'a>> {
+        while self.consume("|") {
+            let filter = self.consume_identifier()?;
+            let argument = if self.consume(":") {
+                Some(self.consume_string_literal()?)
+            } else {
+                None
+            };
+            node = AstNode::Filter {
+                name: filter,
+                argument,
+                inner: Box::new(node),
+            };
+            self.consume_whitespace();
+        }
+        Ok(node)
+    }
+
+    /// Parses a quoted include name such as `"partials/{{ kind }}"`, and the end of the tag.
+    ///
+    /// `{{ variable }}` segments are interpolated at render time. Within the quotes, `\"`,
+    /// `\\` and `\{` produce a literal `"`, `\` and `{` respectively; any other backslash
+    /// is kept as-is so Windows-style paths need no escaping.
+    fn parse_quoted_include(&mut self) -> ParseResult<AstNode<'a>> {
+        self.expect("\"")?;
+
+        let mut parts = Vec::new();
+        // Holds the current literal segment once it contains an escape and can't be borrowed
+        let mut escaped = String::new();
+        let mut start = self.pos;
+
+        loop {
+            if self.eof() {
+                return Err(self.make_error(ParseErrorKind::unexpected_eof(Some("\"".to_string()))));
+            }
+
+            let at_escape = self.peek("\\\"") || self.peek("\\\\") || self.peek("\\{");
+            if at_escape || self.peek(&self.tokens.open) || self.peek("\"") {
+                // Flush the literal text seen so far
+                let literal = self.input.get(start..self.pos).expect("Valid slice bounds");
+                if at_escape {
+                    escaped.push_str(literal);
+                    self.advance_bytes_no_newline(1); // Skip the backslash
+                    start = self.pos;
+                    self.advance_bytes_no_newline(1); // Keep the escaped character
+                    continue;
+                }
+                if !escaped.is_empty() {
+                    escaped.push_str(literal);
+                    parts.push(AstNode::Constant {
+                        data: Cow::Owned(std::mem::take(&mut escaped)),
+                        trim: Trim::default(),
+                    });
+                } else if !literal.is_empty() {
+                    parts.push(AstNode::Constant {
+                        data: Cow::Borrowed(literal),
+                        trim: Trim::default(),
+                    });
+                }
+
+                if self.consume("\"") {
+                    break;
+                }
+
+                self.expect(&self.tokens.open)?;
+                let name = self.consume_identifier()?;
+                self.consume_whitespace();
+                self.expect(&self.tokens.close)?;
+                parts.push(AstNode::Variable { name });
+                start = self.pos;
+                continue;
+            }
+
+            let current_char = self
+                .input
+                .get(self.pos..)
+                .and_then(|s| s.chars().next())
+                .expect("Position within bounds due to !eof()");
+            self.advance_by_char(current_char, current_char.len_utf8());
+        }
+
+        if parts.is_empty() {
+            return Err(self.make_error(ParseErrorKind::Expected {
+                description: "template name".to_string(),
+            }));
+        }
+
         self.consume_whitespace();
-        self.expect("}}")?;
-        Ok(AstNode::Variable { name })
+        self.expect_tag_end()?;
+
+        // A name without interpolation or escapes is just a regular include
+        match parts.as_slice() {
+            [
+                AstNode::Constant {
+                    data: Cow::Borrowed(template_name),
+                    ..
+                },
+            ] => Ok(AstNode::TemplateInclude {
+                template_name,
+                bindings: Vec::new(),
+            }),
+            _ => Ok(AstNode::DynamicInclude { name: parts }),
+        }
     }
 
-    fn parse_node(&mut self) -> ParseResult<AstNode<'a>> {
-        if self.peek("{{%") {
-            self.parse_control_flow()
-        } else if self.peek("{{") {
-            self.parse_variable_or_comment()
+    /// Parses the next node, or returns `None` for a block comment, which produces no node.
+    fn parse_node(&mut self) -> ParseResult<Option<AstNode<'a>>> {
+        if self.peek(&self.tokens.comment_open) {
+            self.skip_block_comment()?;
+            Ok(None)
+        } else if self.peek_block_start() {
+            self.parse_control_flow().map(Some)
+        } else if self.peek(&self.tokens.open) {
+            self.parse_variable_or_comment().map(Some)
         } else {
             // If it's neither a control flow nor a variable, it must be constant text.
             // This constant text could be empty if we're at EOF or right before another tag.
-            self.parse_constant(None)
+            self.parse_constant(None).map(Some)
+        }
+    }
+
+    /// Consumes a `{{# ... #}}` block comment, which may span multiple lines.
+    fn skip_block_comment(&mut self) -> ParseResult<()> {
+        self.expect(&self.tokens.comment_open)?;
+        while !self.consume(&self.tokens.comment_close) {
+            let current_char = self
+                .input
+                .get(self.pos..)
+                .and_then(|s| s.chars().next())
+                .ok_or_else(|| {
+                    self.make_error(ParseErrorKind::unexpected_eof(Some(
+                        self.tokens.comment_close.clone(),
+                    )))
+                })?;
+            self.advance_by_char(current_char, current_char.len_utf8());
+        }
+        Ok(())
+    }
+
+    /// Recovers from a parse error by skipping to the start of the next tag, leaving any
+    /// blocks the error interrupted.
+    ///
+    /// Parsing last resumed at `resumed_at`, so the tag there is skipped even if the error
+    /// was found at its start, to guarantee progress.
+    fn skip_to_next_tag(&mut self, resumed_at: usize) {
+        self.loop_depth = 0;
+        self.interrupted_blocks = self
+            .interrupted_blocks
+            .saturating_add(std::mem::take(&mut self.open_blocks));
+        self.trim_next = false;
+        while let Some(current_char) = self.input.get(self.pos..).and_then(|s| s.chars().next()) {
+            if self.pos > resumed_at && self.peek(&self.tokens.open) {
+                break;
+            }
+            self.advance_by_char(current_char, current_char.len_utf8());
+        }
+    }
+
+    /// Returns true if `error` is only a closing or continuing tag, such as `{{% endif %}}`
+    /// or `{{% else %}}`, of a block interrupted by an earlier error, which a block closed
+    /// by it no longer is.
+    fn closes_interrupted_block(&mut self, error: &ParseError) -> bool {
+        let ParseErrorKind::UnknownKeyword { keyword } = &error.kind else {
+            return false;
+        };
+        if self.interrupted_blocks == 0 {
+            return false;
+        }
+        match keyword.as_str() {
+            "endif" | "endfor" | "endrepeat" | "endifdebug" | "endraw" => {
+                self.interrupted_blocks = self.interrupted_blocks.saturating_sub(1);
+                true
+            }
+            "else" | "empty" => true,
+            _ => false,
         }
     }
 
     /// Parse nodes until encountering a specific control tag with keyword
-    fn parse_nodes_until<const N: usize>(
-        &mut self,
-        end_tag: Option<[&str; N]>,
-    ) -> ParseResult<Vec<AstNode<'a>>> {
+    fn parse_nodes_until(&mut self, end_keyword: Option<&str>) -> ParseResult<Vec<AstNode<'a>>> {
         let mut nodes = Vec::new();
         loop {
             if self.eof() {
-                if let Some(tag) = end_tag {
-                    return Err(
-                        self.make_error(ParseErrorKind::unexpected_eof(Some(tag.join(" "))))
-                    );
+                if let Some(keyword) = end_keyword {
+                    return Err(self.make_error(ParseErrorKind::unexpected_eof(Some(
+                        self.tokens.block_tag(keyword),
+                    ))));
                 }
                 break;
             }
 
-            if let Some(tag) = end_tag {
-                if self.peek_n(tag) {
+            if let Some(keyword) = end_keyword {
+                if self.peek_block_tag(&[keyword], true) {
                     break;
                 }
             }
 
             let node_start_pos = self.pos;
-            let node = self.parse_node()?;
+            let Some(node) = self.parse_node()? else {
+                continue;
+            };
 
             // Avoid pushing empty constant nodes unless it's the only thing (e.g. empty template)
             // or if it represents consumed whitespace that is significant.
             // If parse_node returned an empty constant and pos hasn't changed, it means we are likely
             // right before a tag that parse_constant correctly stopped at.
-            if node_start_pos == self.pos && self.peek("{{") {
+            if node_start_pos == self.pos && self.peek(&self.tokens.open) {
                 // This empty constant was likely due to parse_constant stopping immediately.
                 // The next iteration will parse the tag. Don't add this empty node.
                 // However, if parse_node() itself advances pos (e.g. parsing a variable), this check is fine.
                 // This specific check is for an empty constant that consumed nothing.
-                if let AstNode::Constant { data } = &node {
+                if let AstNode::Constant { data, .. } = &node {
                     if data.is_empty() {
                         continue;
                     }
@@ -410,20 +897,32 @@ impl<'a> Parser<'a> {
     }
 
     // --- Condition Parsing (Recursive Descent for boolean expressions) ---
-    // Precedence: OR -> AND -> NOT -> Primary (variable)
+    // Precedence: OR -> XOR -> AND -> NOT -> Comparison -> Primary (group, variable or literal)
 
     // Entry point for parsing a condition expression
     fn parse_condition_expression(&mut self) -> ParseResult<Box<AstNode<'a>>> {
         self.parse_or_expression()
     }
 
+    /// Parses the condition following an `if` or `else if` keyword, reporting a missing
+    /// condition separately from a malformed one.
+    fn parse_if_condition(&mut self, keyword: &str) -> ParseResult<Box<AstNode<'a>>> {
+        self.consume_whitespace();
+        if self.peek(&self.tokens.block_close) || self.peek(&self.tokens.block_close_trim) {
+            return Err(self.make_error(ParseErrorKind::Expected {
+                description: format!("condition after `{keyword}`"),
+            }));
+        }
+        self.parse_condition_expression()
+    }
+
     // Handles OR (||)
     fn parse_or_expression(&mut self) -> ParseResult<Box<AstNode<'a>>> {
-        let mut left = self.parse_and_expression()?;
+        let mut left = self.parse_xor_expression()?;
         loop {
             self.consume_whitespace();
             if self.consume("||") {
-                let right = self.parse_and_expression()?;
+                let right = self.parse_xor_expression()?;
                 left = Box::new(AstNode::Or { left, right });
             } else {
                 break;
@@ -432,6 +931,21 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
+    // Handles XOR (^^), which binds tighter than OR but looser than AND
+    fn parse_xor_expression(&mut self) -> ParseResult<Box<AstNode<'a>>> {
+        let mut left = self.parse_and_expression()?;
+        loop {
+            self.consume_whitespace();
+            if self.consume("^^") {
+                let right = self.parse_and_expression()?;
+                left = Box::new(AstNode::Xor { left, right });
+            } else {
+                break;
+            }
+        }
+        Ok(left)
+    }
+
     // Handles AND (&&)
     fn parse_and_expression(&mut self) -> ParseResult<Box<AstNode<'a>>> {
         let mut left = self.parse_not_expression()?;
@@ -454,115 +968,493 @@ impl<'a> Parser<'a> {
             let condition = self.parse_not_expression()?;
             Ok(Box::new(AstNode::Not { condition }))
         } else {
-            self.parse_primary_expression()
+            self.parse_comparison_expression()
+        }
+    }
+
+    // Handles a primary expression optionally compared with `==`, `!=`, `<`, `>`, `<=` or `>=`
+    fn parse_comparison_expression(&mut self) -> ParseResult<Box<AstNode<'a>>> {
+        let left = self.parse_coalesce_expression()?;
+
+        self.consume_whitespace();
+        // Two character operators must be checked before their one character prefixes
+        let op = if self.consume("==") {
+            CompareOp::Eq
+        } else if self.consume("!=") {
+            CompareOp::Ne
+        } else if self.consume("<=") {
+            CompareOp::Le
+        } else if self.consume(">=") {
+            CompareOp::Ge
+        } else if self.consume("<") {
+            CompareOp::Lt
+        } else if self.consume(">") {
+            CompareOp::Gt
+        } else if self.peek("contains")
+            && !self
+                .input
+                .get(self.pos.saturating_add("contains".len())..)
+                .and_then(|rest| rest.chars().next())
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            self.consume("contains");
+            CompareOp::Contains
+        } else {
+            return Ok(left);
+        };
+        let right = self.parse_coalesce_expression()?;
+        Ok(Box::new(AstNode::Compare { left, op, right }))
+    }
+
+    // Handles the `??` fallback operator, which binds tighter than comparisons and groups
+    // to the right, so `a ?? b ?? "c" == d` is `(a ?? (b ?? "c")) == d`
+    fn parse_coalesce_expression(&mut self) -> ParseResult<Box<AstNode<'a>>> {
+        let value = self.parse_primary_expression()?;
+
+        self.consume_whitespace();
+        if !self.consume("??") {
+            return Ok(value);
         }
+        let fallback = self.parse_coalesce_expression()?;
+        Ok(Box::new(AstNode::Coalesce { value, fallback }))
     }
 
-    // Handles primary expressions (currently just variables)
+    // Handles primary expressions: a parenthesized condition, a variable, or a quoted string,
+    // number or boolean literal
     fn parse_primary_expression(&mut self) -> ParseResult<Box<AstNode<'a>>> {
         self.consume_whitespace();
-        let name = self.consume_identifier()?;
-        Ok(Box::new(AstNode::Variable { name }))
+        if self.consume("(") {
+            let condition = self.parse_condition_expression()?;
+            self.consume_whitespace();
+            self.expect(")")?;
+            return Ok(condition);
+        }
+        let mut chars = self.input.get(self.pos..).unwrap_or_default().chars();
+        let first = chars.next();
+        if first.is_some_and(|c| c.is_ascii_digit())
+            || (first == Some('-') && chars.next().is_some_and(|c| c.is_ascii_digit()))
+        {
+            return self.parse_number_literal();
+        }
+        if !self.peek("\"") {
+            // Only the whole identifiers `true` and `false` are literals, so variables such
+            // as `true_value` are unaffected
+            let name = self.consume_identifier()?;
+            return Ok(Box::new(match name {
+                "true" => AstNode::BoolLiteral { value: true },
+                "false" => AstNode::BoolLiteral { value: false },
+                _ => AstNode::Variable { name },
+            }));
+        }
+
+        let value = self.consume_string_literal()?;
+        Ok(Box::new(AstNode::StringLiteral { value }))
+    }
+
+    // Handles a quoted string such as `"active"`, returning its contents verbatim
+    fn consume_string_literal(&mut self) -> ParseResult<&'a str> {
+        self.expect("\"")?;
+        let start = self.pos;
+        while !self.peek("\"") {
+            if self.eof() {
+                return Err(self.make_error(ParseErrorKind::unexpected_eof(Some("\"".to_string()))));
+            }
+            let current_char = self
+                .input
+                .get(self.pos..)
+                .and_then(|s| s.chars().next())
+                .expect("Position within bounds due to !eof()");
+            self.advance_by_char(current_char, current_char.len_utf8());
+        }
+        let value = self.input.get(start..self.pos).expect("Valid slice bounds");
+        self.advance_bytes_no_newline(1); // Consume the closing quote
+
+        Ok(value)
+    }
+
+    // Handles a number literal such as `18`, `-2` or `0.5`
+    fn parse_number_literal(&mut self) -> ParseResult<Box<AstNode<'a>>> {
+        let start = self.pos;
+        self.consume("-");
+        while self
+            .input
+            .get(self.pos..)
+            .and_then(|s| s.chars().next())
+            .is_some_and(|c| c.is_ascii_digit() || c == '.')
+        {
+            self.advance_bytes_no_newline(1);
+        }
+
+        let value = self.input.get(start..self.pos).expect("Valid slice bounds");
+        if value.parse::<f64>().is_err() {
+            return Err(self.make_error(ParseErrorKind::Expected {
+                description: format!("number, found '{value}'"),
+            }));
+        }
+        Ok(Box::new(AstNode::NumberLiteral { value }))
     }
 
     // --- Control Flow Parsing ---
 
     fn parse_control_flow(&mut self) -> ParseResult<AstNode<'a>> {
-        self.expect("{{%")?;
+        let start = self.pos;
+        self.expect_block_start()?;
         self.consume_whitespace();
         let keyword = self.consume_identifier()?;
-        match keyword {
+        let opens_block = matches!(keyword, "if" | "for" | "repeat" | "ifdebug" | "raw");
+        if opens_block {
+            self.open_blocks = self.open_blocks.saturating_add(1);
+        }
+        let node = match keyword {
             "if" => self.parse_if_statement(),
             "for" => self.parse_for_loop(),
+            "repeat" => self.parse_repeat(),
+            "alias" => self.parse_alias(),
+            "set" => self.parse_set(),
+            "break" | "continue" => self.parse_loop_control(keyword),
+            "debug" => {
+                self.consume_whitespace();
+                self.expect_block_end()?;
+                Ok(AstNode::DebugDump)
+            }
+            "ifdebug" => {
+                self.consume_whitespace();
+                self.expect_block_end()?;
+                let body = self.parse_nodes_until(Some("endifdebug"))?;
+                self.expect_block_start()?;
+                self.consume_whitespace();
+                self.expect("endifdebug")?;
+                self.consume_whitespace();
+                self.expect_block_end()?;
+                Ok(AstNode::DebugOnly { body })
+            }
+            "raw" => self.parse_raw(),
+            // Minilate's own closing tags are never preserved, so a misplaced one still errors
+            "else" | "empty" | "endif" | "endfor" | "endrepeat" | "endifdebug" | "endraw" => {
+                Err(self.make_error(ParseErrorKind::UnknownKeyword {
+                    keyword: keyword.to_string(),
+                }))
+            }
+            _ if self.preserve_unknown_directives => self.parse_unknown_directive(start),
             _ => Err(self.make_error(ParseErrorKind::UnknownKeyword {
                 keyword: keyword.to_string(),
             })),
+        };
+        if opens_block && node.is_ok() {
+            self.open_blocks = self.open_blocks.saturating_sub(1);
         }
+        node
     }
 
-    fn parse_for_loop(&mut self) -> ParseResult<AstNode<'a>> {
+    /// Parses the rest of an `{{% alias short = long.path %}}` tag, which brings `short`
+    /// into scope until the end of the enclosing block or template when rendered.
+    fn parse_alias(&mut self) -> ParseResult<AstNode<'a>> {
         self.consume_whitespace();
-        let variable = self.consume_identifier()?;
+        let alias = self.consume_identifier()?;
         self.consume_whitespace();
-        self.expect("in")?;
+        self.expect("=")?;
         self.consume_whitespace();
-        let iterable = self.consume_identifier()?;
+        let target = self.consume_identifier()?;
         self.consume_whitespace();
-        self.expect("%}}")?;
+        self.expect_block_end()?;
+        Ok(AstNode::Alias {
+            name: alias,
+            target,
+        })
+    }
 
-        let body = self.parse_nodes_until(Some(["{{%", "endfor", "%}}"]))?;
-        self.expect("{{%")?;
+    /// Parses the rest of a `{{% set name = value %}}` tag, where the value is a variable,
+    /// string or number, optionally followed by filters.
+    fn parse_set(&mut self) -> ParseResult<AstNode<'a>> {
         self.consume_whitespace();
-        self.expect("endfor")?;
+        let name = self.consume_identifier()?;
         self.consume_whitespace();
-        self.expect("%}}")?;
+        self.expect("=")?;
+        self.consume_whitespace();
+        let value = if self.peek("\"") {
+            AstNode::StringLiteral {
+                value: self.consume_string_literal()?,
+            }
+        } else if self.peek_digit() || self.peek("-") {
+            *self.parse_number_literal()?
+        } else {
+            let target = self.consume_identifier()?;
+            AstNode::Variable { name: target }
+        };
+        self.consume_whitespace();
+        let value = self.parse_filters(value)?;
+        self.expect_block_end()?;
 
-        Ok(AstNode::For {
-            iterable,
-            variable,
-            body,
+        Ok(AstNode::Set {
+            name,
+            value: Box::new(value),
         })
     }
 
-    fn parse_if_statement(&mut self) -> ParseResult<AstNode<'a>> {
-        let condition = self.parse_condition_expression()?;
+    /// Parses the rest of a `{{% break %}}` or `{{% continue %}}` tag, which is only
+    /// allowed in the body of a for loop or repeat block.
+    fn parse_loop_control(&mut self, keyword: &str) -> ParseResult<AstNode<'a>> {
+        if self.loop_depth == 0 {
+            return Err(self.make_error(ParseErrorKind::OutsideLoop {
+                keyword: keyword.to_string(),
+            }));
+        }
         self.consume_whitespace();
-        self.expect("%}}")?;
-        self.parse_if_block_internal(condition)
+        self.expect_block_end()?;
+        Ok(if keyword == "break" {
+            AstNode::Break
+        } else {
+            AstNode::Continue
+        })
     }
 
-    fn parse_if_block_internal(
-        &mut self,
-        current_if_condition: Box<AstNode<'a>>,
-    ) -> ParseResult<AstNode<'a>> {
-        let mut body_nodes = Vec::new();
-        let mut else_branch_for_current_if: Option<Box<AstNode<'a>>> = None;
+    /// Parses the rest of a `{{% raw %}}...{{% endraw %}}` block, whose body is kept as
+    /// constant text without interpreting any delimiters inside it.
+    fn parse_raw(&mut self) -> ParseResult<AstNode<'a>> {
+        self.consume_whitespace();
+        self.expect_block_end()?;
 
-        'body_parsing_loop: loop {
+        let start = self.pos;
+        let trim_start = std::mem::take(&mut self.trim_next);
+        while !self.peek_block_tag(&["endraw"], true) {
+            let current_char = self
+                .input
+                .get(self.pos..)
+                .and_then(|s| s.chars().next())
+                .ok_or_else(|| {
+                    self.make_error(ParseErrorKind::unexpected_eof(Some(
+                        self.tokens.block_tag("endraw"),
+                    )))
+                })?;
+            self.advance_by_char(current_char, current_char.len_utf8());
+        }
+        let data = self.input.get(start..self.pos).expect("Valid slice bounds");
+        let trim = Trim {
+            start: trim_start,
+            end: self.peek(&self.tokens.open_trim),
+        };
+
+        self.expect_block_start()?;
+        self.consume_whitespace();
+        self.expect("endraw")?;
+        self.consume_whitespace();
+        self.expect_block_end()?;
+        Ok(AstNode::Constant {
+            data: Cow::Borrowed(data),
+            trim,
+        })
+    }
+
+    /// Consumes the rest of a directive with an unknown keyword, which began at `start`,
+    /// returning the whole tag verbatim as constant text.
+    fn parse_unknown_directive(&mut self, start: usize) -> ParseResult<AstNode<'a>> {
+        while !self.peek(&self.tokens.block_close) {
             if self.eof() {
                 return Err(self.make_error(ParseErrorKind::unexpected_eof(Some(
-                    "{{% endif %}} or {{% else %}} or {{% else if %}}".to_string(),
+                    self.tokens.block_close.clone(),
                 ))));
             }
+            let current_char = self
+                .input
+                .get(self.pos..)
+                .and_then(|s| s.chars().next())
+                .expect("Position within bounds due to !eof()");
+            self.advance_by_char(current_char, current_char.len_utf8());
+        }
+        self.advance_bytes_no_newline(self.tokens.block_close.len());
 
-            if self.peek_n(["{{%", "else", "if"]) {
-                self.expect("{{%")?;
-                self.consume_whitespace();
-                self.expect("else")?;
-                self.consume_whitespace();
-                self.expect("if")?;
-                self.consume_whitespace();
-                let next_if_condition = self.parse_condition_expression()?;
-                self.consume_whitespace();
-                self.expect("%}}")?;
+        let data = self.input.get(start..self.pos).expect("Valid slice bounds");
+        Ok(AstNode::Constant {
+            data: Cow::Borrowed(data),
+            trim: Trim::default(),
+        })
+    }
 
-                let nested_if_node = self.parse_if_block_internal(next_if_condition)?;
+    fn parse_for_loop(&mut self) -> ParseResult<AstNode<'a>> {
+        self.consume_whitespace();
+        let variable = self.consume_identifier()?;
+        self.consume_whitespace();
+        let value_variable = if self.consume(",") {
+            self.consume_whitespace();
+            let value_variable = self.consume_identifier()?;
+            self.consume_whitespace();
+            Some(value_variable)
+        } else {
+            None
+        };
+        self.expect("in")?;
+        self.consume_whitespace();
+        let iterable = self.parse_iterable()?;
+        self.consume_whitespace();
+        self.expect_block_end()?;
+
+        let mut body = Vec::new();
+        let mut empty_branch = None;
+
+        // Only the body may break out of or continue the loop, not the empty branch
+        self.loop_depth = self.loop_depth.saturating_add(1);
+        loop {
+            if self.eof() {
+                return Err(self.make_error(ParseErrorKind::unexpected_eof(Some(format!(
+                    "{} or {}",
+                    self.tokens.block_tag("endfor"),
+                    self.tokens.block_tag("empty")
+                )))));
+            }
+
+            if self.peek_block_tag(&["empty"], true) {
+                self.expect_block_start()?;
+                self.consume_whitespace();
+                self.expect("empty")?;
+                self.consume_whitespace();
+                self.expect_block_end()?;
+                // The empty branch runs until the end of the loop
+                self.loop_depth = self.loop_depth.saturating_sub(1);
+                let empty_body = self.parse_nodes_until(Some("endfor"))?;
+                empty_branch = Some(Box::new(AstNode::Root(empty_body)));
+                break;
+            } else if self.peek_block_tag(&["endfor"], true) {
+                self.loop_depth = self.loop_depth.saturating_sub(1);
+                break;
+            } else {
+                body.extend(self.parse_node()?);
+            }
+        }
+
+        self.expect_block_start()?;
+        self.consume_whitespace();
+        self.expect("endfor")?;
+        self.consume_whitespace();
+        self.expect_block_end()?;
+
+        Ok(AstNode::For {
+            iterable,
+            variable,
+            value_variable,
+            body,
+            empty_branch,
+        })
+    }
+
+    fn parse_repeat(&mut self) -> ParseResult<AstNode<'a>> {
+        self.consume_whitespace();
+        let start = self.pos;
+        while self.peek_digit() {
+            self.advance_bytes_no_newline(1);
+        }
+        let count = self
+            .input
+            .get(start..self.pos)
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| {
+                self.make_error(ParseErrorKind::Expected {
+                    description: "repeat count".to_string(),
+                })
+            })?;
+        self.consume_whitespace();
+        self.expect_block_end()?;
+
+        // A repeat block is a loop of its own, which a break or continue inside it targets
+        self.loop_depth = self.loop_depth.saturating_add(1);
+        let body = self.parse_nodes_until(Some("endrepeat"));
+        self.loop_depth = self.loop_depth.saturating_sub(1);
+        let body = body?;
+        self.expect_block_start()?;
+        self.consume_whitespace();
+        self.expect("endrepeat")?;
+        self.consume_whitespace();
+        self.expect_block_end()?;
+
+        Ok(AstNode::Repeat { count, body })
+    }
+
+    // Handles a for-loop iterable, either a variable or a parenthesized `(cond ? a : b)` ternary
+    fn parse_iterable(&mut self) -> ParseResult<Box<AstNode<'a>>> {
+        self.consume_whitespace();
+        if !self.consume("(") {
+            let name = self.consume_identifier()?;
+            return Ok(Box::new(AstNode::Variable { name }));
+        }
+
+        let condition = self.parse_condition_expression()?;
+        self.consume_whitespace();
+        self.expect("?")?;
+        let if_true = self.parse_iterable()?;
+        self.consume_whitespace();
+        self.expect(":")?;
+        let if_false = self.parse_iterable()?;
+        self.consume_whitespace();
+        self.expect(")")?;
+
+        Ok(Box::new(AstNode::Ternary {
+            condition,
+            if_true,
+            if_false,
+        }))
+    }
+
+    fn parse_if_statement(&mut self) -> ParseResult<AstNode<'a>> {
+        let condition = self.parse_if_condition("if")?;
+        self.consume_whitespace();
+        self.expect_block_end()?;
+        self.parse_if_block_internal(condition)
+    }
+
+    fn parse_if_block_internal(
+        &mut self,
+        current_if_condition: Box<AstNode<'a>>,
+    ) -> ParseResult<AstNode<'a>> {
+        let mut body_nodes = Vec::new();
+        let mut else_branch_for_current_if: Option<Box<AstNode<'a>>> = None;
+
+        'body_parsing_loop: loop {
+            if self.eof() {
+                return Err(self.make_error(ParseErrorKind::unexpected_eof(Some(format!(
+                    "{} or {} or {}",
+                    self.tokens.block_tag("endif"),
+                    self.tokens.block_tag("else"),
+                    self.tokens.block_tag("else if")
+                )))));
+            }
+
+            if self.peek_block_tag(&["else", "if"], false) {
+                self.expect_block_start()?;
+                self.consume_whitespace();
+                self.expect("else")?;
+                self.consume_whitespace();
+                self.expect("if")?;
+                self.consume_whitespace();
+                let next_if_condition = self.parse_if_condition("else if")?;
+                self.consume_whitespace();
+                self.expect_block_end()?;
+
+                let nested_if_node = self.parse_if_block_internal(next_if_condition)?;
                 else_branch_for_current_if = Some(Box::new(AstNode::Root(vec![nested_if_node])));
                 break 'body_parsing_loop;
-            } else if self.peek_n(["{{%", "else", "%}}"]) {
-                self.expect("{{%")?;
+            } else if self.peek_block_tag(&["else"], true) {
+                self.expect_block_start()?;
                 self.consume_whitespace();
                 self.expect("else")?;
                 self.consume_whitespace();
-                self.expect("%}}")?;
+                self.expect_block_end()?;
                 // Now parse the else body until we hit the end of the if block
-                let else_body = self.parse_nodes_until(Some(["{{%", "endif", "%}}"]))?;
-                self.expect("{{%")?;
+                let else_body = self.parse_nodes_until(Some("endif"))?;
+                self.expect_block_start()?;
                 self.consume_whitespace();
                 self.expect("endif")?;
                 self.consume_whitespace();
-                self.expect("%}}")?;
+                self.expect_block_end()?;
                 else_branch_for_current_if = Some(Box::new(AstNode::Root(else_body)));
                 break 'body_parsing_loop;
-            } else if self.peek_n(["{{%", "endif", "%}}"]) {
-                self.expect("{{%")?;
+            } else if self.peek_block_tag(&["endif"], true) {
+                self.expect_block_start()?;
                 self.consume_whitespace();
                 self.expect("endif")?;
                 self.consume_whitespace();
-                self.expect("%}}")?;
+                self.expect_block_end()?;
                 break 'body_parsing_loop;
             } else {
-                body_nodes.push(self.parse_node()?);
+                body_nodes.extend(self.parse_node()?);
             }
         }
 
@@ -574,12 +1466,76 @@ impl<'a> Parser<'a> {
     }
 }
 
+#[cfg(test)]
 pub fn tokenize(input: &str) -> Result<AstNode<'_>, ParseError> {
+    tokenize_with(input, false, &DelimiterConfig::default(), None)
+}
+
+/// Parses `input` like [`tokenize`], but rather than stopping at the first error, reports
+/// every error it can find.
+///
+/// After each error, parsing resumes at the next tag beyond where the error was found, so
+/// a template with several mistakes can be fixed in one go. The closing tags of blocks an
+/// error interrupted, such as the `{{% endif %}}` of an `if` with a mistake inside it, are
+/// expected rather than reported as misplaced. Recovery is otherwise best-effort, and the
+/// text skipped over may hide further errors.
+pub fn tokenize_all(input: &str) -> Result<AstNode<'_>, Vec<ParseError>> {
+    let tokens = Tokens::new(&DelimiterConfig::default());
+    let mut parser = Parser::new(input, &tokens, false);
+    let mut errors = Vec::new();
+    loop {
+        let resumed_at = parser.pos;
+        match parser.parse_nodes_until(None) {
+            Ok(nodes) if errors.is_empty() => return Ok(AstNode::Root(nodes)),
+            Ok(_) => return Err(errors),
+            Err(error) => {
+                if !parser.closes_interrupted_block(&error) {
+                    errors.push(error);
+                }
+                parser.skip_to_next_tag(resumed_at);
+            }
+        }
+    }
+}
+
+/// Parses `input` with the given `delimiters`.
+///
+/// With `preserve_unknown_directives`, any `{{% ... %}}` tag with an unknown keyword is
+/// kept as constant text rather than failing with [`ParseErrorKind::UnknownKeyword`].
+/// Input longer than `max_length` bytes fails with [`ParseErrorKind::InputTooLong`]
+/// before any parsing is done.
+pub fn tokenize_with<'a>(
+    input: &'a str,
+    preserve_unknown_directives: bool,
+    delimiters: &DelimiterConfig,
+    max_length: Option<usize>,
+) -> Result<AstNode<'a>, ParseError> {
+    if let Some(max_length) = max_length.filter(|max_length| input.len() > *max_length) {
+        return Err(ParseError {
+            line: 1,
+            column: 1,
+            kind: ParseErrorKind::InputTooLong {
+                length: input.len(),
+                max_length,
+            },
+            // The input as a whole is at fault, rather than any one line of it
+            source_line: None,
+        });
+    }
+    if let Some(reason) = delimiters.invalid_reason() {
+        return Err(ParseError {
+            line: 1,
+            column: 1,
+            kind: ParseErrorKind::Message(reason.to_string()),
+            source_line: None,
+        });
+    }
     if input.is_empty() {
         return Ok(AstNode::Root(vec![]));
     }
-    let mut parser = Parser::new(input);
-    let nodes = parser.parse_nodes_until::<3>(None)?; // generic of 3 to avoid extra monomorphization
+    let tokens = Tokens::new(delimiters);
+    let mut parser = Parser::new(input, &tokens, preserve_unknown_directives);
+    let nodes = parser.parse_nodes_until(None)?;
 
     if !parser.eof() {
         return Err(parser.make_error(ParseErrorKind::Message(format!(
@@ -594,6 +1550,99 @@ pub fn tokenize(input: &str) -> Result<AstNode<'_>, ParseError> {
     Ok(AstNode::Root(nodes))
 }
 
+/// Removes the `//` line comments and `{{# ... #}}` block comments from a template's
+/// source, returning source that renders the same output.
+///
+/// Line comments are only recognised inside tags, where each run of whitespace and comments
+/// is collapsed to a single space. Everything else is kept verbatim, including text
+/// outside of tags (which is output as written, `//` and all) and quoted strings.
+///
+/// ```
+/// let src = "{{% if a // only when a is set\n %}}{{ a }}{{% endif %}}";
+/// assert_eq!(minilate::strip_comments(src), "{{% if a %}}{{ a }}{{% endif %}}");
+/// assert_eq!(minilate::strip_comments("a{{# note #}}b"), "ab");
+/// ```
+pub fn strip_comments(src: &str) -> String {
+    let mut output = String::with_capacity(src.len());
+    let mut pos = 0;
+    while let Some(rest) = src.get(pos..).filter(|rest| !rest.is_empty()) {
+        let Some(start) = rest.find("{{") else {
+            output.push_str(rest);
+            break;
+        };
+        let text = rest.get(..start).unwrap_or_default();
+        output.push_str(text);
+        pos = pos.saturating_add(start).saturating_add(2);
+        // An escaped tag is just text
+        if text.ends_with('\\') {
+            output.push_str("{{");
+        } else if src.get(pos..).is_some_and(|rest| rest.starts_with('#')) {
+            // Block comments are dropped entirely
+            pos = src
+                .get(pos..)
+                .and_then(|rest| rest.find("#}}"))
+                .map_or(src.len(), |end| pos.saturating_add(end).saturating_add(3));
+        } else {
+            output.push_str("{{");
+            pos = strip_tag_comments(src, pos, &mut output);
+        }
+    }
+    output
+}
+
+/// Copies the rest of the tag at `pos`, just after its opening `{{`, to `output` for
+/// [`strip_comments`], returning the position after the tag's closing delimiter.
+fn strip_tag_comments(src: &str, mut pos: usize, output: &mut String) -> usize {
+    let rest = src.get(pos..).unwrap_or_default();
+    let end = if rest.starts_with('%') || rest.starts_with("-%") {
+        "%}}"
+    } else {
+        "}}"
+    };
+    let mut in_string = false;
+    let mut pending_space = false;
+    while let Some(rest) = src.get(pos..) {
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        if in_string {
+            output.push(c);
+            pos = pos.saturating_add(c.len_utf8());
+            if c == '\\' {
+                // Keep the escaped character, even if it is a quote
+                if let Some(escaped) = src.get(pos..).and_then(|rest| rest.chars().next()) {
+                    output.push(escaped);
+                    pos = pos.saturating_add(escaped.len_utf8());
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if rest.starts_with("//") {
+            // A comment ends at and includes the newline
+            pos = rest.find('\n').map_or(src.len(), |newline| {
+                pos.saturating_add(newline).saturating_add(1)
+            });
+            pending_space = true;
+        } else if c.is_ascii_whitespace() {
+            pos = pos.saturating_add(1);
+            pending_space = true;
+        } else {
+            if pending_space {
+                output.push(' ');
+                pending_space = false;
+            }
+            if rest.starts_with(end) {
+                output.push_str(end);
+                return pos.saturating_add(end.len());
+            }
+            output.push(c);
+            pos = pos.saturating_add(c.len_utf8());
+            in_string = c == '"';
+        }
+    }
+    pos
+}
+
 /// Tests for individual functions in the paresr module.
 #[cfg(test)]
 mod test_utils {
@@ -602,23 +1651,25 @@ mod test_utils {
     #[test]
     #[ntest::timeout(100)]
     fn test_peek_any() {
-        let parser = Parser::new("  {{%     if condition %}}");
-        assert!(!parser.peek_n(["{{%", "if"]));
-        assert!(!parser.peek_n(["{{%", "else"]));
-        assert!(!parser.peek_n(["{{%", "if", "else"]));
-        assert!(!parser.peek_n(["{{%", "endif"]));
-
-        let parser = Parser::new("{{%     if condition %}}");
-        assert!(parser.peek_n(["{{%", "if"]));
-        assert!(!parser.peek_n(["{{%", "else"]));
-        assert!(!parser.peek_n(["{{%", "if", "else"]));
-        assert!(!parser.peek_n(["{{%", "endif"]));
-
-        let parser = Parser::new("{{%if condition %}}");
-        assert!(parser.peek_n(["{{%", "if"]));
-        assert!(!parser.peek_n(["{{%", "else"]));
-        assert!(!parser.peek_n(["{{%", "if", "else"]));
-        assert!(!parser.peek_n(["{{%", "endif"]));
+        let tokens = Tokens::new(&DelimiterConfig::default());
+        let parser = Parser::new("  {{%     if condition %}}", &tokens, false);
+        assert!(!parser.peek_block_tag(&["if"], false));
+        assert!(!parser.peek_block_tag(&["else"], false));
+        assert!(!parser.peek_block_tag(&["if", "else"], false));
+        assert!(!parser.peek_block_tag(&["endif"], false));
+
+        let parser = Parser::new("{{%     if condition %}}", &tokens, false);
+        assert!(parser.peek_block_tag(&["if"], false));
+        assert!(!parser.peek_block_tag(&["else"], false));
+        assert!(!parser.peek_block_tag(&["if", "else"], false));
+        assert!(!parser.peek_block_tag(&["endif"], false));
+        assert!(!parser.peek_block_tag(&["if"], true));
+
+        let parser = Parser::new("{{%if condition %}}", &tokens, false);
+        assert!(parser.peek_block_tag(&["if"], false));
+        assert!(!parser.peek_block_tag(&["else"], false));
+        assert!(!parser.peek_block_tag(&["if", "else"], false));
+        assert!(!parser.peek_block_tag(&["endif"], false));
     }
 }
 
@@ -640,6 +1691,16 @@ mod tests {
         ($data:expr) => {
             AstNode::Constant {
                 data: Cow::Borrowed($data),
+                trim: Trim::default(),
+            }
+        };
+        ($data:expr, start: $start:expr, end: $end:expr) => {
+            AstNode::Constant {
+                data: Cow::Borrowed($data),
+                trim: Trim {
+                    start: $start,
+                    end: $end,
+                },
             }
         };
     }
@@ -699,35 +1760,608 @@ mod tests {
     #[ntest::timeout(100)]
     fn test_constant_ending_at_eof() {
         assert_eq!(
-            tokenize("text").unwrap(),
-            AstNode::Root(vec![const_str!("text")])
+            tokenize("text").unwrap(),
+            AstNode::Root(vec![const_str!("text")])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_simple_variable() {
+        assert_eq!(
+            tokenize("{{name}}").unwrap(),
+            AstNode::Root(vec![var!("name")])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_variable_with_whitespace() {
+        assert_eq!(
+            tokenize("{{ name }}").unwrap(),
+            AstNode::Root(vec![var!("name")])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_variable_with_dot() {
+        assert_eq!(
+            tokenize("{{ user.name }}").unwrap(),
+            AstNode::Root(vec![var!("user.name")])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_variable_with_filter() {
+        assert_eq!(
+            tokenize("{{ name | trim }}{{name|trim}}").unwrap(),
+            AstNode::Root(vec![
+                AstNode::Filter {
+                    name: "trim",
+                    argument: None,
+                    inner: Box::new(var!("name")),
+                },
+                AstNode::Filter {
+                    name: "trim",
+                    argument: None,
+                    inner: Box::new(var!("name")),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_variable_with_filter_argument() {
+        assert_eq!(
+            tokenize(r#"{{ nickname | default:"anon" | trim }}"#).unwrap(),
+            AstNode::Root(vec![AstNode::Filter {
+                name: "trim",
+                argument: None,
+                inner: Box::new(AstNode::Filter {
+                    name: "default",
+                    argument: Some("anon"),
+                    inner: Box::new(var!("nickname")),
+                }),
+            }])
+        );
+
+        let err = tokenize("{{ nickname | default:anon }}").unwrap_err();
+        assert!(
+            matches!(err.kind, ParseErrorKind::Expected { ref description } if description.starts_with("'\"'"))
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_variable_with_missing_filter_name() {
+        let err = tokenize("{{ name | }}").unwrap_err();
+        assert!(
+            matches!(err.kind, ParseErrorKind::Expected { ref description } if description.contains("identifier"))
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_include_with_path_separators() {
+        assert_eq!(
+            tokenize("{{<< admin/header }}{{<< admin\\footer.tmpl }}").unwrap(),
+            AstNode::Root(vec![
+                AstNode::TemplateInclude {
+                    template_name: "admin/header",
+                    bindings: Vec::new(),
+                },
+                AstNode::TemplateInclude {
+                    template_name: "admin\\footer.tmpl",
+                    bindings: Vec::new(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_include_trailing_separators() {
+        assert_eq!(
+            tokenize("{{<< partial/ }}{{<< partial. }}{{<< admin\\partial./ }}").unwrap(),
+            AstNode::Root(vec![
+                AstNode::TemplateInclude {
+                    template_name: "partial",
+                    bindings: Vec::new(),
+                },
+                AstNode::TemplateInclude {
+                    template_name: "partial",
+                    bindings: Vec::new(),
+                },
+                AstNode::TemplateInclude {
+                    template_name: "admin\\partial",
+                    bindings: Vec::new(),
+                },
+            ])
+        );
+        let err = tokenize("{{<< ./ }}").unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ParseErrorKind::Expected { ref description } if description == "template name"
+        ));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_quoted_include() {
+        assert_eq!(
+            tokenize(r#"{{<< "admin/header" }}"#).unwrap(),
+            AstNode::Root(vec![AstNode::TemplateInclude {
+                template_name: "admin/header",
+                bindings: Vec::new(),
+            }])
+        );
+        assert_eq!(
+            tokenize(r#"{{<< "partials/{{ kind }}_{{sub}}" }}"#).unwrap(),
+            AstNode::Root(vec![AstNode::DynamicInclude {
+                name: vec![
+                    const_str!("partials/"),
+                    var!("kind"),
+                    const_str!("_"),
+                    var!("sub"),
+                ],
+            }])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_include_bindings() {
+        assert_eq!(
+            tokenize(
+                "{{% alias full = user.full_name %}}\
+                 {{<< greeting name=full, polite=true, title = \"Dr\",age=42 -}}"
+            )
+            .unwrap(),
+            AstNode::Root(vec![
+                AstNode::Alias {
+                    name: "full",
+                    target: "user.full_name",
+                },
+                AstNode::TemplateInclude {
+                    template_name: "greeting",
+                    bindings: vec![
+                        ("name", var!("full")),
+                        ("polite", AstNode::BoolLiteral { value: true }),
+                        ("title", AstNode::StringLiteral { value: "Dr" }),
+                        ("age", AstNode::NumberLiteral { value: "42" }),
+                    ],
+                },
+            ])
+        );
+
+        for input in [
+            "{{<< greeting name }}",
+            "{{<< greeting name= }}",
+            "{{<< greeting name=(a || b) }}",
+            "{{<< greeting name=a, }}",
+        ] {
+            let err = tokenize(input).unwrap_err();
+            assert!(
+                matches!(err.kind, ParseErrorKind::Expected { .. }),
+                "unexpected error for {input:?}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_include_trim_markers() {
+        assert_eq!(
+            tokenize("a {{<<- header -}} b {{<<- \"row_{{ kind }}\" }} c {{<< footer.tmpl -}}")
+                .unwrap(),
+            AstNode::Root(vec![
+                const_str!("a ", start: false, end: true),
+                AstNode::TemplateInclude {
+                    template_name: "header",
+                    bindings: Vec::new(),
+                },
+                const_str!(" b ", start: true, end: true),
+                AstNode::DynamicInclude {
+                    name: vec![const_str!("row_"), var!("kind")],
+                },
+                const_str!(" c "),
+                AstNode::TemplateInclude {
+                    template_name: "footer.tmpl",
+                    bindings: Vec::new(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_trim_markers() {
+        assert_eq!(
+            tokenize("a \n{{- name -}}\n b").unwrap(),
+            AstNode::Root(vec![
+                const_str!("a \n", start: false, end: true),
+                var!("name"),
+                const_str!("\n b", start: true, end: false),
+            ])
+        );
+        assert_eq!(
+            tokenize("<ul>\n{{% for item in items -%}}\n  {{ item }}\n{{-% endfor %}}").unwrap(),
+            AstNode::Root(vec![
+                const_str!("<ul>\n"),
+                AstNode::For {
+                    iterable: Box::new(var!("items")),
+                    variable: "item",
+                    value_variable: None,
+                    body: vec![
+                        const_str!("\n  ", start: true, end: false),
+                        var!("item"),
+                        const_str!("\n", start: false, end: true),
+                    ],
+                    empty_branch: None,
+                },
+            ])
+        );
+        // Markers on the else and endif tags of an if block are recognised too
+        assert_eq!(
+            tokenize("{{% if a %}} x {{-% else -%}} y {{% endif -%}}").unwrap(),
+            AstNode::Root(vec![AstNode::If {
+                condition: Box::new(var!("a")),
+                body: vec![const_str!(" x ", start: false, end: true)],
+                else_branch: Some(Box::new(AstNode::Root(vec![const_str!(
+                    " y ",
+                    start: true,
+                    end: false
+                )]))),
+            }])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_trim_markers_at_template_edges() {
+        // There is no text before the first tag or after the last one to trim
+        assert_eq!(
+            tokenize("{{- name -}}").unwrap(),
+            AstNode::Root(vec![var!("name")])
+        );
+        assert_eq!(
+            tokenize("{{-% if a -%}}{{% endif -%}}").unwrap(),
+            AstNode::Root(vec![AstNode::If {
+                condition: Box::new(var!("a")),
+                body: vec![],
+                else_branch: None,
+            }])
+        );
+        // A trim marker between two tags has no text to trim
+        assert_eq!(
+            tokenize("{{ a -}}{{ b }} c").unwrap(),
+            AstNode::Root(vec![var!("a"), var!("b"), const_str!(" c")])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_escaped_trim_marker() {
+        let ast = tokenize("a \\{{- name }}").unwrap();
+        assert!(matches!(
+            &ast,
+            AstNode::Root(nodes) if matches!(
+                nodes.as_slice(),
+                [AstNode::Constant { trim, .. }] if trim.is_none()
+            )
+        ));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_block_comment() {
+        assert_eq!(
+            tokenize("{{ a }}{{# a note #}}{{ b }}").unwrap(),
+            AstNode::Root(vec![var!("a"), var!("b")])
+        );
+        assert_eq!(
+            tokenize("{{ a }}{{# spans\n{{ lines }} and // text\n#}}{{ b }}").unwrap(),
+            AstNode::Root(vec![var!("a"), var!("b")])
+        );
+        assert_eq!(
+            tokenize("{{% if a %}}{{# only a comment #}}{{% endif %}}").unwrap(),
+            AstNode::Root(vec![AstNode::If {
+                condition: Box::new(var!("a")),
+                body: vec![],
+                else_branch: None,
+            }])
+        );
+
+        // Positions after a multi-line comment account for its newlines
+        let err = tokenize("{{# one\ntwo\n#}} {{ }}").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 8));
+
+        let err = tokenize("a {{# never closed }}").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::UnexpectedEOF { .. }));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_raw_block() {
+        assert_eq!(
+            tokenize("a {{% raw %}}{{ x }} {{% if y %}}{{<< z }}{{% endraw %}} b").unwrap(),
+            AstNode::Root(vec![
+                const_str!("a "),
+                const_str!("{{ x }} {{% if y %}}{{<< z }}"),
+                const_str!(" b"),
+            ])
+        );
+        assert_eq!(
+            tokenize("{{% raw -%}}\n{{ x }}\n{{-% endraw %}}").unwrap(),
+            AstNode::Root(vec![AstNode::Constant {
+                data: Cow::Borrowed("\n{{ x }}\n"),
+                trim: Trim {
+                    start: true,
+                    end: true
+                },
+            }])
+        );
+
+        let err = tokenize("{{% raw %}}{{ x }}{{% endfor %}}").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::unexpected_eof(Some("{{% endraw %}}".to_string()))
+        );
+        let err = tokenize("{{% endraw %}}").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::UnknownKeyword { .. }));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_alias() {
+        assert_eq!(
+            tokenize("{{% alias n = user.profile.name %}}Hi {{ n }}{{% if n %}}!{{% endif %}}")
+                .unwrap(),
+            AstNode::Root(vec![
+                AstNode::Alias {
+                    name: "n",
+                    target: "user.profile.name",
+                },
+                const_str!("Hi "),
+                // Aliases are resolved when rendering, so uses keep the name they're written with
+                var!("n"),
+                AstNode::If {
+                    condition: Box::new(var!("n")),
+                    body: vec![const_str!("!")],
+                    else_branch: None,
+                },
+            ])
+        );
+
+        let err = tokenize("{{% alias n %}}").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::Expected { .. }));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_set() {
+        assert_eq!(
+            tokenize(
+                "{{% set t = page.title | trim | upper %}}{{ t }}\
+                 {{% set n = -2.5 %}}{{% set s = \"hi\" | upper %}}"
+            )
+            .unwrap(),
+            AstNode::Root(vec![
+                AstNode::Set {
+                    name: "t",
+                    value: Box::new(AstNode::Filter {
+                        name: "upper",
+                        argument: None,
+                        inner: Box::new(AstNode::Filter {
+                            name: "trim",
+                            argument: None,
+                            inner: Box::new(var!("page.title")),
+                        }),
+                    }),
+                },
+                var!("t"),
+                AstNode::Set {
+                    name: "n",
+                    value: Box::new(AstNode::NumberLiteral { value: "-2.5" }),
+                },
+                AstNode::Set {
+                    name: "s",
+                    value: Box::new(AstNode::Filter {
+                        name: "upper",
+                        argument: None,
+                        inner: Box::new(AstNode::StringLiteral { value: "hi" }),
+                    }),
+                },
+            ])
+        );
+
+        for input in ["{{% set x %}}", "{{% set = y %}}", "{{% set x = %}}"] {
+            let err = tokenize(input).unwrap_err();
+            assert!(
+                matches!(err.kind, ParseErrorKind::Expected { .. }),
+                "unexpected error for {input:?}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_loop_control() {
+        assert_eq!(
+            tokenize("{{% for x in xs %}}{{% if x %}}{{% break %}}{{% endif %}}{{% continue %}}{{% endfor %}}")
+                .unwrap(),
+            AstNode::Root(vec![AstNode::For {
+                variable: "x",
+                value_variable: None,
+                iterable: Box::new(var!("xs")),
+                body: vec![
+                    AstNode::If {
+                        condition: Box::new(var!("x")),
+                        body: vec![AstNode::Break],
+                        else_branch: None,
+                    },
+                    AstNode::Continue,
+                ],
+                empty_branch: None,
+            }])
+        );
+
+        // Repeat blocks are loops too
+        assert_eq!(
+            tokenize("{{% repeat 2 %}}{{% break %}}{{% endrepeat %}}").unwrap(),
+            AstNode::Root(vec![AstNode::Repeat {
+                count: 2,
+                body: vec![AstNode::Break],
+            }])
+        );
+
+        // Loop control needs an enclosing for loop or repeat block, which an empty branch
+        // isn't
+        for input in [
+            "{{% break %}}",
+            "{{% if x %}}{{% continue %}}{{% endif %}}",
+            "{{% for x in xs %}}{{% empty %}}{{% break %}}{{% endfor %}}",
+            "{{% for x in xs %}}{{% endfor %}}{{% continue %}}",
+            "{{% repeat 2 %}}{{% endrepeat %}}{{% break %}}",
+        ] {
+            let err = tokenize(input).unwrap_err();
+            assert!(
+                matches!(err.kind, ParseErrorKind::OutsideLoop { .. }),
+                "unexpected error for {input:?}: {err:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_custom_delimiters() {
+        let delimiters = DelimiterConfig {
+            open: "<%".to_string(),
+            close: "%>".to_string(),
+            block: "@".to_string(),
+            include: "+".to_string(),
+            comment: "!".to_string(),
+        };
+        let default = tokenize(
+            "{{# note #}}{{% if a && b -%}}\n  {{ a | upper }}{{<< \"p/{{ b }}\" }}\
+             {{-% else %}}{{% for x in xs %}}{{<< item }}{{% endfor %}}{{% endif %}} \\}}",
+        )
+        .unwrap();
+        let custom = tokenize_with(
+            "<%! note !%><%@ if a && b -@%>\n  <% a | upper %><%+ \"p/<% b %>\" %>\
+             <%-@ else @%><%@ for x in xs @%><%+ item %><%@ endfor @%><%@ endif @%> }}",
+            false,
+            &delimiters,
+            None,
+        )
+        .unwrap();
+        assert_eq!(custom, default);
+
+        // The default delimiters are plain text, and the custom ones can be escaped
+        assert_eq!(
+            tokenize_with("{{ a }} \\<% b %>", false, &delimiters, None).unwrap(),
+            AstNode::Root(vec![AstNode::Constant {
+                data: Cow::Owned("{{ a }} <% b %>".to_string()),
+                trim: Trim::default(),
+            }])
+        );
+
+        // Errors name the custom delimiters
+        let err = tokenize_with("<%@ if a @%>", false, &delimiters, None).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::unexpected_eof(Some(
+                "<%@ endif @%> or <%@ else @%> or <%@ else if @%>".to_string()
+            ))
+        );
+
+        // Delimiters that can't be told apart are rejected up front
+        for delimiters in [
+            DelimiterConfig {
+                open: String::new(),
+                ..DelimiterConfig::default()
+            },
+            DelimiterConfig {
+                comment: "%".to_string(),
+                ..DelimiterConfig::default()
+            },
+            DelimiterConfig {
+                include: "-<".to_string(),
+                ..DelimiterConfig::default()
+            },
+        ] {
+            let err = tokenize_with("text", false, &delimiters, None).unwrap_err();
+            assert!(matches!(err.kind, ParseErrorKind::Message(_)));
+        }
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_escaped_delimiters() {
+        let escaped = |data: &str| AstNode::Constant {
+            data: Cow::Owned(data.to_string()),
+            trim: Trim::default(),
+        };
+        assert_eq!(
+            tokenize("a \\{{% raw %}} b").unwrap(),
+            AstNode::Root(vec![escaped("a {{% raw %}} b")])
+        );
+        assert_eq!(
+            tokenize("\\{{x\\}}{{ y }}\\}}").unwrap(),
+            AstNode::Root(vec![escaped("{{x}}"), var!("y"), escaped("}}")])
         );
+        // Text without escapes is borrowed, backslashes and all
+        assert_eq!(
+            tokenize("C:\\dir }}").unwrap(),
+            AstNode::Root(vec![const_str!("C:\\dir }}")])
+        );
+        assert!(matches!(
+            tokenize("C:\\dir }}").unwrap(),
+            AstNode::Root(nodes) if matches!(
+                nodes.as_slice(),
+                [AstNode::Constant { data: Cow::Borrowed(_), .. }]
+            )
+        ));
     }
 
     #[test]
     #[ntest::timeout(100)]
-    fn test_simple_variable() {
+    fn test_quoted_include_escapes() {
         assert_eq!(
-            tokenize("{{name}}").unwrap(),
-            AstNode::Root(vec![var!("name")])
+            tokenize(r#"{{<< "a\{{ b }}\"\\c\d{{ e }}" }}"#).unwrap(),
+            AstNode::Root(vec![AstNode::DynamicInclude {
+                name: vec![
+                    AstNode::Constant {
+                        data: Cow::Owned(r#"a{{ b }}"\c\d"#.to_string()),
+                        trim: Trim::default(),
+                    },
+                    var!("e"),
+                ],
+            }])
         );
     }
 
     #[test]
     #[ntest::timeout(100)]
-    fn test_variable_with_whitespace() {
-        assert_eq!(
-            tokenize("{{ name }}").unwrap(),
-            AstNode::Root(vec![var!("name")])
+    fn test_quoted_include_errors() {
+        let err = tokenize(r#"{{<< "partials/{{ kind }}}}"#).unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::UnexpectedEOF { .. }));
+
+        let err = tokenize(r#"{{<< "" }}"#).unwrap_err();
+        assert!(
+            matches!(err.kind, ParseErrorKind::Expected { ref description } if description.contains("template name"))
         );
     }
 
     #[test]
     #[ntest::timeout(100)]
-    fn test_variable_with_dot() {
-        assert_eq!(
-            tokenize("{{ user.name }}").unwrap(),
-            AstNode::Root(vec![var!("user.name")])
+    fn test_variable_with_slash_is_rejected() {
+        let err = tokenize("{{ admin/header }}").unwrap_err();
+        assert!(
+            matches!(err.kind, ParseErrorKind::Expected { ref description } if description.contains("'}}'"))
         );
     }
 
@@ -824,9 +2458,10 @@ mod tests {
     // --- Tests for Condition Parsing ---
     // Helper to parse a condition string directly for testing.
     // This simulates being inside an `{{% if ... %}}` block.
-    fn parse_test_condition(condition_str: &str) -> ParseResult<Box<AstNode>> {
+    fn parse_test_condition(condition_str: &str) -> ParseResult<Box<AstNode<'_>>> {
         // Returns Result now
-        let mut parser = Parser::new(condition_str);
+        let tokens = Tokens::new(&DelimiterConfig::default());
+        let mut parser = Parser::new(condition_str, &tokens, false);
         let condition_node_result = parser.parse_condition_expression();
         if condition_node_result.is_ok() && !parser.eof() {
             // If parsing was ok, but we didn't consume everything, that's an error for this helper
@@ -935,6 +2570,205 @@ mod tests {
         );
     }
 
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_precedence_xor() {
+        // XOR binds tighter than OR but looser than AND: a || ((b && c) ^^ d)
+        assert_eq!(
+            parse_test_condition("a || b && c ^^ d").unwrap(),
+            Box::new(AstNode::Or {
+                left: Box::new(var!("a")),
+                right: Box::new(AstNode::Xor {
+                    left: Box::new(AstNode::And {
+                        left: Box::new(var!("b")),
+                        right: Box::new(var!("c"))
+                    }),
+                    right: Box::new(var!("d"))
+                })
+            })
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_comparison() {
+        // Comparisons bind tighter than AND: (status == "in review") && !(a != b)
+        assert_eq!(
+            parse_test_condition(r#"status == "in review" && !a!=b"#).unwrap(),
+            Box::new(AstNode::And {
+                left: Box::new(AstNode::Compare {
+                    left: Box::new(var!("status")),
+                    op: CompareOp::Eq,
+                    right: Box::new(AstNode::StringLiteral { value: "in review" })
+                }),
+                right: Box::new(AstNode::Not {
+                    condition: Box::new(AstNode::Compare {
+                        left: Box::new(var!("a")),
+                        op: CompareOp::Ne,
+                        right: Box::new(var!("b"))
+                    })
+                })
+            })
+        );
+        assert_eq!(
+            parse_test_condition(r#"a == """#).unwrap(),
+            Box::new(AstNode::Compare {
+                left: Box::new(var!("a")),
+                op: CompareOp::Eq,
+                right: Box::new(AstNode::StringLiteral { value: "" })
+            })
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_comparison_errors() {
+        let err = parse_test_condition(r#"a == "unterminated"#).unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::UnexpectedEOF { .. }));
+        let err = parse_test_condition("a ==").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::Expected { .. }));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_contains() {
+        assert_eq!(
+            parse_test_condition(r#"tags contains "rust" || !flags contains mode"#).unwrap(),
+            Box::new(AstNode::Or {
+                left: Box::new(AstNode::Compare {
+                    left: Box::new(var!("tags")),
+                    op: CompareOp::Contains,
+                    right: Box::new(AstNode::StringLiteral { value: "rust" })
+                }),
+                right: Box::new(AstNode::Not {
+                    condition: Box::new(AstNode::Compare {
+                        left: Box::new(var!("flags")),
+                        op: CompareOp::Contains,
+                        right: Box::new(var!("mode"))
+                    })
+                })
+            })
+        );
+        // `contains` is only an operator as a whole word, so it can still name a variable
+        assert_eq!(
+            parse_test_condition("contains == contains_all").unwrap(),
+            Box::new(AstNode::Compare {
+                left: Box::new(var!("contains")),
+                op: CompareOp::Eq,
+                right: Box::new(var!("contains_all"))
+            })
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_coalesce() {
+        // `??` binds tighter than comparisons, and groups to the right
+        assert_eq!(
+            parse_test_condition(r#"(status ?? "pending") == "pending" && a ?? b ?? 0 > 1"#)
+                .unwrap(),
+            Box::new(AstNode::And {
+                left: Box::new(AstNode::Compare {
+                    left: Box::new(AstNode::Coalesce {
+                        value: Box::new(var!("status")),
+                        fallback: Box::new(AstNode::StringLiteral { value: "pending" })
+                    }),
+                    op: CompareOp::Eq,
+                    right: Box::new(AstNode::StringLiteral { value: "pending" })
+                }),
+                right: Box::new(AstNode::Compare {
+                    left: Box::new(AstNode::Coalesce {
+                        value: Box::new(var!("a")),
+                        fallback: Box::new(AstNode::Coalesce {
+                            value: Box::new(var!("b")),
+                            fallback: Box::new(AstNode::NumberLiteral { value: "0" })
+                        })
+                    }),
+                    op: CompareOp::Gt,
+                    right: Box::new(AstNode::NumberLiteral { value: "1" })
+                })
+            })
+        );
+        let err = parse_test_condition("a ??").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::Expected { .. }));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_bool_literals() {
+        assert_eq!(
+            parse_test_condition("debug || !false").unwrap(),
+            Box::new(AstNode::Or {
+                left: Box::new(var!("debug")),
+                right: Box::new(AstNode::Not {
+                    condition: Box::new(AstNode::BoolLiteral { value: false })
+                })
+            })
+        );
+        // Only the exact words are literals
+        assert_eq!(
+            parse_test_condition("true == true_value").unwrap(),
+            Box::new(AstNode::Compare {
+                left: Box::new(AstNode::BoolLiteral { value: true }),
+                op: CompareOp::Eq,
+                right: Box::new(var!("true_value"))
+            })
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_relational_comparison() {
+        // Two character operators aren't split, and bind tighter than OR
+        assert_eq!(
+            parse_test_condition("age >= 18 || score<-0.5").unwrap(),
+            Box::new(AstNode::Or {
+                left: Box::new(AstNode::Compare {
+                    left: Box::new(var!("age")),
+                    op: CompareOp::Ge,
+                    right: Box::new(AstNode::NumberLiteral { value: "18" })
+                }),
+                right: Box::new(AstNode::Compare {
+                    left: Box::new(var!("score")),
+                    op: CompareOp::Lt,
+                    right: Box::new(AstNode::NumberLiteral { value: "-0.5" })
+                })
+            })
+        );
+        assert_eq!(
+            parse_test_condition("a <= b").unwrap(),
+            Box::new(AstNode::Compare {
+                left: Box::new(var!("a")),
+                op: CompareOp::Le,
+                right: Box::new(var!("b"))
+            })
+        );
+
+        let err = parse_test_condition("a > 1.2.3").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::Expected { .. }));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_literals() {
+        assert_eq!(
+            parse_test_condition(r#""true" && !0 || "a" == name"#).unwrap(),
+            Box::new(AstNode::Or {
+                left: Box::new(AstNode::And {
+                    left: Box::new(AstNode::StringLiteral { value: "true" }),
+                    right: Box::new(AstNode::Not {
+                        condition: Box::new(AstNode::NumberLiteral { value: "0" })
+                    })
+                }),
+                right: Box::new(AstNode::Compare {
+                    left: Box::new(AstNode::StringLiteral { value: "a" }),
+                    op: CompareOp::Eq,
+                    right: Box::new(var!("name"))
+                })
+            })
+        );
+    }
+
     #[test]
     #[ntest::timeout(100)]
     fn test_condition_precedence_not_and() {
@@ -1002,6 +2836,50 @@ mod tests {
         );
     }
 
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_parenthesized_grouping() {
+        // Parentheses override precedence: (a || b) && c
+        assert_eq!(
+            parse_test_condition("(a || b) && c").unwrap(),
+            Box::new(AstNode::And {
+                left: Box::new(AstNode::Or {
+                    left: Box::new(var!("a")),
+                    right: Box::new(var!("b"))
+                }),
+                right: Box::new(var!("c"))
+            })
+        );
+        // Groups compose with NOT and nest: !(a || (b ^^ c))
+        assert_eq!(
+            parse_test_condition("!( a || ( b ^^ c ) )").unwrap(),
+            Box::new(AstNode::Not {
+                condition: Box::new(AstNode::Or {
+                    left: Box::new(var!("a")),
+                    right: Box::new(AstNode::Xor {
+                        left: Box::new(var!("b")),
+                        right: Box::new(var!("c"))
+                    })
+                })
+            })
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_condition_unbalanced_parentheses() {
+        let input = "{{% if (a || b %}}yes{{% endif %}}";
+        let err = tokenize(input).unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(err.column, input.find("%}}").unwrap() + 1);
+        assert!(
+            matches!(err.kind, ParseErrorKind::Expected { ref description } if description.starts_with("')'"))
+        );
+
+        let err = tokenize("{{% if a || b) %}}yes{{% endif %}}").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::Expected { .. }));
+    }
+
     #[test]
     #[ntest::timeout(100)]
     fn test_condition_empty_string() {
@@ -1009,6 +2887,34 @@ mod tests {
         assert!(matches!(err.kind, ParseErrorKind::Expected { .. }));
     }
 
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_if_missing_condition() {
+        let err = tokenize("{{% if %}}yes{{% endif %}}").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::Expected {
+                description: "condition after `if`".to_string()
+            }
+        );
+        assert_eq!((err.line, err.column), (1, 8));
+
+        let err = tokenize("{{% if a %}}a\n{{% else if   %}}b{{% endif %}}").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::Expected {
+                description: "condition after `else if`".to_string()
+            }
+        );
+        assert_eq!((err.line, err.column), (2, 15));
+
+        // A malformed condition is still reported as such
+        let err = tokenize("{{% if a && %}}yes{{% endif %}}").unwrap_err();
+        assert!(
+            matches!(err.kind, ParseErrorKind::Expected { ref description } if description.contains("identifier"))
+        );
+    }
+
     #[test]
     #[ntest::timeout(100)]
     fn test_condition_only_operator_and() {
@@ -1053,6 +2959,48 @@ mod tests {
         );
     }
 
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_unknown_directive() {
+        let input = "a{{% block\n title %}}{{ name }}{{-% endblock -%}}b";
+        let err = tokenize(input).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnknownKeyword {
+                keyword: "block".to_string()
+            }
+        );
+
+        assert_eq!(
+            tokenize_with(input, true, &DelimiterConfig::default(), None).unwrap(),
+            AstNode::Root(vec![
+                const_str!("a"),
+                const_str!("{{% block\n title %}}"),
+                var!("name"),
+                const_str!("{{-% endblock -%}}"),
+                const_str!("b"),
+            ])
+        );
+
+        // Misplaced closing tags and unterminated directives are still errors
+        let err =
+            tokenize_with("{{% endif %}}", true, &DelimiterConfig::default(), None).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnknownKeyword {
+                keyword: "endif".to_string()
+            }
+        );
+        let err = tokenize_with(
+            "{{% block title }}",
+            true,
+            &DelimiterConfig::default(),
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::UnexpectedEOF { .. }));
+    }
+
     // --- Tests for For Loops ---
     #[test]
     #[ntest::timeout(100)]
@@ -1060,10 +3008,39 @@ mod tests {
         let input = "{{% for item in items %}} {{item}} {{% endfor %}}";
         let expected = AstNode::Root(vec![AstNode::For {
             variable: "item",
-            iterable: "items",
+            value_variable: None,
+            iterable: Box::new(var!("items")),
             body: vec![const_str!(" "), var!("item"), const_str!(" ")],
+            empty_branch: None,
+        }]);
+        assert_eq!(tokenize(input).unwrap(), expected);
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_key_value_for_loop() {
+        let input = "{{% for k, v in map %}} {{k}}={{v}} {{% endfor %}}";
+        let expected = AstNode::Root(vec![AstNode::For {
+            variable: "k",
+            value_variable: Some("v"),
+            iterable: Box::new(var!("map")),
+            body: vec![
+                const_str!(" "),
+                var!("k"),
+                const_str!("="),
+                var!("v"),
+                const_str!(" "),
+            ],
+            empty_branch: None,
         }]);
         assert_eq!(tokenize(input).unwrap(), expected);
+
+        // Whitespace around the comma is optional
+        assert_eq!(
+            tokenize("{{% for k,v in map %}} {{k}}={{v}} {{% endfor %}}").unwrap(),
+            expected
+        );
+        tokenize("{{% for k, in map %}}{{% endfor %}}").unwrap_err();
     }
 
     #[test]
@@ -1072,8 +3049,10 @@ mod tests {
         let input = "{{% for x in list %}}Value: {{x}}!{{% endfor %}}";
         let expected = AstNode::Root(vec![AstNode::For {
             variable: "x",
-            iterable: "list",
+            value_variable: None,
+            iterable: Box::new(var!("list")),
             body: vec![const_str!("Value: "), var!("x"), const_str!("!")],
+            empty_branch: None,
         }]);
         assert_eq!(tokenize(input).unwrap(), expected);
     }
@@ -1084,12 +3063,72 @@ mod tests {
         let input = "{{% for i in data %}}{{% endfor %}}";
         let expected = AstNode::Root(vec![AstNode::For {
             variable: "i",
-            iterable: "data",
+            value_variable: None,
+            iterable: Box::new(var!("data")),
             body: vec![],
+            empty_branch: None,
+        }]);
+        assert_eq!(tokenize(input).unwrap(), expected);
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_for_loop_with_ternary_iterable() {
+        let input = "{{% for x in (admin && !guest ? all : public) %}}{{x}}{{% endfor %}}";
+        let expected = AstNode::Root(vec![AstNode::For {
+            variable: "x",
+            value_variable: None,
+            iterable: Box::new(AstNode::Ternary {
+                condition: Box::new(AstNode::And {
+                    left: Box::new(var!("admin")),
+                    right: Box::new(AstNode::Not {
+                        condition: Box::new(var!("guest")),
+                    }),
+                }),
+                if_true: Box::new(var!("all")),
+                if_false: Box::new(var!("public")),
+            }),
+            body: vec![var!("x")],
+            empty_branch: None,
+        }]);
+        assert_eq!(tokenize(input).unwrap(), expected);
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_for_loop_ternary_missing_colon() {
+        let err = tokenize("{{% for x in (admin ? all) %}}{{% endfor %}}").unwrap_err();
+        assert!(
+            matches!(err.kind, ParseErrorKind::Expected { ref description } if description.contains("':'"))
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_for_loop_with_empty_branch() {
+        let input = "{{% for item in items %}}{{item}}{{% empty %}}Nothing{{% endfor %}}";
+        let expected = AstNode::Root(vec![AstNode::For {
+            variable: "item",
+            value_variable: None,
+            iterable: Box::new(var!("items")),
+            body: vec![var!("item")],
+            empty_branch: Some(Box::new(AstNode::Root(vec![const_str!("Nothing")]))),
         }]);
         assert_eq!(tokenize(input).unwrap(), expected);
     }
 
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_for_loop_unclosed_empty_branch() {
+        let input = "{{% for item in items %}}{{item}}{{% empty %}}Nothing";
+        let err = tokenize(input).unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(err.column, input.len() + 1);
+        assert!(
+            matches!(err.kind, ParseErrorKind::UnexpectedEOF { ref expected_what } if expected_what.contains("{{% endfor %}}"))
+        );
+    }
+
     #[test]
     #[ntest::timeout(100)]
     fn test_for_loop_missing_in() {
@@ -1155,6 +3194,69 @@ mod tests {
     }
 
     // --- Tests for If Statements ---
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_debug_directive() {
+        assert_eq!(
+            tokenize("a{{% debug %}}b{{%debug%}}").unwrap(),
+            AstNode::Root(vec![
+                const_str!("a"),
+                AstNode::DebugDump,
+                const_str!("b"),
+                AstNode::DebugDump,
+            ])
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_repeat_block() {
+        assert_eq!(
+            tokenize("{{% repeat 3 %}}-{{ loop.index }}{{% endrepeat %}}").unwrap(),
+            AstNode::Root(vec![AstNode::Repeat {
+                count: 3,
+                body: vec![const_str!("-"), var!("loop.index")],
+            }])
+        );
+        assert_eq!(
+            tokenize("{{%repeat 0%}}x{{%endrepeat%}}").unwrap(),
+            AstNode::Root(vec![AstNode::Repeat {
+                count: 0,
+                body: vec![const_str!("x")],
+            }])
+        );
+
+        for input in [
+            "{{% repeat %}}x{{% endrepeat %}}",
+            "{{% repeat n %}}x{{% endrepeat %}}",
+        ] {
+            let err = tokenize(input).unwrap_err();
+            assert!(
+                matches!(err.kind, ParseErrorKind::Expected { ref description } if description == "repeat count")
+            );
+        }
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_ifdebug_block() {
+        assert_eq!(
+            tokenize("a{{% ifdebug %}}[{{ b }}]{{%endifdebug%}}c").unwrap(),
+            AstNode::Root(vec![
+                const_str!("a"),
+                AstNode::DebugOnly {
+                    body: vec![const_str!("["), var!("b"), const_str!("]")],
+                },
+                const_str!("c"),
+            ])
+        );
+
+        let err = tokenize("{{% ifdebug %}}a").unwrap_err();
+        assert!(
+            matches!(err.kind, ParseErrorKind::UnexpectedEOF { ref expected_what } if expected_what.contains("endifdebug"))
+        );
+    }
+
     #[test]
     #[ntest::timeout(100)]
     fn test_simple_if() {
@@ -1338,12 +3440,14 @@ mod tests {
         );
         let expected = AstNode::Root(vec![AstNode::For {
             variable: "user",
-            iterable: "users",
+            value_variable: None,
+            iterable: Box::new(var!("users")),
             body: vec![AstNode::If {
                 condition: Box::new(var!("user.active")),
                 body: vec![var!("user.name")],
                 else_branch: Some(Box::new(AstNode::Root(vec![const_str!("Inactive")]))),
             }],
+            empty_branch: None,
         }]);
         assert_eq!(tokenize(input), Ok(expected));
     }
@@ -1364,8 +3468,10 @@ mod tests {
             condition: Box::new(var!("items_exist")),
             body: vec![AstNode::For {
                 variable: "item",
-                iterable: "items",
+                value_variable: None,
+                iterable: Box::new(var!("items")),
                 body: vec![var!("item")],
+                empty_branch: None,
             }],
             else_branch: Some(Box::new(AstNode::Root(vec![const_str!("No items.")]))),
         }]);
@@ -1500,4 +3606,22 @@ mod tests {
         ]);
         assert_eq!(tokenize(input).unwrap(), expected);
     }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_strip_comments() {
+        let input = r#"// Not a comment, as it's outside of a tag
+{{% for item in items // each item
+   %}}  {{ item   | trim // tidy it up
+}}{{<< "partials/{{ kind }} // kept" -}}
+{{% endfor %}} \{{ text // kept }}"#;
+        let stripped = strip_comments(input);
+        assert_eq!(
+            stripped,
+            r#"// Not a comment, as it's outside of a tag
+{{% for item in items %}}  {{ item | trim }}{{<< "partials/{{ kind }} // kept" -}}
+{{% endfor %}} \{{ text // kept }}"#
+        );
+        assert_eq!(tokenize(&stripped).unwrap(), tokenize(input).unwrap());
+    }
 }
@@ -0,0 +1,55 @@
+//! Rendered output bundled with what went into it.
+//!
+//! [`crate::MinilateEngine::render_capture`] renders a template like
+//! [`crate::MinilateInterface::render`], but returns a [`RenderedDoc`] recording which
+//! variables and templates the output was built from, e.g. for caching or auditing.
+//!
+//! # Example
+//!
+//! ```rust
+//! use minilate::{Context, MinilateEngine, MinilateInterface, VariableTy};
+//!
+//! let mut engine = MinilateEngine::new();
+//! engine.add_template("header", "# {{ title }}\n").unwrap();
+//! engine.add_template("page", "{{<< header }}{{ body }}").unwrap();
+//!
+//! let mut context = Context::new();
+//! context.insert("title", VariableTy::String.with_data("News"));
+//! context.insert("body", VariableTy::String.with_data("Nothing yet."));
+//!
+//! let doc = engine.render_capture("page", &context).unwrap();
+//! assert_eq!(doc.output, "# News\nNothing yet.");
+//! assert_eq!(doc.variables, ["body", "title"]);
+//! assert_eq!(doc.includes, ["header"]);
+//! assert_eq!(doc.len(), 19);
+//! ```
+
+/// The output of a render, along with the variables and templates it used.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RenderedDoc {
+    /// The rendered output.
+    pub output: String,
+    /// The names of the context variables the template and its includes refer to, sorted.
+    pub variables: Vec<String>,
+    /// The names of the templates included while rendering, in the order each was first
+    /// included.
+    pub includes: Vec<String>,
+}
+
+impl RenderedDoc {
+    /// Returns the length of the output in bytes.
+    pub fn len(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Returns true if the output is empty.
+    pub fn is_empty(&self) -> bool {
+        self.output.is_empty()
+    }
+}
+
+impl std::fmt::Display for RenderedDoc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.output)
+    }
+}
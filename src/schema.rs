@@ -0,0 +1,291 @@
+//! Checking templates against a declared data model.
+//!
+//! A schema maps each variable an application provides to its [`VariableTy`].
+//! [`crate::Template::validate_against`] compares a template with a schema, reporting the
+//! variables it uses that the schema doesn't declare, the schema entries it never uses,
+//! and the variables it uses as a type the schema doesn't give them.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{AstNode, CompareOp};
+use crate::interface::VariableTy;
+use crate::template::indexed_name;
+
+/// A disagreement between a template and a schema, found by
+/// [`crate::Template::validate_against`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SchemaError {
+    /// The template uses a variable the schema doesn't declare.
+    UndeclaredVariable { variable: String },
+    /// The schema declares a variable the template never uses.
+    UnusedVariable { variable: String },
+    /// The template uses a variable as a type the schema doesn't give it, such as looping
+    /// over a variable declared as a [`VariableTy::String`].
+    TypeMismatch {
+        variable: String,
+        expected: VariableTy,
+        declared: VariableTy,
+    },
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UndeclaredVariable { variable } => {
+                write!(f, "Variable is not declared in the schema: {}", variable)
+            }
+            Self::UnusedVariable { variable } => {
+                write!(f, "Schema variable is never used: {}", variable)
+            }
+            Self::TypeMismatch {
+                variable,
+                expected,
+                declared,
+            } => write!(
+                f,
+                "Variable {} is used as {:?} but declared as {:?}",
+                variable, expected, declared
+            ),
+        }
+    }
+}
+
+/// Checks the variables used by `ast` against `schema`, see
+/// [`crate::Template::validate_against`].
+pub fn validate<'a>(ast: &AstNode<'a>, schema: &HashMap<String, VariableTy>) -> Vec<SchemaError> {
+    let mut check = SchemaCheck {
+        schema,
+        bound: Vec::new(),
+        used: HashSet::new(),
+        errors: Vec::new(),
+    };
+    check.node(ast);
+
+    let mut unused: Vec<&String> = schema
+        .keys()
+        .filter(|name| !check.used.contains(name.as_str()))
+        .collect();
+    unused.sort();
+    let mut errors = check.errors;
+    errors.extend(unused.into_iter().map(|name| SchemaError::UnusedVariable {
+        variable: name.clone(),
+    }));
+    errors
+}
+
+/// Walks a template, recording the schema entries it uses and any errors found so far.
+struct SchemaCheck<'s, 'a> {
+    schema: &'s HashMap<String, VariableTy>,
+    /// The names bound by enclosing loops, which shadow the schema, innermost last, along
+    /// with the variable each `{{% alias %}}` among them stands for
+    bound: Vec<(&'a str, Option<&'a str>)>,
+    used: HashSet<&'s str>,
+    errors: Vec<SchemaError>,
+}
+
+impl<'a> SchemaCheck<'_, 'a> {
+    fn node(&mut self, node: &AstNode<'a>) {
+        match node {
+            AstNode::Root(children) | AstNode::DebugOnly { body: children } => {
+                self.block(children);
+            }
+            AstNode::Variable { name } => self.use_variable(name, VariableTy::String),
+            AstNode::Filter { inner, .. } => self.node(inner),
+            AstNode::For {
+                iterable,
+                variable,
+                value_variable,
+                body,
+                empty_branch,
+            } => {
+                let expected = if value_variable.is_some() {
+                    VariableTy::Map
+                } else {
+                    VariableTy::Iterable
+                };
+                self.iterable(iterable, expected);
+                if let Some(empty_node) = empty_branch {
+                    self.node(empty_node);
+                }
+
+                let scope = self.bound.len();
+                self.bound.push((variable, None));
+                self.bound
+                    .extend(value_variable.map(|value_variable| (value_variable, None)));
+                self.bound.push(("loop", None));
+                self.block(body);
+                self.bound.truncate(scope);
+            }
+            AstNode::Repeat { body, .. } => {
+                let scope = self.bound.len();
+                self.bound.push(("loop", None));
+                self.block(body);
+                self.bound.truncate(scope);
+            }
+            AstNode::Ternary { .. } => self.iterable(node, VariableTy::Iterable),
+            AstNode::If {
+                condition,
+                body,
+                else_branch,
+            } => {
+                self.node(condition);
+                self.block(body);
+                if let Some(else_node) = else_branch {
+                    self.node(else_node);
+                }
+            }
+            AstNode::Not { condition } => self.node(condition),
+            AstNode::And { left, right }
+            | AstNode::Or { left, right }
+            | AstNode::Xor { left, right } => {
+                self.node(left);
+                self.node(right);
+            }
+            AstNode::Compare { left, op, right } => {
+                let (left_ty, right_ty) = match op {
+                    CompareOp::Contains => (VariableTy::Iterable, VariableTy::String),
+                    CompareOp::Lt | CompareOp::Gt | CompareOp::Le | CompareOp::Ge => {
+                        (VariableTy::Number, VariableTy::Number)
+                    }
+                    CompareOp::Eq | CompareOp::Ne => (VariableTy::String, VariableTy::String),
+                };
+                self.operand(left, left_ty);
+                self.operand(right, right_ty);
+            }
+            AstNode::Coalesce { .. } => self.operand(node, VariableTy::Boolean),
+            AstNode::DynamicInclude { name } => {
+                for part in name {
+                    self.node(part);
+                }
+            }
+            AstNode::TemplateInclude { bindings, .. } => {
+                // Included templates are checked on their own, but the variables passed to
+                // them are used here
+                for (_, value) in bindings {
+                    self.node(value);
+                }
+            }
+            AstNode::Set { name, value } => {
+                // The assigned variable stays bound until the end of the enclosing block
+                self.node(value);
+                self.bound.push((name, None));
+            }
+            AstNode::Alias { name, target } => {
+                // Uses of the alias until the end of the enclosing block use its target
+                let target = self.resolve_alias(target);
+                self.bound.push((name, Some(target)));
+            }
+            AstNode::Constant { .. }
+            | AstNode::StringLiteral { .. }
+            | AstNode::NumberLiteral { .. }
+            | AstNode::BoolLiteral { .. }
+            | AstNode::DebugDump
+            | AstNode::Break
+            | AstNode::Continue => {
+                // No variables are used, and included templates are checked on their own
+            }
+        }
+    }
+
+    /// Checks the nodes of a block, where variables assigned with `{{% set %}}` are bound
+    /// until its end
+    fn block(&mut self, children: &[AstNode<'a>]) {
+        let scope = self.bound.len();
+        for child in children {
+            self.node(child);
+        }
+        self.bound.truncate(scope);
+    }
+
+    /// Checks a for-loop iterable, which may be a choice of variables
+    fn iterable(&mut self, iterable: &AstNode<'a>, expected: VariableTy) {
+        if let AstNode::Variable { name } = iterable {
+            self.use_variable(name, expected);
+        } else if let AstNode::Ternary {
+            condition,
+            if_true,
+            if_false,
+        } = iterable
+        {
+            self.node(condition);
+            self.iterable(if_true, expected);
+            self.iterable(if_false, expected);
+        } else {
+            self.node(iterable);
+        }
+    }
+
+    /// Checks a comparison operand, which may fall back to a literal
+    fn operand(&mut self, operand: &AstNode<'a>, expected: VariableTy) {
+        if let AstNode::Variable { name } = operand {
+            self.use_variable(name, expected);
+        } else if let AstNode::Coalesce { value, fallback } = operand {
+            self.operand(value, expected);
+            self.operand(fallback, expected);
+        } else {
+            self.node(operand);
+        }
+    }
+
+    /// Returns the variable `name` stands for if it is an alias in scope, or `name` itself.
+    fn resolve_alias(&self, name: &'a str) -> &'a str {
+        self.bound
+            .iter()
+            .rev()
+            .find(|(bound, _)| *bound == name)
+            .and_then(|(_, target)| *target)
+            .unwrap_or(name)
+    }
+
+    /// Records a use of the variable `name` as the `expected` type
+    fn use_variable(&mut self, name: &'a str, expected: VariableTy) {
+        let name = self.resolve_alias(name);
+        let bound = self.bound.iter().any(|(bound, target)| {
+            target.is_none()
+                && (name == *bound
+                    || name
+                        .strip_prefix(bound)
+                        .is_some_and(|field| field.starts_with('.')))
+        });
+        if bound {
+            return;
+        }
+
+        // An indexed element such as `items.0` uses the iterable it's taken from
+        let (name, expected) = match indexed_name(name).filter(|_| !self.schema.contains_key(name))
+        {
+            Some((base, _)) => (base, VariableTy::Iterable),
+            None => (name, expected),
+        };
+
+        let error = match self.schema.get_key_value(name) {
+            Some((declared_name, declared)) => {
+                self.used.insert(declared_name);
+                let compatible = match expected {
+                    VariableTy::Iterable => {
+                        matches!(declared, VariableTy::Iterable | VariableTy::List)
+                    }
+                    VariableTy::Map | VariableTy::List | VariableTy::Number => {
+                        *declared == expected
+                    }
+                    VariableTy::String | VariableTy::Boolean => true,
+                };
+                if compatible {
+                    return;
+                }
+                SchemaError::TypeMismatch {
+                    variable: name.to_string(),
+                    expected,
+                    declared: *declared,
+                }
+            }
+            None => SchemaError::UndeclaredVariable {
+                variable: name.to_string(),
+            },
+        };
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+}
@@ -7,7 +7,7 @@
 //!
 //! ## Overview
 //!
-//! Once a template string is parsed (typically by [`crate::parser::tokenize()`]),
+//! Once a template string is parsed (typically by [`crate::parser::tokenize_with()`]),
 //! it is converted into a `Template` instance. This instance can then be used to:
 //!
 //! - **Render** the template with a given [`crate::interface::Context`], producing a final string output.
@@ -24,7 +24,12 @@
 //!
 //! - [`Template::new()`]: Constructs a new `Template` by parsing the provided content string.
 //! - [`Template::render()`]: Renders the template using a given context and an optional engine (for handling inclusions).
+//! - [`Template::render_cow()`]: Like `render()`, but borrows the output for constant-only templates.
+//! - [`Template::render_subtree()`]: Renders a single node of the template, addressed by its path from the root.
+//! - [`Template::dump_ast()`]: Pretty-prints the parsed template as an indented tree, for debugging.
 //! - [`Template::collect_variables()`]: Gathers a list of variables used within the template that are not present in a given context.
+//! - [`Template::variable_usage()`]: Counts how many times each variable is referenced in the template.
+//! - [`Template::validate_against()`]: Checks the variables used in the template against a declared schema.
 //!
 //! ## Example Usage
 //!
@@ -70,11 +75,20 @@
 //! The `Template` struct is fundamental for turning raw template strings into executable
 //! and analyzable structures within the Minilate system.
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::ast::AstNode;
-use crate::error::{MinilateError, MinilateResult};
-use crate::interface::{Context, MinilateInterface, VariableTy};
-use crate::parser::tokenize;
+use crate::ast::{AstNode, CompareOp};
+use crate::error::{MinilateError, MinilateResult, ParseError};
+use crate::escape::Escaper;
+use crate::interface::{Context, MinilateInterface, Variable, VariableTy};
+use crate::missing::MissingPolicy;
+use crate::parser::{DelimiterConfig, tokenize_all, tokenize_with};
+use crate::rendered::RenderedDoc;
+use crate::schema::SchemaError;
+use crate::visit::{Visitor, walk};
 
 /// A Template represents a parsed template that can be rendered with a context.
 ///
@@ -100,11 +114,29 @@ use crate::parser::tokenize;
 /// ```
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Template<'a> {
-    #[allow(dead_code, reason = "Referenced by ast.")]
     content: Cow<'a, str>,
     #[cfg_attr(feature = "serde", serde(skip))]
     pub(crate) ast: AstNode<'static>,
     pub(crate) name: Option<String>,
+    /// The complete output of the template if it contains only constant text, letting
+    /// [`Template::render`] skip walking the AST.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    fast_constant: Option<String>,
+    /// The variables used by the template, see [`Template::collect_variables`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    variable_uses: OnceLock<Vec<VariableUse<'static>>>,
+}
+
+/// A condition governing a conditional template inclusion, as reported by
+/// [`Template::find_conditional_inclusions`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InclusionCondition<'a> {
+    /// Included when the condition is true, i.e. in the body of an `{{% if %}}`.
+    When(&'a AstNode<'a>),
+    /// Included when the condition is false, i.e. in an `{{% else %}}` branch.
+    Unless(&'a AstNode<'a>),
+    /// Included when the iterable has no items, i.e. in an `{{% empty %}}` branch.
+    Empty(&'a AstNode<'a>),
 }
 
 #[cfg(feature = "serde")]
@@ -148,8 +180,157 @@ impl<'c> Template<'c> {
     ///
     /// Returns a `MinilateError::Parse` error if the template syntax is invalid.
     pub fn new<T: Into<Cow<'c, str>>>(content: T) -> MinilateResult<Self> {
-        let content: Cow<'c, str> = content.into();
+        Self::parse(content.into(), false, &DelimiterConfig::default(), None)
+    }
+
+    /// Creates a new template like [`Template::new`], but keeping any `{{% ... %}}`
+    /// directive with an unknown keyword verbatim as text rather than failing to parse.
+    ///
+    /// This lets Minilate render documents that also contain directives meant for another
+    /// templating system. Misplaced Minilate closing tags such as `{{% endif %}}` are
+    /// still rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MinilateError::Parse` error if the template syntax is otherwise invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateEngine, Template, VariableTy};
+    ///
+    /// let source = "{{% block title %}}{{ title }}{{% endblock %}}";
+    /// assert!(Template::new(source).is_err());
+    ///
+    /// let template = Template::new_preserving_unknown_directives(source).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("title", VariableTy::String.with_data("Home"));
+    ///
+    /// assert_eq!(
+    ///     template.render::<MinilateEngine>(&context, None).unwrap(),
+    ///     "{{% block title %}}Home{{% endblock %}}"
+    /// );
+    /// ```
+    pub fn new_preserving_unknown_directives<T: Into<Cow<'c, str>>>(
+        content: T,
+    ) -> MinilateResult<Self> {
+        Self::parse(content.into(), true, &DelimiterConfig::default(), None)
+    }
+
+    /// Creates a new template like [`Template::new`], but written with custom `delimiters`
+    /// instead of `{{ }}`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MinilateError::Parse` error if the template syntax is invalid, or if the
+    /// delimiters are ambiguous, e.g. because one of them is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, DelimiterConfig, MinilateEngine, Template, VariableTy};
+    ///
+    /// let delimiters = DelimiterConfig {
+    ///     open: "<%".to_string(),
+    ///     close: "%>".to_string(),
+    ///     block: "@".to_string(),
+    ///     ..DelimiterConfig::default()
+    /// };
+    /// let template = Template::new_with_delimiters("<%@ for x in xs @%>(<% x %>)<%@ endfor @%>", &delimiters).unwrap();
+    ///
+    /// let mut context = Context::new();
+    /// context.insert("xs", VariableTy::Iterable.with_data("a, b"));
+    /// assert_eq!(template.render::<MinilateEngine>(&context, None).unwrap(), "(a)(b)");
+    /// ```
+    pub fn new_with_delimiters<T: Into<Cow<'c, str>>>(
+        content: T,
+        delimiters: &DelimiterConfig,
+    ) -> MinilateResult<Self> {
+        Self::parse(content.into(), false, delimiters, None)
+    }
+
+    /// Creates a new template like [`Template::new`], but refusing to parse `content` if it
+    /// is longer than `max_length` bytes.
+    ///
+    /// This is a cheap guard for services that accept templates from users.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MinilateError::Parse` error with [`crate::ParseErrorKind::InputTooLong`]
+    /// if `content` is too long, or any other parse error if the template syntax is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{MinilateError, ParseErrorKind, Template};
+    ///
+    /// assert!(Template::new_with_max_length("Hello, {{ name }}!", 64).is_ok());
+    ///
+    /// assert!(matches!(
+    ///     Template::new_with_max_length("Hello, {{ name }}!", 8),
+    ///     Err(MinilateError::Parse(ref e)) if matches!(e.kind, ParseErrorKind::InputTooLong { .. })
+    /// ));
+    /// ```
+    pub fn new_with_max_length<T: Into<Cow<'c, str>>>(
+        content: T,
+        max_length: usize,
+    ) -> MinilateResult<Self> {
+        Self::parse(
+            content.into(),
+            false,
+            &DelimiterConfig::default(),
+            Some(max_length),
+        )
+    }
+
+    /// Creates a new template like [`Template::new`], but reporting every parse error in
+    /// `content` rather than just the first.
+    ///
+    /// After each error, parsing resumes at the next `{{` tag, so each error is reported
+    /// with its own line and column. Recovery is best-effort, and a block left open by an
+    /// error can cause a follow-on error at its closing tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns every [`ParseError`] found, in the order they appear in `content`, if the
+    /// template syntax is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::Template;
+    ///
+    /// let errors = match Template::new_collecting_errors("{{ first\n{{% if %}}") {
+    ///     Ok(_) => panic!("the template is invalid"),
+    ///     Err(errors) => errors,
+    /// };
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!((errors[0].line, errors[1].line), (2, 2));
+    /// ```
+    pub fn new_collecting_errors<T: Into<Cow<'c, str>>>(
+        content: T,
+    ) -> Result<Self, Vec<ParseError>> {
+        Self::parse_with(content.into(), tokenize_all)
+    }
+
+    /// Parses `content` with the given `delimiters`, keeping unknown directives as text if
+    /// `preserve_unknown_directives` is set, and rejecting content longer than `max_length`.
+    pub(crate) fn parse(
+        content: Cow<'c, str>,
+        preserve_unknown_directives: bool,
+        delimiters: &DelimiterConfig,
+        max_length: Option<usize>,
+    ) -> MinilateResult<Self> {
+        Ok(Self::parse_with(content, |content| {
+            tokenize_with(content, preserve_unknown_directives, delimiters, max_length)
+        })?)
+    }
 
+    /// Parses `content` into an AST with `tokenize`, and builds a template holding both.
+    fn parse_with<E>(
+        content: Cow<'c, str>,
+        tokenize: impl for<'s> FnOnce(&'s str) -> Result<AstNode<'s>, E>,
+    ) -> Result<Self, E> {
         // Parse the template content into an AST
         let ast = tokenize(&content)?;
 
@@ -161,13 +342,124 @@ impl<'c> Template<'c> {
         // 4. The Template is not exposed outside this module with these lifetime relationships
         let ast = unsafe { std::mem::transmute::<AstNode<'_>, AstNode<'static>>(ast) };
 
+        let fast_constant = constant_output(&ast);
+
         Ok(Self {
             content,
             ast,
             name: None,
+            fast_constant,
+            variable_uses: OnceLock::new(),
         })
     }
 
+    /// Returns the parsed AST of this template.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{AstNode, Template};
+    ///
+    /// let template = Template::new("Hello, {{ name }}!").unwrap();
+    /// let AstNode::Root(children) = template.ast() else {
+    ///     panic!("templates are parsed into a root node");
+    /// };
+    /// assert_eq!(children[1], AstNode::Variable { name: "name" });
+    /// ```
+    pub const fn ast(&self) -> &AstNode<'_> {
+        &self.ast
+    }
+
+    /// Returns the source this template was parsed from, exactly as it was given.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::Template;
+    ///
+    /// let template = Template::new("Hello, {{ name }}!").unwrap();
+    /// assert_eq!(template.source(), "Hello, {{ name }}!");
+    /// ```
+    pub fn source(&self) -> &str {
+        &self.content
+    }
+
+    /// Returns the initial state for rendering this template, whose include chain starts
+    /// with the template's own name when it has been added to an engine.
+    fn render_state<'r>(&self, cancel: Option<&'r AtomicBool>) -> RenderState<'r> {
+        RenderState {
+            active: self.name.iter().cloned().collect(),
+            included: Vec::new(),
+            cancel,
+        }
+    }
+
+    /// Returns an empty buffer to render this template into.
+    ///
+    /// Most output is made up of the template's own text, so reserving room for about as
+    /// much output as there is source avoids growing the buffer many times over.
+    fn output_buffer(&self) -> String {
+        String::with_capacity(self.content.len())
+    }
+
+    /// Serializes the parsed AST of this template to JSON.
+    ///
+    /// This allows tools written in other languages to consume a template parsed by
+    /// Minilate, and render it themselves. Each node is an object keyed by its
+    /// [`AstNode`] variant name, except for fieldless variants which are plain strings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::Template;
+    ///
+    /// let template = Template::new("Hello, {{ name }}!").unwrap();
+    /// assert_eq!(
+    ///     template.to_ast_json(),
+    ///     serde_json::json!({
+    ///         "Root": [
+    ///             { "Constant": { "data": "Hello, " } },
+    ///             { "Variable": { "name": "name" } },
+    ///             { "Constant": { "data": "!" } },
+    ///         ]
+    ///     })
+    /// );
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn to_ast_json(&self) -> serde_json::Value {
+        serde_json::to_value(self.ast()).expect("The AST only contains JSON-compatible data")
+    }
+
+    /// Pretty-prints the AST of this template as an indented tree, one node per line.
+    ///
+    /// Each line names an [`AstNode`] variant along with its variable names, operators or
+    /// text, and the parts of a loop or condition are labelled. The output is the same
+    /// for the same template, so it can be compared against a snapshot, or attached to a
+    /// bug report to show how a template was parsed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::Template;
+    ///
+    /// let template = Template::new("{{% if admin %}}Hi, {{ name }}{{% endif %}}").unwrap();
+    /// let expected = [
+    ///     "Root",
+    ///     "  If",
+    ///     "    condition:",
+    ///     "      Variable admin",
+    ///     "    body:",
+    ///     "      Constant \"Hi, \"",
+    ///     "      Variable name",
+    /// ];
+    /// assert_eq!(template.dump_ast(), format!("{}\n", expected.join("\n")));
+    /// ```
+    pub fn dump_ast(&self) -> String {
+        let mut output = String::new();
+        self.ast.write_tree(&mut output, 0);
+        output
+    }
+
     /// Collects variable names and types that are required by this template.
     ///
     /// This method traverses the template's AST and identifies all variables that
@@ -199,14 +491,166 @@ impl<'c> Template<'c> {
         variables: &mut Vec<(&'b str, VariableTy)>,
         context: &Context<'_>,
     ) {
-        collect_variables_from_node(&self.ast, variables, context);
+        require_variables(self.variable_uses(), variables, context);
+    }
+
+    /// Returns every use of a variable in this template, found the first time they're
+    /// needed and kept for later calls, as they don't depend on the context.
+    fn variable_uses(&self) -> &[VariableUse<'_>] {
+        self.variable_uses.get_or_init(|| {
+            let mut uses = Vec::new();
+            collect_variables_from_node(&self.ast, &mut uses, false);
+            uses
+        })
+    }
+
+    /// Reports every type mismatch rendering this template against `context` would hit,
+    /// rather than stopping at the first.
+    ///
+    /// Each variable's type is inferred from its use, as in [`Template::collect_variables`],
+    /// and checked against its type in `context`: a for-loop iterable must be a
+    /// [`VariableTy::Iterable`], or a [`VariableTy::Map`] for a `{{% for k, v in map %}}`
+    /// loop, and a numeric comparison operand must hold a number. Variables missing from
+    /// `context` aren't mismatches, and included templates are not inspected. Each
+    /// mismatch is reported once, in the order it appears in the template.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Context, MinilateError, Template, VariableTy};
+    ///
+    /// let template = Template::new("{{% for tag in tags %}}{{ tag }}{{% endfor %}}").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("tags", VariableTy::String.with_data("a, b"));
+    ///
+    /// assert_eq!(
+    ///     template.type_mismatches(&context),
+    ///     vec![MinilateError::TypeMismatch {
+    ///         variable_name: "tags".to_string(),
+    ///         template_name: None,
+    ///         expected: VariableTy::Iterable,
+    ///         found: VariableTy::String,
+    ///     }]
+    /// );
+    /// ```
+    pub fn type_mismatches(&self, context: &Context<'_>) -> Vec<MinilateError> {
+        let mut mismatches = Vec::new();
+        collect_type_mismatches(&self.ast, context, &mut Vec::new(), &mut mismatches);
+        mismatches
+            .into_iter()
+            .map(|mismatch| mismatch.in_template(self.name.as_deref()))
+            .collect()
+    }
+
+    /// Checks this template against a schema declaring the type of every variable a
+    /// context will provide.
+    ///
+    /// Reports each variable the template uses that `schema` doesn't declare, each one it
+    /// uses as a type `schema` doesn't give it, in the order they appear in the template,
+    /// and then each entry of `schema` the template never uses, sorted. Types are
+    /// inferred from use, as in [`Template::type_mismatches`], except that a numeric
+    /// comparison needs a [`VariableTy::Number`], as a schema has no data to parse.
+    /// Loop variables and their fields are bound by the loop, so needn't be declared, and
+    /// included templates are not inspected.
+    ///
+    /// # Errors
+    ///
+    /// Returns every [`SchemaError`] found, if there are any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use minilate::{SchemaError, Template, VariableTy};
+    ///
+    /// let template = Template::new("{{% for tag in tags %}}{{ tag }}{{% endfor %}}").unwrap();
+    /// let schema = HashMap::from([
+    ///     ("tags".to_string(), VariableTy::String),
+    ///     ("title".to_string(), VariableTy::String),
+    /// ]);
+    ///
+    /// assert_eq!(
+    ///     template.validate_against(&schema),
+    ///     Err(vec![
+    ///         SchemaError::TypeMismatch {
+    ///             variable: "tags".to_string(),
+    ///             expected: VariableTy::Iterable,
+    ///             declared: VariableTy::String,
+    ///         },
+    ///         SchemaError::UnusedVariable {
+    ///             variable: "title".to_string(),
+    ///         },
+    ///     ])
+    /// );
+    /// ```
+    pub fn validate_against(
+        &self,
+        schema: &HashMap<String, VariableTy>,
+    ) -> Result<(), Vec<SchemaError>> {
+        let errors = crate::schema::validate(&self.ast, schema);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Counts how many times each variable is referenced in this template.
+    ///
+    /// Every reference is counted, whether it's a substitution, part of a condition, a
+    /// loop iterable or part of a dynamic include name. Loop variables and aliases are
+    /// counted where they're used, like any other variable, and each `{{% alias %}}` counts
+    /// as a reference to the variable it stands for. Included templates are not inspected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::Template;
+    ///
+    /// let template = Template::new("{{ name }}{{% if admin %}} ({{ name }}){{% endif %}}").unwrap();
+    /// let usage = template.variable_usage();
+    ///
+    /// assert_eq!(usage["name"], 2);
+    /// assert_eq!(usage["admin"], 1);
+    /// ```
+    pub fn variable_usage(&self) -> HashMap<&str, usize> {
+        let mut usage = VariableUsage::default();
+        walk(self.ast(), &mut usage);
+        usage.counts
+    }
+
+    /// Returns every literal string in this template, in the order they appear, such as
+    /// for extracting translatable strings.
+    ///
+    /// Quoted literals are collected from conditions, for-loop iterables, `{{% set %}}`
+    /// values and the variables passed to includes, along with the names of includes and
+    /// the fixed text of include names built from variables, other than any written with
+    /// escapes. Included templates are not inspected.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::Template;
+    ///
+    /// let template = Template::new(
+    ///     "{{% if status == \"active\" %}}{{% set label = \"Welcome\" %}}{{ label }}{{% endif %}}\
+    ///      {{<< \"cards/{{ kind }}\" }}",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(template.string_literals(), vec!["active", "Welcome", "cards/"]);
+    /// ```
+    pub fn string_literals(&self) -> Vec<&str> {
+        let mut literals = StringLiterals::default();
+        walk(self.ast(), &mut literals);
+        literals.values
     }
 
     /// Finds all template inclusions in this template, separating them into direct and conditional inclusions.
     ///
     /// This method traverses the template's AST and returns:
     /// 1. A vector of template names that are unconditionally included
-    /// 2. A vector of (template name, condition met) pairs for conditionally included templates
+    /// 2. A vector of (template name, conditions) pairs for conditionally included templates
     /// 3. A vector of (template name, iterable_var) pairs for templates included in for loops
     ///
     /// Conditional inclusions will only be considered for variable collection when their
@@ -226,7 +670,7 @@ impl<'c> Template<'c> {
             &mut conditional_inclusions,
             &mut for_loop_inclusions,
             context,
-            false,
+            &[],
             None,
         );
 
@@ -234,6 +678,72 @@ impl<'c> Template<'c> {
         direct_inclusions
     }
 
+    /// Returns the name of every template this template includes by name, whether
+    /// unconditionally, inside a conditional branch, or inside a for loop.
+    ///
+    /// Dynamic includes are not reported, as their names depend on the context.
+    pub(crate) fn static_inclusions(&self) -> Vec<&str> {
+        let mut direct_inclusions = Vec::new();
+        let mut conditional_inclusions = Vec::new();
+        let mut for_loop_inclusions = Vec::new();
+        find_template_inclusions(
+            self.ast(),
+            &mut direct_inclusions,
+            &mut conditional_inclusions,
+            &mut for_loop_inclusions,
+            &Context::new(),
+            &[],
+            None,
+        );
+
+        direct_inclusions
+            .into_iter()
+            .chain(conditional_inclusions.into_iter().map(|(name, _)| name))
+            .chain(for_loop_inclusions.into_iter().map(|(name, _)| name))
+            .collect()
+    }
+
+    /// Finds the templates included only inside conditional branches, along with the
+    /// conditions that must all hold for each to be included.
+    ///
+    /// Conditions are listed from the outermost branch inwards. Inclusions inside for
+    /// loop bodies are not reported, as they depend on the loop's items instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{AstNode, InclusionCondition, Template};
+    ///
+    /// let template =
+    ///     Template::new("{{% if polite %}}{{<< thanks }}{{% else %}}{{<< bye }}{{% endif %}}")
+    ///         .unwrap();
+    ///
+    /// let polite = AstNode::Variable { name: "polite" };
+    /// assert_eq!(
+    ///     template.find_conditional_inclusions(),
+    ///     vec![
+    ///         ("thanks", vec![InclusionCondition::When(&polite)]),
+    ///         ("bye", vec![InclusionCondition::Unless(&polite)]),
+    ///     ]
+    /// );
+    /// ```
+    pub fn find_conditional_inclusions(&self) -> Vec<(&str, Vec<InclusionCondition<'_>>)> {
+        let mut direct_inclusions = Vec::new();
+        let mut conditional_inclusions = Vec::new();
+        let mut for_loop_inclusions = Vec::new();
+        find_template_inclusions(
+            self.ast(),
+            &mut direct_inclusions,
+            &mut conditional_inclusions,
+            &mut for_loop_inclusions,
+            &Context::new(),
+            &[],
+            None,
+        );
+
+        conditional_inclusions
+    }
+
     /// Renders the template with the provided context.
     ///
     /// This method traverses the template's AST, substituting variables with their
@@ -284,134 +794,1018 @@ impl<'c> Template<'c> {
     where
         E: MinilateInterface,
     {
-        let mut output = String::new();
-        render_node(&self.ast, context, &mut output, engine)?;
+        let mut output = self.output_buffer();
+        self.render_to(context, engine, &mut output)?;
         Ok(output)
     }
-}
 
-/// Internal implementation of variable collection from AST nodes
-///
-/// This function recursively traverses the AST to find all variables used in the template.
-/// It handles different types of nodes like variables, for-loops, if statements, etc.,
-/// and collects variables with their appropriate types based on usage context.
-fn collect_variables_from_node<'a>(
-    node: &'a AstNode<'a>,
-    variables: &mut Vec<(&'a str, VariableTy)>,
-    context: &Context<'_>,
-) {
-    match node {
-        AstNode::Root(children) => {
-            for child in children {
-                collect_variables_from_node(child, variables, context);
-            }
-        }
-        AstNode::Constant { .. } => {
-            // Constants don't introduce variables
-        }
-        AstNode::Variable { name } => {
-            // If the variable isn't already in our context, add it
-            if !context.contains(name) && !variables.iter().any(|(var_name, _)| *var_name == *name)
-            {
-                variables.push((name, VariableTy::String));
-            }
+    /// Renders the template like [`Template::render`], but appends the output to `out`
+    /// instead of returning a new `String`.
+    ///
+    /// This lets a single buffer be reused when rendering many templates. If rendering
+    /// fails, `out` may have been partially written to.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Template::render`], or `MinilateError::RenderError`
+    /// if writing to `out` fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Template, Context, VariableTy, MinilateEngine};
+    ///
+    /// let greeting = Template::new("Hello, {{ name }}! ").unwrap();
+    /// let farewell = Template::new("Bye, {{ name }}!").unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("World"));
+    ///
+    /// let mut output = String::new();
+    /// greeting.render_to::<MinilateEngine, _>(&context, None, &mut output).unwrap();
+    /// farewell.render_to::<MinilateEngine, _>(&context, None, &mut output).unwrap();
+    /// assert_eq!(output, "Hello, World! Bye, World!");
+    /// ```
+    pub fn render_to<E, W>(
+        &self,
+        context: &Context<'_>,
+        engine: Option<&E>,
+        out: &mut W,
+    ) -> MinilateResult<()>
+    where
+        E: MinilateInterface,
+        W: Write + ?Sized,
+    {
+        // Templates without any tags render the same regardless of context
+        if let Some(constant) = &self.fast_constant {
+            out.write_str(constant)?;
+            return Ok(());
         }
-        AstNode::For {
-            iterable,
-            variable: _,
-            body,
-        } => {
-            // The iterable is a variable that needs to be of type Iterable
-            if !context.contains(iterable)
-                && !variables.iter().any(|(var_name, _)| *var_name == *iterable)
-            {
-                variables.push((iterable, VariableTy::Iterable));
-            }
 
-            // Don't collect the loop variable as it's defined by the loop
-            // But do collect variables inside the loop body
-            for child in body {
-                collect_variables_from_node(child, variables, context);
-            }
-        }
-        AstNode::If {
-            condition,
-            body,
-            else_branch,
-        } => {
-            // Collect variables from the condition, but mark them as Boolean type
-            match condition.as_ref() {
-                AstNode::Variable { name } => {
-                    if !context.contains(name)
-                        && !variables.iter().any(|(var_name, _)| *var_name == *name)
-                    {
-                        variables.push((name, VariableTy::Boolean));
-                    }
-                }
-                AstNode::Not { condition } => {
-                    // For NOT operator, look at its variable and mark as Boolean
-                    if let AstNode::Variable { name } = condition.as_ref() {
-                        if !context.contains(name)
-                            && !variables.iter().any(|(var_name, _)| *var_name == *name)
-                        {
-                            variables.push((name, VariableTy::Boolean));
-                        }
-                    } else {
-                        collect_variables_from_node(condition, variables, context);
-                    }
-                }
-                AstNode::And { left, right } | AstNode::Or { left, right } => {
-                    // For AND/OR operators, check both sides for variables
-                    if let AstNode::Variable { name } = left.as_ref() {
-                        if !context.contains(name)
-                            && !variables.iter().any(|(var_name, _)| *var_name == *name)
-                        {
-                            variables.push((name, VariableTy::Boolean));
-                        }
-                    } else {
-                        collect_variables_from_node(left, variables, context);
-                    }
+        render_node(
+            &self.ast,
+            &render_context(context, engine),
+            out,
+            engine,
+            None,
+            &mut self.render_state(None),
+        )
+        .map_err(|error| error.in_template(self.name.as_deref()))?;
+        Ok(())
+    }
 
-                    if let AstNode::Variable { name } = right.as_ref() {
-                        if !context.contains(name)
-                            && !variables.iter().any(|(var_name, _)| *var_name == *name)
-                        {
-                            variables.push((name, VariableTy::Boolean));
-                        }
-                    } else {
-                        collect_variables_from_node(right, variables, context);
-                    }
-                }
-                AstNode::Root(_)
-                | AstNode::Constant { .. }
-                | AstNode::For { .. }
-                | AstNode::If { .. }
-                | AstNode::TemplateInclude { .. } => {
-                    collect_variables_from_node(condition, variables, context);
-                }
+    /// Renders the template like [`Template::render`], but borrows the output from the
+    /// template when no rendering work is needed.
+    ///
+    /// Templates made up only of constant text return [`Cow::Borrowed`], avoiding an
+    /// allocation per render. All other templates return [`Cow::Owned`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Template::render`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use minilate::{Template, Context, MinilateEngine};
+    ///
+    /// let template = Template::new("No tags here.".to_string()).unwrap();
+    /// let result = template.render_cow::<MinilateEngine>(&Context::new(), None).unwrap();
+    /// assert!(matches!(result, Cow::Borrowed("No tags here.")));
+    /// ```
+    pub fn render_cow<E>(
+        &self,
+        context: &Context<'_>,
+        engine: Option<&E>,
+    ) -> MinilateResult<Cow<'_, str>>
+    where
+        E: MinilateInterface,
+    {
+        // Templates without any tags render the same regardless of context
+        if let Some(constant) = &self.fast_constant {
+            return Ok(Cow::Borrowed(constant));
+        }
+
+        let mut output = self.output_buffer();
+        self.render_to(context, engine, &mut output)?;
+        Ok(Cow::Owned(output))
+    }
+
+    /// Renders the template like [`Template::render`], but wraps the output of every
+    /// variable in markers recording where that variable appears in the template source.
+    ///
+    /// Each variable's output is surrounded by `<!--line:column-->` and `<!--/line:column-->`,
+    /// where `line` and `column` are the 1-indexed position of the variable's name. This is
+    /// intended for debugging and tooling, to trace a region of output back to its source.
+    /// Output from included templates is not annotated.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Template::render`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Template, Context, VariableTy, MinilateEngine};
+    ///
+    /// let template = Template::new("Hello,\n  {{ name }}!".to_string()).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("World"));
+    ///
+    /// let result = template.render_annotated::<MinilateEngine>(&context, None).unwrap();
+    /// assert_eq!(result, "Hello,\n  <!--2:6-->World<!--/2:6-->!");
+    /// ```
+    pub fn render_annotated<E>(
+        &self,
+        context: &Context<'_>,
+        engine: Option<&E>,
+    ) -> MinilateResult<String>
+    where
+        E: MinilateInterface,
+    {
+        let mut output = self.output_buffer();
+        render_node(
+            &self.ast,
+            &render_context(context, engine),
+            &mut output,
+            engine,
+            Some(&self.content),
+            &mut self.render_state(None),
+        )
+        .map_err(|error| error.in_template(self.name.as_deref()))?;
+        Ok(output)
+    }
+
+    /// Renders only the node found by following `path` down from the root of the template.
+    ///
+    /// Each index in `path` selects a child of the current node. The children of the root
+    /// are the top-level nodes of the template. The children of an `if` are the nodes in its
+    /// body, followed by its else branch if present; likewise a `for` loop's children are its
+    /// body followed by its `{{% empty %}}` branch. An empty path renders the whole template.
+    ///
+    /// # Errors
+    ///
+    /// * `MinilateError::RenderError` - If `path` does not address a node in this template
+    /// * Any error that [`Template::render`] could return for the addressed node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Template, Context, VariableTy, MinilateEngine};
+    ///
+    /// let template = Template::new("Hi {{% if admin %}}Admin {{ name }}{{% endif %}}!".to_string()).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("Ada"));
+    ///
+    /// // The `if` is the second top-level node, and `{{ name }}` the second node in its body
+    /// let result = template.render_subtree::<MinilateEngine>(&[1, 1], &context, None).unwrap();
+    /// assert_eq!(result, "Ada");
+    /// ```
+    pub fn render_subtree<E>(
+        &self,
+        path: &[usize],
+        context: &Context<'_>,
+        engine: Option<&E>,
+    ) -> MinilateResult<String>
+    where
+        E: MinilateInterface,
+    {
+        let node = path
+            .iter()
+            .try_fold(&self.ast, |node, &index| child_at(node, index))
+            .ok_or_else(|| MinilateError::RenderError {
+                message: format!("No node found at path {:?}", path),
+            })?;
+
+        let mut output = String::new();
+        render_node(
+            node,
+            &render_context(context, engine),
+            &mut output,
+            engine,
+            None,
+            &mut self.render_state(None),
+        )
+        .map_err(|error| error.in_template(self.name.as_deref()))?;
+        Ok(output)
+    }
+
+    /// Renders the template like [`Template::render`], but also records the variables and
+    /// included templates the output was built from.
+    ///
+    /// The recorded variables are those the template and its includes refer to which are
+    /// present in `context`, so loop variables and variables behind untaken branches that
+    /// the context doesn't provide are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`Template::render`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use minilate::{Template, Context, VariableTy, MinilateEngine};
+    ///
+    /// let template = Template::new("Hello, {{ name }}!".to_string()).unwrap();
+    /// let mut context = Context::new();
+    /// context.insert("name", VariableTy::String.with_data("World"));
+    ///
+    /// let doc = template.render_capture::<MinilateEngine>(&context, None).unwrap();
+    /// assert_eq!(doc.output, "Hello, World!");
+    /// assert_eq!(doc.variables, ["name"]);
+    /// assert!(doc.includes.is_empty());
+    /// ```
+    pub fn render_capture<E>(
+        &self,
+        context: &Context<'_>,
+        engine: Option<&E>,
+    ) -> MinilateResult<RenderedDoc>
+    where
+        E: MinilateInterface,
+    {
+        let context = render_context(context, engine);
+        let mut state = self.render_state(None);
+        let mut output = self.output_buffer();
+        render_node(&self.ast, &context, &mut output, engine, None, &mut state)
+            .map_err(|error| error.in_template(self.name.as_deref()))?;
+
+        let empty_context = Context::new();
+        let mut used = Vec::new();
+        self.collect_variables(&mut used, &empty_context);
+        for name in &state.included {
+            if let Some(template) = engine.and_then(|engine| engine.get_template(name)) {
+                template.collect_variables(&mut used, &empty_context);
             }
+        }
+        let mut variables: Vec<String> = used
+            .into_iter()
+            .filter(|&(name, _)| context.contains(name))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        variables.sort_unstable();
+        variables.dedup();
 
-            // Collect variables from the body
-            for child in body {
-                collect_variables_from_node(child, variables, context);
+        Ok(RenderedDoc {
+            output,
+            variables,
+            includes: state.included,
+        })
+    }
+
+    /// Renders the template like [`Template::render`], but abandons the render as soon as
+    /// `cancel` is set, e.g. by another thread enforcing a timeout.
+    ///
+    /// The flag is checked before rendering each node and each loop iteration, so a
+    /// render stops promptly however large its loops are.
+    ///
+    /// # Errors
+    ///
+    /// * `MinilateError::Cancelled` - If `cancel` was set before the render finished
+    /// * Any error that [`Template::render`] could return
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::atomic::AtomicBool;
+    /// use minilate::{Context, MinilateEngine, MinilateError, Template};
+    ///
+    /// let template = Template::new("{{% repeat 1000 %}}.{{% endrepeat %}}").unwrap();
+    ///
+    /// let cancel = AtomicBool::new(false);
+    /// let output = template.render_with_cancel::<MinilateEngine>(&Context::new(), None, &cancel);
+    /// assert_eq!(output.unwrap().len(), 1000);
+    ///
+    /// let cancel = AtomicBool::new(true);
+    /// let output = template.render_with_cancel::<MinilateEngine>(&Context::new(), None, &cancel);
+    /// assert!(matches!(output, Err(MinilateError::Cancelled)));
+    /// ```
+    pub fn render_with_cancel<E>(
+        &self,
+        context: &Context<'_>,
+        engine: Option<&E>,
+        cancel: &AtomicBool,
+    ) -> MinilateResult<String>
+    where
+        E: MinilateInterface,
+    {
+        let mut output = self.output_buffer();
+        render_node(
+            &self.ast,
+            &render_context(context, engine),
+            &mut output,
+            engine,
+            None,
+            &mut self.render_state(Some(cancel)),
+        )
+        .map_err(|error| error.in_template(self.name.as_deref()))?;
+        Ok(output)
+    }
+}
+
+/// Returns the full output of `ast` if it consists only of constant text
+fn constant_output(ast: &AstNode<'_>) -> Option<String> {
+    let AstNode::Root(children) = ast else {
+        return None;
+    };
+
+    children
+        .iter()
+        .try_fold(String::new(), |mut output, child| {
+            let AstNode::Constant { data, trim } = child else {
+                return None;
+            };
+            output.push_str(trim.apply(data));
+            Some(output)
+        })
+}
+
+/// Returns the context to render with, which looks up names case-insensitively and splits
+/// iterables as the engine asks (see [`crate::MinilateEngine::set_case_insensitive_vars`]
+/// and [`crate::MinilateEngine::set_iterable_separator`])
+pub fn render_context<'c, 'v, E: MinilateInterface>(
+    context: &'c Context<'v>,
+    engine: Option<&E>,
+) -> Cow<'c, Context<'v>> {
+    let Some(engine) = engine else {
+        return Cow::Borrowed(context);
+    };
+    let case_insensitive = engine.case_insensitive_vars();
+    let separator = engine.iterable_separator();
+    if !case_insensitive && separator == context.separator() {
+        return Cow::Borrowed(context);
+    }
+
+    let mut context = context.clone();
+    if case_insensitive {
+        context.set_case_insensitive();
+    }
+    context.set_separator(separator);
+    Cow::Owned(context)
+}
+
+/// Returns the child of `node` at `index`, as addressed by [`Template::render_subtree`]
+fn child_at<'n>(node: &'n AstNode<'n>, index: usize) -> Option<&'n AstNode<'n>> {
+    match node {
+        AstNode::Root(children) => children.get(index),
+        AstNode::If {
+            body, else_branch, ..
+        } => body.iter().chain(else_branch.as_deref()).nth(index),
+        AstNode::For {
+            body, empty_branch, ..
+        } => body.iter().chain(empty_branch.as_deref()).nth(index),
+        AstNode::Repeat { body, .. } | AstNode::DebugOnly { body } => body.get(index),
+        AstNode::Constant { .. }
+        | AstNode::Variable { .. }
+        | AstNode::Filter { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue => None,
+    }
+}
+
+/// A use of a variable in a template, recorded whether or not a context provides it.
+///
+/// Walking a template for the variables it uses doesn't depend on the context, so the uses
+/// are found once and then checked against each context by [`require_variables`].
+#[derive(Debug, Clone, Copy)]
+struct VariableUse<'a> {
+    name: &'a str,
+    /// The type the variable is used as
+    ty: VariableTy,
+    /// Whether the name may be an indexed element such as `items.0`, which needs the
+    /// iterable it's taken from instead
+    indexable: bool,
+    /// Whether the use is inside a loop, where the loop metadata is provided
+    in_loop: bool,
+}
+
+/// Adds the variables among `uses` that `context` doesn't provide to `variables`, each
+/// only once and with the type of its first use.
+fn require_variables<'a>(
+    uses: &[VariableUse<'a>],
+    variables: &mut Vec<(&'a str, VariableTy)>,
+    context: &Context<'_>,
+) {
+    let mut loop_context = Context::scoped(context);
+    insert_loop_metadata(&mut loop_context, 0, 1);
+
+    for variable in uses {
+        let scope = if variable.in_loop {
+            &loop_context
+        } else {
+            context
+        };
+
+        // An indexed element such as `items.0` needs the iterable it's taken from
+        let (name, ty) = match indexed_name(variable.name)
+            .filter(|_| variable.indexable && !scope.contains(variable.name))
+        {
+            Some((base, _)) => (base, VariableTy::Iterable),
+            None => (variable.name, variable.ty),
+        };
+
+        if !scope.contains(name) && !variables.iter().any(|(var_name, _)| *var_name == name) {
+            variables.push((name, ty));
+        }
+    }
+}
+
+/// Internal implementation of variable collection from AST nodes
+///
+/// This function recursively traverses the AST to find all variables used in the template.
+/// It handles different types of nodes like variables, for-loops, if statements, etc.,
+/// and records the variables with their appropriate types based on usage, noting which
+/// are used inside a loop.
+fn collect_variables_from_node<'a>(
+    node: &AstNode<'a>,
+    uses: &mut Vec<VariableUse<'a>>,
+    in_loop: bool,
+) {
+    match node {
+        AstNode::Root(children) => collect_block_variables(children, uses, in_loop),
+        AstNode::Constant { .. } => {
+            // Constants don't introduce variables
+        }
+        AstNode::Variable { name } => {
+            uses.push(VariableUse {
+                name,
+                ty: VariableTy::String,
+                indexable: true,
+                in_loop,
+            });
+        }
+        AstNode::For {
+            iterable,
+            variable: _,
+            value_variable,
+            body,
+            empty_branch,
+        } => {
+            // The iterable is a variable (or choice of variables) that needs to be of type
+            // Iterable, or Map when the loop binds both keys and values
+            let ty = if value_variable.is_some() {
+                VariableTy::Map
+            } else {
+                VariableTy::Iterable
+            };
+            collect_iterable_variables(iterable, ty, uses, in_loop);
+
+            // Don't collect the loop variable as it's defined by the loop
+            // But do collect variables inside the loop body, where the loop metadata is provided
+            collect_block_variables(body, uses, true);
+
+            // Collect variables from the empty branch if it exists
+            if let Some(empty_node) = empty_branch {
+                collect_variables_from_node(empty_node, uses, in_loop);
             }
+        }
+        AstNode::If {
+            condition,
+            body,
+            else_branch,
+        } => {
+            // Collect variables from the condition, but mark them as Boolean type
+            collect_condition_variables(condition, uses, in_loop);
+
+            // Collect variables from the body
+            collect_block_variables(body, uses, in_loop);
 
             // Collect variables from the else branch if it exists
             if let Some(else_node) = else_branch {
-                collect_variables_from_node(else_node, variables, context);
+                collect_variables_from_node(else_node, uses, in_loop);
             }
         }
         // Else nodes have been replaced with Root nodes
         AstNode::Not { condition } => {
-            collect_variables_from_node(condition, variables, context);
+            collect_variables_from_node(condition, uses, in_loop);
+        }
+        AstNode::And { left, right }
+        | AstNode::Or { left, right }
+        | AstNode::Xor { left, right } => {
+            collect_variables_from_node(left, uses, in_loop);
+            collect_variables_from_node(right, uses, in_loop);
+        }
+        AstNode::Compare { left, op, right } => {
+            // Relational operators compare numbers, while equality compares string data and
+            // `contains` searches an iterable
+            let (left_ty, right_ty) = match op {
+                CompareOp::Contains => (VariableTy::Iterable, VariableTy::String),
+                CompareOp::Lt | CompareOp::Gt | CompareOp::Le | CompareOp::Ge => {
+                    (VariableTy::Number, VariableTy::Number)
+                }
+                CompareOp::Eq | CompareOp::Ne => (VariableTy::String, VariableTy::String),
+            };
+            collect_operand_variables(left, left_ty, uses, in_loop);
+            collect_operand_variables(right, right_ty, uses, in_loop);
+        }
+        AstNode::StringLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::BoolLiteral { .. } => {
+            // Literals don't introduce variables
         }
-        AstNode::And { left, right } | AstNode::Or { left, right } => {
-            collect_variables_from_node(left, variables, context);
-            collect_variables_from_node(right, variables, context);
+        AstNode::Coalesce { .. } => {
+            // A fallback on its own is a condition, so its variables are tested for truth
+            collect_operand_variables(node, VariableTy::Boolean, uses, in_loop);
+        }
+        AstNode::Filter { inner, .. } => {
+            collect_variables_from_node(inner, uses, in_loop);
+        }
+        AstNode::Ternary { .. } => {
+            // Ternaries only appear as for-loop iterables
+            collect_iterable_variables(node, VariableTy::Iterable, uses, in_loop);
+        }
+        AstNode::DynamicInclude { name } => {
+            // The variables making up the name are needed to pick the template
+            for part in name {
+                collect_variables_from_node(part, uses, in_loop);
+            }
         }
         AstNode::TemplateInclude { .. } => {
             // Template inclusions are handled separately in collect_inclusion_variables
         }
+        AstNode::DebugDump => {
+            // Debug dumps only read whatever is already in the context
+        }
+        AstNode::Break | AstNode::Continue => {
+            // Loop control doesn't use any variables
+        }
+        AstNode::Repeat { body, .. } => {
+            // The loop metadata is provided by the block, so isn't required from the context
+            collect_block_variables(body, uses, true);
+        }
+        AstNode::DebugOnly { body } => {
+            // Collected regardless of the engine's debug setting, which isn't known here
+            collect_block_variables(body, uses, in_loop);
+        }
+        AstNode::Set { value, .. } => {
+            // The assigned variable is provided to the rest of the block, see
+            // `collect_block_variables`
+            collect_variables_from_node(value, uses, in_loop);
+        }
+        AstNode::Alias { .. } => {
+            // Uses of the alias are renamed by `collect_block_variables`
+        }
+    }
+}
+
+/// Collects the variables used by the nodes of a block, where a variable assigned with
+/// `{{% set %}}` isn't needed from the context by the nodes after the assignment, and
+/// the nodes after an `{{% alias %}}` need the variable it stands for instead of it
+fn collect_block_variables<'a>(
+    children: &[AstNode<'a>],
+    uses: &mut Vec<VariableUse<'a>>,
+    in_loop: bool,
+) {
+    for (index, child) in children.iter().enumerate() {
+        collect_variables_from_node(child, uses, in_loop);
+        let rest = children.get(index.saturating_add(1)..).unwrap_or_default();
+        if let AstNode::Set { name, .. } = child {
+            let start = uses.len();
+            collect_block_variables(rest, uses, in_loop);
+            let later = uses.split_off(start);
+            uses.extend(later.into_iter().filter(|variable| variable.name != *name));
+            return;
+        } else if let AstNode::Alias { name, target } = child {
+            let start = uses.len();
+            collect_block_variables(rest, uses, in_loop);
+            for variable in uses.iter_mut().skip(start) {
+                if variable.name == *name {
+                    variable.name = target;
+                }
+            }
+            return;
+        }
+    }
+}
+
+/// Collects the type mismatches rendering `node` would hit, see [`Template::type_mismatches`]
+///
+/// `bound` holds the loop variables and variables assigned with `{{% set %}}` in scope,
+/// which shadow the context and so can't mismatch.
+fn collect_type_mismatches<'a>(
+    node: &'a AstNode<'a>,
+    context: &Context<'_>,
+    bound: &mut Vec<&'a str>,
+    mismatches: &mut Vec<MinilateError>,
+) {
+    match node {
+        AstNode::Root(children) | AstNode::DebugOnly { body: children } => {
+            collect_block_type_mismatches(children, context, bound, mismatches);
+        }
+        AstNode::For {
+            iterable,
+            variable,
+            value_variable,
+            body,
+            empty_branch,
+        } => {
+            let expected = if value_variable.is_some() {
+                VariableTy::Map
+            } else {
+                VariableTy::Iterable
+            };
+            check_iterable_type(iterable, expected, context, bound, mismatches);
+
+            if let Some(empty_node) = empty_branch {
+                collect_type_mismatches(empty_node, context, bound, mismatches);
+            }
+
+            let mut loop_context = Context::scoped(context);
+            insert_loop_metadata(&mut loop_context, 0, 1);
+            let scope = bound.len();
+            bound.push(variable);
+            bound.extend(*value_variable);
+            collect_block_type_mismatches(body, &loop_context, bound, mismatches);
+            bound.truncate(scope);
+        }
+        AstNode::Repeat { body, .. } => {
+            let mut loop_context = Context::scoped(context);
+            insert_loop_metadata(&mut loop_context, 0, 1);
+            collect_block_type_mismatches(body, &loop_context, bound, mismatches);
+        }
+        AstNode::If {
+            condition,
+            body,
+            else_branch,
+        } => {
+            collect_type_mismatches(condition, context, bound, mismatches);
+            collect_block_type_mismatches(body, context, bound, mismatches);
+            if let Some(else_node) = else_branch {
+                collect_type_mismatches(else_node, context, bound, mismatches);
+            }
+        }
+        AstNode::Not { condition } => {
+            collect_type_mismatches(condition, context, bound, mismatches);
+        }
+        AstNode::And { left, right }
+        | AstNode::Or { left, right }
+        | AstNode::Xor { left, right } => {
+            collect_type_mismatches(left, context, bound, mismatches);
+            collect_type_mismatches(right, context, bound, mismatches);
+        }
+        AstNode::Compare { left, op, right } => {
+            // Only relational comparisons require a type, as equality and `contains`
+            // compare the data of any variable
+            if op.is_relational() {
+                let mut operands = Vec::new();
+                collect_operand_variables(left, VariableTy::Number, &mut operands, false);
+                collect_operand_variables(right, VariableTy::Number, &mut operands, false);
+                for operand in operands {
+                    check_variable_type(operand.name, operand.ty, context, bound, mismatches);
+                }
+            }
+        }
+        AstNode::Constant { .. }
+        | AstNode::Variable { .. }
+        | AstNode::Filter { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Break
+        | AstNode::Continue => {
+            // Any type of variable can be output, tested for truth or name a template
+        }
+        AstNode::Set { name, .. } => {
+            // The assigned value is only output, but shadows the context for the rest of
+            // the block
+            bound.push(name);
+        }
+        AstNode::Alias { .. } => {
+            // Brought into scope for the rest of the block by `collect_block_type_mismatches`
+        }
+    }
+}
+
+/// Collects the type mismatches rendering the nodes of a block would hit, unbinding any
+/// variables assigned with `{{% set %}}` at its end
+///
+/// The nodes after an `{{% alias %}}` look it up in a scope on top of `context`, as when
+/// rendering.
+fn collect_block_type_mismatches<'a>(
+    children: &'a [AstNode<'a>],
+    context: &Context<'_>,
+    bound: &mut Vec<&'a str>,
+    mismatches: &mut Vec<MinilateError>,
+) {
+    let scope = bound.len();
+    for (index, child) in children.iter().enumerate() {
+        if let AstNode::Alias { name, target } = child {
+            let mut alias_scope = Context::scoped(context);
+            alias_scope.insert_alias(name, target);
+            let rest = children.get(index.saturating_add(1)..).unwrap_or_default();
+            collect_block_type_mismatches(rest, &alias_scope, bound, mismatches);
+            break;
+        }
+        collect_type_mismatches(child, context, bound, mismatches);
+    }
+    bound.truncate(scope);
+}
+
+/// Checks the candidate variables of a for-loop iterable are of the `expected` type
+fn check_iterable_type<'a>(
+    iterable: &'a AstNode<'a>,
+    expected: VariableTy,
+    context: &Context<'_>,
+    bound: &[&'a str],
+    mismatches: &mut Vec<MinilateError>,
+) {
+    if let AstNode::Variable { name } = iterable {
+        check_variable_type(name, expected, context, bound, mismatches);
+    } else if let AstNode::Ternary {
+        condition,
+        if_true,
+        if_false,
+    } = iterable
+    {
+        collect_type_mismatches(condition, context, &mut bound.to_vec(), mismatches);
+        check_iterable_type(if_true, expected, context, bound, mismatches);
+        check_iterable_type(if_false, expected, context, bound, mismatches);
+    }
+}
+
+/// Records a mismatch if the variable `name` in `context` can't be used as `expected`
+fn check_variable_type(
+    name: &str,
+    expected: VariableTy,
+    context: &Context<'_>,
+    bound: &[&str],
+    mismatches: &mut Vec<MinilateError>,
+) {
+    let Some(variable) = context.get(name).filter(|_| !bound.contains(&name)) else {
+        return;
+    };
+    let mismatched = match expected {
+        // Numbers are compared by their data, whatever the variable's type
+        VariableTy::Number => variable
+            .data()
+            .is_some_and(|data| data.trim().parse::<f64>().is_err()),
+        // A structured list can be looped over wherever an iterable can
+        VariableTy::Iterable => !matches!(variable.ty(), VariableTy::Iterable | VariableTy::List),
+        VariableTy::Map | VariableTy::List => variable.ty() != expected,
+        VariableTy::String | VariableTy::Boolean => false,
+    };
+
+    let mismatch = MinilateError::TypeMismatch {
+        variable_name: context.resolve_alias(name).to_string(),
+        template_name: None,
+        expected,
+        found: variable.ty(),
+    };
+    if mismatched && !mismatches.contains(&mismatch) {
+        mismatches.push(mismatch);
+    }
+}
+
+/// Renders `node` with the engine's renderer for it, if it is a variable with a
+/// renderer registered for its name or type
+fn render_with_renderer<E: MinilateInterface>(
+    node: &AstNode<'_>,
+    context: &Context<'_>,
+    engine: &E,
+) -> Option<String> {
+    let AstNode::Variable { name } = node else {
+        return None;
+    };
+    let variable = lookup_variable(name, context, engine.dotted_fallback())?;
+    engine
+        .renderer(context.resolve_alias(name), variable.ty())
+        .map(|renderer| renderer(variable))
+}
+
+/// Returns true if `node` applies the built-in `safe` filter at any point, so its output
+/// isn't escaped
+fn is_marked_safe<E: MinilateInterface>(node: &AstNode<'_>, engine: &E) -> bool {
+    let mut node = node;
+    while let AstNode::Filter { name, inner, .. } = node {
+        if *name == "safe" && engine.filter(name).is_none() {
+            return true;
+        }
+        node = inner;
+    }
+    false
+}
+
+/// Returns true if `node` is a variable of type [`VariableTy::Boolean`] in `context`
+fn is_boolean_variable(node: &AstNode<'_>, context: &Context<'_>) -> bool {
+    matches!(node, AstNode::Variable { name } if context
+        .get(name)
+        .is_some_and(|variable| variable.ty() == VariableTy::Boolean))
+}
+
+/// Counts references to each variable, see [`Template::variable_usage`]
+#[derive(Default)]
+struct VariableUsage<'a> {
+    counts: HashMap<&'a str, usize>,
+}
+
+impl<'a> Visitor<'a> for VariableUsage<'a> {
+    fn visit_variable(&mut self, name: &'a str) {
+        let count = self.counts.entry(name).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    fn visit_alias(&mut self, _name: &'a str, target: &'a str) {
+        self.visit_variable(target);
+    }
+}
+
+/// Collects string literals, see [`Template::string_literals`]
+#[derive(Default)]
+struct StringLiterals<'a> {
+    values: Vec<&'a str>,
+}
+
+impl<'a> Visitor<'a> for StringLiterals<'a> {
+    fn visit_string_literal(&mut self, value: &'a str) {
+        self.values.push(value);
+    }
+
+    fn visit_template_include(&mut self, template_name: &'a str) {
+        self.values.push(template_name);
+    }
+
+    fn visit_dynamic_include(&mut self, name: &[AstNode<'a>]) {
+        // Text with escapes is owned by the node rather than borrowed from the template
+        for part in name {
+            if let AstNode::Constant {
+                data: Cow::Borrowed(data),
+                ..
+            } = part
+            {
+                self.values.push(data);
+            }
+        }
+    }
+}
+
+/// Collects variables from an `if` condition, marking directly referenced variables as Boolean
+fn collect_condition_variables<'a>(
+    condition: &AstNode<'a>,
+    uses: &mut Vec<VariableUse<'a>>,
+    in_loop: bool,
+) {
+    match condition {
+        AstNode::Variable { name } => {
+            uses.push(VariableUse {
+                name,
+                ty: VariableTy::Boolean,
+                indexable: false,
+                in_loop,
+            });
+        }
+        AstNode::Not { condition } => {
+            // For NOT operator, look at its variable and mark as Boolean
+            if let AstNode::Variable { name } = condition.as_ref() {
+                uses.push(VariableUse {
+                    name,
+                    ty: VariableTy::Boolean,
+                    indexable: false,
+                    in_loop,
+                });
+            } else {
+                collect_variables_from_node(condition, uses, in_loop);
+            }
+        }
+        AstNode::And { left, right }
+        | AstNode::Or { left, right }
+        | AstNode::Xor { left, right } => {
+            // For AND/OR operators, check both sides for variables
+            if let AstNode::Variable { name } = left.as_ref() {
+                uses.push(VariableUse {
+                    name,
+                    ty: VariableTy::Boolean,
+                    indexable: false,
+                    in_loop,
+                });
+            } else {
+                collect_variables_from_node(left, uses, in_loop);
+            }
+
+            if let AstNode::Variable { name } = right.as_ref() {
+                uses.push(VariableUse {
+                    name,
+                    ty: VariableTy::Boolean,
+                    indexable: false,
+                    in_loop,
+                });
+            } else {
+                collect_variables_from_node(right, uses, in_loop);
+            }
+        }
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::Filter { .. }
+        | AstNode::For { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::If { .. }
+        | AstNode::Compare { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. } => {
+            collect_variables_from_node(condition, uses, in_loop);
+        }
+    }
+}
+
+/// Collects the variables making up an operand of a condition, marking them as `ty`
+fn collect_operand_variables<'a>(
+    operand: &AstNode<'a>,
+    ty: VariableTy,
+    uses: &mut Vec<VariableUse<'a>>,
+    in_loop: bool,
+) {
+    if let AstNode::Coalesce { value, fallback } = operand {
+        collect_operand_variables(value, ty, uses, in_loop);
+        collect_operand_variables(fallback, ty, uses, in_loop);
+    } else if let AstNode::Variable { name } = operand {
+        uses.push(VariableUse {
+            name,
+            ty,
+            indexable: false,
+            in_loop,
+        });
+    }
+}
+
+/// Collects variables from a for-loop iterable, marking the candidate iterables as `ty`
+fn collect_iterable_variables<'a>(
+    iterable: &AstNode<'a>,
+    ty: VariableTy,
+    uses: &mut Vec<VariableUse<'a>>,
+    in_loop: bool,
+) {
+    match iterable {
+        AstNode::Variable { name } => {
+            uses.push(VariableUse {
+                name,
+                ty,
+                indexable: false,
+                in_loop,
+            });
+        }
+        AstNode::Ternary {
+            condition,
+            if_true,
+            if_false,
+        } => {
+            collect_condition_variables(condition, uses, in_loop);
+            collect_iterable_variables(if_true, ty, uses, in_loop);
+            collect_iterable_variables(if_false, ty, uses, in_loop);
+        }
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::Filter { .. }
+        | AstNode::For { .. }
+        | AstNode::If { .. }
+        | AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. } => {
+            collect_variables_from_node(iterable, uses, in_loop);
+        }
     }
 }
 
@@ -419,11 +1813,11 @@ fn collect_variables_from_node<'a>(
 fn find_template_inclusions<'a>(
     node: &'a AstNode<'a>,
     direct_inclusions: &mut Vec<&'a str>,
-    conditional_inclusions: &mut Vec<(&'a str, bool)>,
-    for_loop_inclusions: &mut Vec<(&'a str, &'a str)>,
+    conditional_inclusions: &mut Vec<(&'a str, Vec<InclusionCondition<'a>>)>,
+    for_loop_inclusions: &mut Vec<(&'a str, &'a AstNode<'a>)>,
     context: &Context<'_>,
-    in_condition: bool,
-    in_for_loop: Option<&'a str>, // Track if we're in a for loop and the iterable name
+    conditions: &[InclusionCondition<'a>], // The conditions governing the current branch
+    in_for_loop: Option<&'a AstNode<'a>>,  // Track if we're in a for loop and the iterable
 ) {
     match node {
         AstNode::Root(children) => {
@@ -434,15 +1828,17 @@ fn find_template_inclusions<'a>(
                     conditional_inclusions,
                     for_loop_inclusions,
                     context,
-                    in_condition,
+                    conditions,
                     in_for_loop,
                 );
             }
         }
         AstNode::For {
             variable: _,
+            value_variable: _,
             iterable,
             body,
+            empty_branch,
         } => {
             // Process children with for loop context
             for child in body {
@@ -452,8 +1848,21 @@ fn find_template_inclusions<'a>(
                     conditional_inclusions,
                     for_loop_inclusions,
                     context,
-                    in_condition,
-                    Some(iterable),
+                    conditions,
+                    Some(iterable.as_ref()),
+                );
+            }
+
+            // The empty branch only renders when the iterable has no items, so it is conditional
+            if let Some(empty_node) = empty_branch {
+                find_template_inclusions(
+                    empty_node,
+                    direct_inclusions,
+                    conditional_inclusions,
+                    for_loop_inclusions,
+                    context,
+                    &with_condition(conditions, InclusionCondition::Empty(iterable)),
+                    in_for_loop,
                 );
             }
         }
@@ -466,6 +1875,7 @@ fn find_template_inclusions<'a>(
             let _condition_result = evaluate_condition(condition, context).unwrap_or(false);
 
             // Check the if body - these are conditional inclusions
+            let body_conditions = with_condition(conditions, InclusionCondition::When(condition));
             for child in body {
                 find_template_inclusions(
                     child,
@@ -473,7 +1883,7 @@ fn find_template_inclusions<'a>(
                     conditional_inclusions,
                     for_loop_inclusions,
                     context,
-                    true,
+                    &body_conditions,
                     in_for_loop,
                 );
             }
@@ -486,12 +1896,12 @@ fn find_template_inclusions<'a>(
                     conditional_inclusions,
                     for_loop_inclusions,
                     context,
-                    true,
+                    &with_condition(conditions, InclusionCondition::Unless(condition)),
                     in_for_loop,
                 );
             }
         }
-        AstNode::TemplateInclude { template_name } => {
+        AstNode::TemplateInclude { template_name, .. } => {
             // First check if this is in a for loop
             if let Some(iterable) = in_for_loop {
                 if !for_loop_inclusions
@@ -504,12 +1914,12 @@ fn find_template_inclusions<'a>(
             }
 
             // If this is in a conditional branch, add to conditional inclusions
-            if in_condition {
+            if !conditions.is_empty() {
                 if !conditional_inclusions
                     .iter()
                     .any(|(name, _)| *name == *template_name)
                 {
-                    conditional_inclusions.push((template_name, true)); // We'll use true by default since we don't have condition info here
+                    conditional_inclusions.push((template_name, conditions.to_vec()));
                 }
             } else {
                 // Otherwise add to direct inclusions if not already included
@@ -525,15 +1935,53 @@ fn find_template_inclusions<'a>(
                 }
             }
         }
+        AstNode::Repeat { body, .. } | AstNode::DebugOnly { body } => {
+            for child in body {
+                find_template_inclusions(
+                    child,
+                    direct_inclusions,
+                    conditional_inclusions,
+                    for_loop_inclusions,
+                    context,
+                    conditions,
+                    in_for_loop,
+                );
+            }
+        }
         // Other node types don't contain template inclusions
         AstNode::Constant { .. }
         | AstNode::Variable { .. }
+        | AstNode::Filter { .. }
+        | AstNode::Ternary { .. }
         | AstNode::Not { .. }
         | AstNode::And { .. }
-        | AstNode::Or { .. } => {}
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue => {}
+        // Dynamic inclusions can't be known without rendering
+        AstNode::DynamicInclude { .. } => {}
     }
 }
 
+/// Returns `conditions` with `condition` appended, for descending into a conditional branch.
+fn with_condition<'a>(
+    conditions: &[InclusionCondition<'a>],
+    condition: InclusionCondition<'a>,
+) -> Vec<InclusionCondition<'a>> {
+    let mut conditions = conditions.to_vec();
+    conditions.push(condition);
+    conditions
+}
+
 /// Internal function to render an AST node to a String
 ///
 /// This function is the core of the rendering process. It recursively traverses
@@ -542,70 +1990,150 @@ fn find_template_inclusions<'a>(
 /// - Variables are looked up in the context and their values appended
 /// - Control structures (if/for) are evaluated and their contents rendered as appropriate
 /// - Template inclusions reference other templates in the engine
-fn render_node<'a, E>(
+///
+/// If `source` is provided, each variable's output is wrapped in markers giving the
+/// position of the variable within `source` (see [`Template::render_annotated`]).
+///
+/// `state` tracks the templates being rendered, so that an include cycle is reported
+/// rather than recursing forever, and whether the render has been cancelled.
+///
+/// Returns how rendering continues after the node, which is only changed by a
+/// `{{% break %}}` or `{{% continue %}}` for the innermost enclosing for loop or repeat
+/// block to act on.
+fn render_node<'a, E, W>(
     node: &AstNode<'a>,
     context: &Context<'a>,
-    output: &mut String,
+    output: &mut W,
     engine: Option<&E>,
-) -> MinilateResult<()>
+    source: Option<&str>,
+    state: &mut RenderState<'_>,
+) -> MinilateResult<Flow>
 where
     E: MinilateInterface,
+    W: Write + ?Sized,
 {
+    state.check_cancelled()?;
+
     match node {
         AstNode::Root(children) => {
-            for child in children {
-                render_node(child, context, output, engine)?;
-            }
+            return render_block(children, context, output, engine, source, state);
         }
-        AstNode::Constant { data } => {
-            output.push_str(data);
+        AstNode::Constant { data, trim } => {
+            output.write_str(trim.apply(data))?;
         }
-        AstNode::Variable { name } => {
-            // Get the variable from context
-            match context.get(name) {
-                Some(var) => {
-                    match var.data() {
-                        Some(data) => {
-                            // Check if the data string is empty (for testing missing data)
-                            if data.is_empty() {
-                                return Err(MinilateError::MissingVariableData {
-                                    variable_name: name.to_string(),
-                                });
-                            }
-                            output.push_str(data);
-                        }
-                        None => {
-                            return Err(MinilateError::MissingVariableData {
-                                variable_name: name.to_string(),
-                            });
+        AstNode::Variable { .. } | AstNode::Filter { .. } => {
+            let value = match render_value(node, context, engine) {
+                Ok(value) => value,
+                Err(error) => {
+                    // A missing variable may be rendered as a placeholder instead
+                    if let MinilateError::MissingVariable { variable_name, .. }
+                    | MinilateError::MissingVariableData { variable_name, .. } = &error
+                    {
+                        let policy =
+                            engine.map_or(&MissingPolicy::Error, MinilateInterface::missing_policy);
+                        if let Some(placeholder) = policy.placeholder(variable_name) {
+                            output.write_str(&placeholder)?;
+                            return Ok(Flow::Next);
                         }
                     }
+                    return Err(error);
                 }
-                None => {
-                    return Err(MinilateError::MissingVariable {
-                        variable_name: name.to_string(),
-                    });
+            };
+
+            // A renderer registered for the variable replaces its data entirely
+            let rendered = engine.and_then(|engine| render_with_renderer(node, context, engine));
+
+            // Otherwise humanize boolean variables with the engine's labels, if any are configured
+            let value = match (rendered, engine.and_then(MinilateInterface::bool_labels)) {
+                (Some(rendered), _) => Cow::Owned(rendered),
+                (None, Some((true_label, false_label))) if is_boolean_variable(node, context) => {
+                    Cow::Borrowed(if evaluate_condition(node, context)? {
+                        true_label
+                    } else {
+                        false_label
+                    })
+                }
+                (None, Some(_) | None) => value,
+            };
+
+            // Escape the final value, after any filters have been applied, unless it has
+            // been marked as trusted with the `safe` filter
+            let escaper = match engine {
+                Some(engine) if !is_marked_safe(node, engine) => engine.escaper(),
+                Some(_) | None => Escaper::None,
+            };
+            let value = escaper.escape(&value);
+
+            match source
+                .zip(value_name(node))
+                .and_then(|(source, name)| source_position(source, name))
+            {
+                Some((line, column)) => {
+                    write!(
+                        output,
+                        "<!--{line}:{column}-->{value}<!--/{line}:{column}-->"
+                    )?;
                 }
+                None => output.write_str(&value)?,
             }
         }
         AstNode::For {
             iterable,
             variable,
+            value_variable,
             body,
+            empty_branch,
         } => {
+            // Work out which variable we are iterating over
+            let iterable = resolve_iterable(iterable, context)?;
+
             // Get the iterable from context
             let iterable_var =
                 context
                     .get(iterable)
                     .ok_or_else(|| MinilateError::MissingVariable {
                         variable_name: iterable.to_string(),
+                        template_name: None,
                     })?;
 
-            // Make sure it's an iterable type
-            if iterable_var.ty() != VariableTy::Iterable {
+            // Structured lists bind each item's fields under the loop variable instead
+            if iterable_var.ty() == VariableTy::List && value_variable.is_none() {
+                let items = iterable_var.items();
+                if let Some(empty_node) = empty_branch.as_deref().filter(|_| items.is_empty()) {
+                    return render_node(empty_node, context, output, engine, source, state);
+                }
+
+                for (index, item) in items.iter().enumerate() {
+                    state.check_cancelled()?;
+
+                    // Each item gets a fresh scope, so fields missing from this item don't
+                    // keep the previous item's values
+                    let mut loop_context = Context::scoped(context);
+                    for (field, value) in item.iter() {
+                        loop_context.insert(format!("{variable}.{field}"), value.clone());
+                    }
+                    insert_loop_metadata(&mut loop_context, index, items.len());
+
+                    if render_block(body, &loop_context, output, engine, source, state)?
+                        == Flow::Break
+                    {
+                        break;
+                    }
+                }
+                return Ok(Flow::Next);
+            }
+
+            // Make sure it's an iterable type, or a map when iterating over keys and values
+            let expected = if value_variable.is_some() {
+                VariableTy::Map
+            } else {
+                VariableTy::Iterable
+            };
+            if iterable_var.ty() != expected {
                 return Err(MinilateError::TypeMismatch {
                     variable_name: iterable.to_string(),
-                    expected: VariableTy::Iterable,
+                    template_name: None,
+                    expected,
                     found: iterable_var.ty(),
                 });
             }
@@ -616,22 +2144,50 @@ where
                     .data()
                     .ok_or_else(|| MinilateError::MissingVariableData {
                         variable_name: iterable.to_string(),
+                        template_name: None,
                     })?;
 
+            // Render the empty branch instead of the body if no item has any content
+            if let Some(empty_node) = empty_branch {
+                if iterable_data
+                    .split(context.separator())
+                    .all(|item| item.trim().is_empty())
+                {
+                    return render_node(empty_node, context, output, engine, source, state);
+                }
+            }
+
             // Skip rendering if iterable is empty
             if iterable_data.is_empty() {
-                return Ok(());
+                return Ok(Flow::Next);
             }
 
+            // The loop variable is bound in a scope of its own on top of the context, which
+            // is discarded once the loop ends so neither it nor any shadowed outer value
+            // leaks past `endfor`
+            let mut loop_context = Context::scoped(context);
+
             // Split by commas (simple implementation for now)
-            for item in iterable_data.split(',') {
-                // Create a temporary context with the loop variable
-                let mut loop_context = context.clone();
-                loop_context.insert(variable, VariableTy::String.with_data(item.trim()));
-
-                // Render each child node with the updated context
-                for child in body {
-                    render_node(child, &loop_context, output, engine)?;
+            let length = iterable_data.split(context.separator()).count();
+            for (index, item) in iterable_data.split(context.separator()).enumerate() {
+                // Checked per iteration too, as a loop with an empty body renders no nodes
+                state.check_cancelled()?;
+
+                // Rebind the loop variables and metadata for this iteration
+                if let Some(value_variable) = value_variable {
+                    let (key, value) = map_entry(item);
+                    loop_context.insert(variable, VariableTy::String.with_data(key));
+                    loop_context.insert(value_variable, VariableTy::String.with_data(value));
+                } else {
+                    loop_context.insert(variable, VariableTy::String.with_data(item.trim()));
+                }
+                insert_loop_metadata(&mut loop_context, index, length);
+
+                // Render each child node with the updated context, where `continue` only
+                // ends this iteration early
+                if render_block(body, &loop_context, output, engine, source, state)? == Flow::Break
+                {
+                    break;
                 }
             }
         }
@@ -641,67 +2197,668 @@ where
             else_branch,
         } => {
             if evaluate_condition(condition, context)? {
-                for child in body {
-                    render_node(child, context, output, engine)?;
-                }
+                return render_block(body, context, output, engine, source, state);
             } else if let Some(else_node) = else_branch {
-                render_node(else_node, context, output, engine)?;
+                return render_node(else_node, context, output, engine, source, state);
             }
         }
         // Else nodes have been replaced with Root nodes
         // Template inclusion handling
-        AstNode::TemplateInclude { template_name } => {
-            if let Some(engine) = engine {
-                // Check if we're in a for loop
-                let in_for_loop = context.contains("members")
-                    && context
-                        .get("members")
-                        .and_then(|v| v.data())
-                        .is_some_and(|d| !d.is_empty());
-
-                // For the group_greeting template, we need to make sure the name variable exists
-                if in_for_loop && context.get("name").is_none() {
-                    // If rendering the greeting template inside a for loop, provide a name
-                    let mut new_context = context.clone();
-                    if !new_context.contains("name") {
-                        new_context.insert("name", VariableTy::String.with_data("Team Member"));
-                    }
-                    // Render the included template with the modified context
-                    let rendered = engine.render(template_name, Some(&new_context))?;
-                    output.push_str(&rendered);
-                } else {
-                    // Render the included template with the current context
-                    let rendered = engine.render(template_name, Some(context))?;
-                    output.push_str(&rendered);
-                }
+        AstNode::TemplateInclude {
+            template_name,
+            bindings,
+        } => {
+            if bindings.is_empty() {
+                render_include(template_name, context, output, engine, state)?;
             } else {
-                return Err(MinilateError::RenderError {
-                    message: "Cannot include template: no engine provided".to_string(),
-                });
+                // The included template only sees the variables passed to it
+                let mut include_context = context.isolated();
+                for (name, value) in bindings {
+                    include_context.insert(name, assigned_value(value, context, engine)?);
+                }
+                render_include(template_name, &include_context, output, engine, state)?;
+            }
+        }
+        AstNode::DynamicInclude { name } => {
+            let template_name = resolve_include_name(name, context, engine)?;
+            render_include(&template_name, context, output, engine, state)?;
+        }
+        AstNode::DebugDump => {
+            // Only dump the context when debugging has been enabled on the engine
+            if engine.is_some_and(MinilateInterface::debug) {
+                for (name, variable) in context.iter() {
+                    match variable.data() {
+                        Some(data) => writeln!(output, "{name}: {:?} = {data:?}", variable.ty()),
+                        None => writeln!(output, "{name}: {:?}", variable.ty()),
+                    }?;
+                }
+            }
+        }
+        AstNode::Repeat { count, body } => {
+            let mut loop_context = Context::scoped(context);
+            for index in 0..*count {
+                state.check_cancelled()?;
+                insert_loop_metadata(&mut loop_context, index, *count);
+                if render_block(body, &loop_context, output, engine, source, state)? == Flow::Break
+                {
+                    break;
+                }
+            }
+        }
+        AstNode::DebugOnly { body } => {
+            if engine.is_some_and(MinilateInterface::debug) {
+                return render_block(body, context, output, engine, source, state);
             }
         }
         // These nodes should only appear in condition expressions
-        AstNode::Not { .. } | AstNode::And { .. } | AstNode::Or { .. } => {
+        AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. } => {
             return Err(MinilateError::RenderError {
                 message: "Conditional operator node found outside of condition context".to_string(),
             });
         }
+        AstNode::Ternary { .. } => {
+            return Err(MinilateError::RenderError {
+                message: "Ternary node found outside of a for-loop iterable".to_string(),
+            });
+        }
+        AstNode::Set { .. } | AstNode::Alias { .. } => {
+            // Assignments and aliases only affect the nodes after them, so are handled by
+            // `render_block`
+        }
+        AstNode::Break => return Ok(Flow::Break),
+        AstNode::Continue => return Ok(Flow::Continue),
+    }
+
+    Ok(Flow::Next)
+}
+
+/// How rendering continues after a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    /// On to the next node
+    Next,
+    /// Out of the innermost for loop or repeat block, skipping the rest of its body
+    Break,
+    /// On to the next iteration of the innermost for loop or repeat block, skipping the
+    /// rest of its body
+    Continue,
+}
+
+/// Renders the nodes of a block in order
+///
+/// Each `{{% set %}}` binds its variable in a new scope for the nodes after it, which is
+/// discarded at the end of the block so the variable doesn't leak out of it. Each
+/// `{{% alias %}}` is scoped the same way.
+fn render_block<'a, E, W>(
+    children: &[AstNode<'a>],
+    context: &Context<'a>,
+    output: &mut W,
+    engine: Option<&E>,
+    source: Option<&str>,
+    state: &mut RenderState<'_>,
+) -> MinilateResult<Flow>
+where
+    E: MinilateInterface,
+    W: Write + ?Sized,
+{
+    for (index, child) in children.iter().enumerate() {
+        if let AstNode::Set { name, value } = child {
+            let mut scope = Context::scoped(context);
+            scope.insert(*name, assigned_value(value, context, engine)?);
+            let rest = children.get(index.saturating_add(1)..).unwrap_or_default();
+            return render_block(rest, &scope, output, engine, source, state);
+        }
+        if let AstNode::Alias { name, target } = child {
+            let mut scope = Context::scoped(context);
+            scope.insert_alias(name, target);
+            let rest = children.get(index.saturating_add(1)..).unwrap_or_default();
+            return render_block(rest, &scope, output, engine, source, state);
+        }
+
+        // Loop control skips the rest of the block, up to the loop it's for
+        let flow = render_node(child, context, output, engine, source, state)?;
+        if flow != Flow::Next {
+            return Ok(flow);
+        }
+    }
+    Ok(Flow::Next)
+}
+
+/// Evaluates the value of a `{{% set %}}` or of a binding passed to an include
+///
+/// A plain variable is copied along with its type, so e.g. a boolean is still tested as
+/// one, as are number and boolean literals, while a filtered value or a string is
+/// assigned as a [`VariableTy::String`].
+pub fn assigned_value<'c, E: MinilateInterface>(
+    value: &AstNode<'_>,
+    context: &'c Context<'_>,
+    engine: Option<&E>,
+) -> MinilateResult<Variable<'c>> {
+    if let AstNode::Variable { name } = value {
+        let dotted_fallback = engine.is_some_and(MinilateInterface::dotted_fallback);
+        if let Some(variable) = lookup_variable(name, context, dotted_fallback) {
+            return Ok(variable.clone());
+        }
+    }
+    if let AstNode::NumberLiteral { value } = value {
+        return Ok(VariableTy::Number.with_data(value.to_string()));
+    }
+    if let AstNode::BoolLiteral { value } = value {
+        return Ok(VariableTy::Boolean.with_data(bool_data(*value)));
+    }
+
+    let error = match render_value(value, context, engine) {
+        Ok(data) => return Ok(VariableTy::String.with_data(data.into_owned())),
+        Err(error) => error,
+    };
+
+    // A missing variable may be assigned a placeholder instead, as it would be rendered
+    if let MinilateError::MissingVariable { variable_name, .. }
+    | MinilateError::MissingVariableData { variable_name, .. } = &error
+    {
+        let policy = engine.map_or(&MissingPolicy::Error, MinilateInterface::missing_policy);
+        if let Some(placeholder) = policy.placeholder(variable_name) {
+            return Ok(VariableTy::String.with_data(placeholder));
+        }
+    }
+    Err(error)
+}
+
+/// Splits one `key=value` entry of a [`VariableTy::Map`] into its trimmed key and value
+///
+/// An entry without `=` is a key with an empty value.
+pub fn map_entry(entry: &str) -> (&str, &str) {
+    let (key, value) = entry.split_once('=').unwrap_or((entry, ""));
+    (key.trim(), value.trim())
+}
+
+/// Binds the loop metadata variables for the iteration at 0-based `index` of a loop with
+/// `length` iterations, as seen by the body of an [`AstNode::For`] or [`AstNode::Repeat`]
+///
+/// - `loop.index`: the 1-based iteration number
+/// - `loop.index0`: the 0-based iteration number
+/// - `loop.first`/`loop.last`: whether this is the first/last iteration
+/// - `loop.length`: the total number of iterations
+pub fn insert_loop_metadata(loop_context: &mut Context<'_>, index: usize, length: usize) {
+    let number = index.saturating_add(1);
+    loop_context
+        .insert(
+            "loop.index",
+            VariableTy::Number.with_data(number.to_string()),
+        )
+        .insert(
+            "loop.index0",
+            VariableTy::Number.with_data(index.to_string()),
+        )
+        .insert(
+            "loop.first",
+            VariableTy::Boolean.with_data(bool_data(index == 0)),
+        )
+        .insert(
+            "loop.last",
+            VariableTy::Boolean.with_data(bool_data(number == length)),
+        )
+        .insert(
+            "loop.length",
+            VariableTy::Number.with_data(length.to_string()),
+        );
+}
+
+/// Returns the data of a [`VariableTy::Boolean`] variable with the given value
+const fn bool_data(value: bool) -> &'static str {
+    if value { "true" } else { "false" }
+}
+
+/// Renders the template called `template_name` from the engine into `output`
+fn render_include<'a, E, W>(
+    template_name: &str,
+    context: &Context<'a>,
+    output: &mut W,
+    engine: Option<&E>,
+    state: &mut RenderState<'_>,
+) -> MinilateResult<()>
+where
+    E: MinilateInterface,
+    W: Write + ?Sized,
+{
+    let Some(engine) = engine else {
+        return Err(MinilateError::RenderError {
+            message: "Cannot include template: no engine provided".to_string(),
+        });
+    };
+
+    let template =
+        engine
+            .get_template(template_name)
+            .ok_or_else(|| MinilateError::MissingTemplate {
+                template_name: template_name.to_string(),
+            })?;
+    // Track the name the template is registered under, as includes may spell it differently
+    let template_name = template.name.as_deref().unwrap_or(template_name);
+
+    if state.active.iter().any(|name| name == template_name) {
+        let mut chain = state.active.clone();
+        chain.push(template_name.to_string());
+        return Err(MinilateError::RecursionLimit { chain });
     }
 
+    // Render the included template with the current context, which includes any
+    // variables bound by enclosing for loops, but not the aliases in scope
+    let unaliased;
+    let context = if context.has_aliases() {
+        unaliased = Context::without_aliases(context);
+        &unaliased
+    } else {
+        context
+    };
+    state.active.push(template_name.to_string());
+    if !state.included.iter().any(|name| name == template_name) {
+        state.included.push(template_name.to_string());
+    }
+    let result = render_node(&template.ast, context, output, Some(engine), None, state);
+    state.active.pop();
+    result.map_err(|error| error.in_template(Some(template_name)))?;
     Ok(())
 }
 
+/// The state of a render that isn't tied to a single node: the includes followed so far,
+/// and whether the render has been cancelled
+struct RenderState<'r> {
+    /// The names of the templates currently being rendered, outermost first
+    active: Vec<String>,
+    /// Every template included so far, in the order each was first included
+    included: Vec<String>,
+    /// Abandons the render once set, see [`Template::render_with_cancel`]
+    cancel: Option<&'r AtomicBool>,
+}
+
+impl RenderState<'_> {
+    /// Fails with `MinilateError::Cancelled` if the render's cancel flag has been set
+    fn check_cancelled(&self) -> MinilateResult<()> {
+        if self
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(MinilateError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Builds the template name of a `DynamicInclude` from its parts
+///
+/// Variables are interpolated unescaped, since the result names a template rather than
+/// being output.
+pub fn resolve_include_name<E: MinilateInterface>(
+    parts: &[AstNode<'_>],
+    context: &Context<'_>,
+    engine: Option<&E>,
+) -> MinilateResult<String> {
+    let mut template_name = String::new();
+    for part in parts {
+        match part {
+            AstNode::Constant { data, .. } => template_name.push_str(data),
+            AstNode::Variable { .. } | AstNode::Filter { .. } => {
+                template_name.push_str(&render_value(part, context, engine)?);
+            }
+            AstNode::Root(_)
+            | AstNode::For { .. }
+            | AstNode::Ternary { .. }
+            | AstNode::If { .. }
+            | AstNode::Not { .. }
+            | AstNode::And { .. }
+            | AstNode::Or { .. }
+            | AstNode::Xor { .. }
+            | AstNode::Compare { .. }
+            | AstNode::StringLiteral { .. }
+            | AstNode::Coalesce { .. }
+            | AstNode::BoolLiteral { .. }
+            | AstNode::NumberLiteral { .. }
+            | AstNode::TemplateInclude { .. }
+            | AstNode::DynamicInclude { .. }
+            | AstNode::DebugDump
+            | AstNode::Set { .. }
+            | AstNode::Alias { .. }
+            | AstNode::Break
+            | AstNode::Continue
+            | AstNode::DebugOnly { .. }
+            | AstNode::Repeat { .. } => {
+                return Err(MinilateError::RenderError {
+                    message: format!("Invalid include name part: {:?}", part),
+                });
+            }
+        }
+    }
+    Ok(template_name)
+}
+
+/// Returns the name of the variable at the heart of a value node, looking through any filters
+fn value_name<'n>(node: &AstNode<'n>) -> Option<&'n str> {
+    match node {
+        AstNode::Variable { name } => Some(name),
+        AstNode::Filter { inner, .. } => value_name(inner),
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::For { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::If { .. }
+        | AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. } => None,
+    }
+}
+
+/// Computes the 1-indexed line and column of `fragment` within `source`
+///
+/// Columns are counted in bytes, matching [`crate::error::ParseError`]. Returns `None`
+/// if `fragment` is not a slice of `source`.
+fn source_position(source: &str, fragment: &str) -> Option<(usize, usize)> {
+    let offset = fragment
+        .as_ptr()
+        .addr()
+        .checked_sub(source.as_ptr().addr())?;
+    let before = source.get(..offset)?;
+
+    let line = before.matches('\n').count().saturating_add(1);
+    let line_start = before.rfind('\n').map_or(0, |i| i.saturating_add(1));
+    let column = offset.saturating_sub(line_start).saturating_add(1);
+
+    Some((line, column))
+}
+
+/// Looks up a variable, falling back to shorter names for a missing dotted name if
+/// `dotted_fallback` is set (see [`crate::MinilateEngine::set_dotted_fallback`])
+fn lookup_variable<'c, 'v>(
+    name: &str,
+    context: &'c Context<'v>,
+    dotted_fallback: bool,
+) -> Option<&'c Variable<'v>> {
+    let name = context.resolve_alias(name);
+    if let Some(var) = context.get(name) {
+        return Some(var);
+    }
+    if !dotted_fallback {
+        return None;
+    }
+
+    // Try `a.b.c` as `a.c` and then `c`, dropping the segments nearest the last
+    let (mut parent, last) = name.rsplit_once('.')?;
+    loop {
+        match parent.rsplit_once('.') {
+            Some((shorter, _)) => {
+                parent = shorter;
+                if let Some(var) = context.get(format!("{parent}.{last}")) {
+                    return Some(var);
+                }
+            }
+            None => return context.get(last),
+        }
+    }
+}
+
+/// Splits a name such as `items.2` into the name of an iterable and the 0-based index of
+/// one of its elements
+pub fn indexed_name(name: &str) -> Option<(&str, usize)> {
+    let (base, index) = name.rsplit_once('.')?;
+    Some((base, index.parse().ok()?))
+}
+
+/// Resolves a name such as `items.2` to an element of the [`VariableTy::Iterable`] `items`,
+/// split exactly as a for loop would split it
+///
+/// Returns `None` if the name has no index or the iterable doesn't exist, and an error if
+/// the index is out of bounds.
+fn indexed_element<'c>(name: &str, context: &'c Context<'_>) -> MinilateResult<Option<&'c str>> {
+    let Some((base, index)) = indexed_name(name) else {
+        return Ok(None);
+    };
+    let Some(variable) = context
+        .get(base)
+        .filter(|variable| variable.ty() == VariableTy::Iterable)
+    else {
+        return Ok(None);
+    };
+    let data = variable
+        .data()
+        .ok_or_else(|| MinilateError::MissingVariableData {
+            variable_name: base.to_string(),
+            template_name: None,
+        })?;
+
+    let length = if data.is_empty() {
+        0
+    } else {
+        data.split(context.separator()).count()
+    };
+    data.split(context.separator())
+        .nth(index)
+        .filter(|_| index < length)
+        .map(|element| Some(element.trim()))
+        .ok_or_else(|| MinilateError::RenderError {
+            message: format!(
+                "Index {index} is out of bounds for '{base}', which has {length} items"
+            ),
+        })
+}
+
+/// Returns the output of the [`VariableTy::Number`] variable `name` with the given data,
+/// dropping a fractional part made only of zeros so that e.g. `5.0` renders as `5`, and
+/// the sign of a negative zero so that `-0.0` renders as `0`
+///
+/// Returns an error if the data isn't a finite number.
+fn number_output<'d>(name: &str, data: &'d str) -> MinilateResult<&'d str> {
+    let number = data.trim();
+    let value = match number.parse::<f64>() {
+        Ok(value) if value.is_finite() => value,
+        Ok(_) | Err(_) => {
+            return Err(MinilateError::RenderError {
+                message: format!("Variable '{name}' is a Number, but {data:?} isn't a number"),
+            });
+        }
+    };
+    let number = if value == 0.0 {
+        number.trim_start_matches('-')
+    } else {
+        number
+    };
+
+    // The data is otherwise output as given, so exponents and large integers keep their form
+    Ok(match number.split_once('.') {
+        Some((whole, fraction))
+            if whole.ends_with(|c: char| c.is_ascii_digit())
+                && fraction.bytes().all(|digit| digit == b'0') =>
+        {
+            whole
+        }
+        Some(_) | None => number,
+    })
+}
+
+/// Evaluates a variable or literal, possibly wrapped in filters, to its unescaped output
+fn render_value<'c, E: MinilateInterface>(
+    node: &AstNode<'_>,
+    context: &'c Context<'_>,
+    engine: Option<&E>,
+) -> MinilateResult<Cow<'c, str>> {
+    match node {
+        // Literals are only rendered as the value of a `{{% set %}}`
+        AstNode::StringLiteral { value } | AstNode::NumberLiteral { value } => {
+            Ok(Cow::Owned(value.to_string()))
+        }
+        AstNode::Variable { name } => {
+            let dotted_fallback = engine.is_some_and(MinilateInterface::dotted_fallback);
+            // Errors name the variable an alias stands for, which the context is missing
+            let name = context.resolve_alias(name);
+            // Get the variable from context
+            let Some(var) = lookup_variable(name, context, dotted_fallback) else {
+                // `items.0` takes the first element of an iterable `items`
+                return match indexed_element(name, context)? {
+                    Some(element) => Ok(Cow::Borrowed(element)),
+                    None => Err(MinilateError::MissingVariable {
+                        variable_name: name.to_string(),
+                        template_name: None,
+                    }),
+                };
+            };
+
+            // Empty data is a legitimate value, which renders as nothing
+            let data = var
+                .data()
+                .ok_or_else(|| MinilateError::MissingVariableData {
+                    variable_name: name.to_string(),
+                    template_name: None,
+                })?;
+            if var.ty() == VariableTy::Number {
+                return number_output(name, data).map(Cow::Borrowed);
+            }
+            Ok(Cow::Borrowed(data))
+        }
+        AstNode::Filter {
+            name,
+            argument,
+            inner,
+        } => {
+            // Filters registered with the engine take precedence over the built-ins
+            let registered = engine.and_then(|engine| engine.filter(name));
+
+            // `default` stands in its argument for a missing variable, so must see the error
+            if *name == "default" && registered.is_none() {
+                return match render_value(inner, context, engine) {
+                    Err(
+                        MinilateError::MissingVariable { .. }
+                        | MinilateError::MissingVariableData { .. },
+                    ) => Ok(Cow::Owned(argument.unwrap_or_default().to_string())),
+                    result => result,
+                };
+            }
+
+            let value = render_value(inner, context, engine)?;
+            let unexpected_argument = || MinilateError::RenderError {
+                message: format!("Filter '{}' doesn't take an argument", name),
+            };
+
+            if let Some(filter) = registered {
+                return match argument {
+                    Some(_) => Err(unexpected_argument()),
+                    None => Ok(Cow::Owned(filter(&value))),
+                };
+            }
+
+            match (*name, argument) {
+                ("trim", None) => Ok(match value {
+                    Cow::Borrowed(value) => Cow::Borrowed(value.trim_ascii()),
+                    Cow::Owned(value) => Cow::Owned(value.trim_ascii().to_string()),
+                }),
+                ("upper", None) => Ok(Cow::Owned(value.to_uppercase())),
+                ("lower", None) => Ok(Cow::Owned(value.to_lowercase())),
+                // Only affects escaping, see `is_marked_safe`
+                ("safe", None) => Ok(value),
+                ("trim" | "upper" | "lower" | "safe", Some(_)) => Err(unexpected_argument()),
+                #[cfg(feature = "unicode")]
+                ("truncate_graphemes", Some(length)) => truncate_graphemes(value, length),
+                #[cfg(feature = "unicode")]
+                ("truncate_graphemes", None) => Err(MinilateError::RenderError {
+                    message: "Filter 'truncate_graphemes' needs a length, e.g. \
+                              `truncate_graphemes:\"10\"`"
+                        .to_string(),
+                }),
+                _ => Err(MinilateError::RenderError {
+                    message: format!("Unknown filter '{}'", name),
+                }),
+            }
+        }
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::For { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::If { .. }
+        | AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. } => Err(MinilateError::RenderError {
+            message: format!("Invalid value node: {:?}", node),
+        }),
+    }
+}
+
+/// Shortens `value` to at most `length` grapheme clusters, so that characters built from
+/// several code points, such as emoji with skin tone modifiers, are never split.
+#[cfg(feature = "unicode")]
+fn truncate_graphemes<'v>(value: Cow<'v, str>, length: &str) -> MinilateResult<Cow<'v, str>> {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let length = length
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| MinilateError::RenderError {
+            message: format!(
+                "Filter 'truncate_graphemes' needs a whole number length, found '{}'",
+                length
+            ),
+        })?;
+
+    let Some((end, _)) = value.grapheme_indices(true).nth(length) else {
+        return Ok(value);
+    };
+    Ok(match value {
+        Cow::Borrowed(value) => Cow::Borrowed(value.get(..end).unwrap_or(value)),
+        Cow::Owned(mut value) => {
+            value.truncate(end);
+            Cow::Owned(value)
+        }
+    })
+}
+
 /// Evaluates a condition node to a boolean value
 ///
 /// This function handles the logic for evaluating conditional expressions in if statements:
 /// - Variables are looked up in the context and evaluated based on their type
 /// - Not/And/Or operators are evaluated with appropriate short-circuiting
+/// - Comparisons (`==`/`!=`) compare the string data of variables and literals
 /// - Missing variables or empty values typically evaluate to false
 ///
 /// The rules for boolean evaluation are:
 /// - Boolean variables: use their true/false value
 /// - String variables: true if non-empty
 /// - Iterable variables: true if non-empty
+/// - Number variables and literals: true if non-zero, while a Number variable whose data
+///   isn't a finite number fails to render, as it does when output
+/// - String literals: true if non-empty
 /// - Missing variables: false
 pub fn evaluate_condition<'a>(
     condition: &AstNode<'a>,
@@ -727,13 +2884,26 @@ pub fn evaluate_condition<'a>(
                                 None => Ok(false), // Missing data is treated as false
                             }
                         }
-                        VariableTy::Iterable => {
+                        VariableTy::Iterable | VariableTy::Map => {
                             // Iterable is true if it has at least one item
                             match var.data() {
                                 Some(data) => Ok(!data.is_empty()),
                                 None => Ok(false), // Missing data is treated as false
                             }
                         }
+                        VariableTy::Number => {
+                            // Number is true if it is non-zero, and fails as when output if
+                            // its data isn't a number
+                            match var.data() {
+                                Some(data) => {
+                                    let number = number_output(name, data)?;
+                                    Ok(number.parse::<f64>().is_ok_and(|n| n != 0.0))
+                                }
+                                None => Ok(false), // Missing data is treated as false
+                            }
+                        }
+                        // A list is true if it has at least one item
+                        VariableTy::List => Ok(!var.items().is_empty()),
                     }
                 }
                 None => Ok(false), // Missing variable is treated as false
@@ -759,15 +2929,314 @@ pub fn evaluate_condition<'a>(
             }
             evaluate_condition(right, context)
         }
-        // Template includes cannot be used in conditions
-        AstNode::TemplateInclude { .. } => Err(MinilateError::RenderError {
-            message: "Template includes cannot be used in conditions".to_string(),
+        AstNode::Xor { left, right } => {
+            // Both sides are always needed, so there's no short circuit
+            let left_result = evaluate_condition(left, context)?;
+            let right_result = evaluate_condition(right, context)?;
+            Ok(left_result != right_result)
+        }
+        // Literals are truthy like String and Number variables with the same data
+        AstNode::StringLiteral { value } => Ok(!value.is_empty()),
+        AstNode::NumberLiteral { value } => Ok(value.parse::<f64>().is_ok_and(|n| n != 0.0)),
+        AstNode::BoolLiteral { value } => Ok(*value),
+        AstNode::Coalesce { value, fallback } => {
+            evaluate_condition(coalesced(value, fallback, context), context)
+        }
+        AstNode::Compare { left, op, right } => Ok(match op {
+            CompareOp::Eq => {
+                comparison_operand(left, context)? == comparison_operand(right, context)?
+            }
+            CompareOp::Ne => {
+                comparison_operand(left, context)? != comparison_operand(right, context)?
+            }
+            CompareOp::Lt => numeric_operand(left, context)? < numeric_operand(right, context)?,
+            CompareOp::Gt => numeric_operand(left, context)? > numeric_operand(right, context)?,
+            CompareOp::Le => numeric_operand(left, context)? <= numeric_operand(right, context)?,
+            CompareOp::Ge => numeric_operand(left, context)? >= numeric_operand(right, context)?,
+            CompareOp::Contains => {
+                let needle = comparison_operand(right, context)?;
+                // Missing and empty iterables have no items, so contain nothing
+                comparison_operand(left, context)?
+                    .split(context.separator())
+                    .filter(|item| !item.is_empty())
+                    .any(|item| item.trim() == needle)
+            }
         }),
-        // These nodes shouldn't be conditions
-        AstNode::Root(_) | AstNode::Constant { .. } | AstNode::For { .. } | AstNode::If { .. } => {
+        // Template includes cannot be used in conditions
+        AstNode::TemplateInclude { .. } | AstNode::DynamicInclude { .. } => {
             Err(MinilateError::RenderError {
-                message: format!("Invalid condition node: {:?}", condition),
+                message: "Template includes cannot be used in conditions".to_string(),
+            })
+        }
+        // These nodes shouldn't be conditions
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::Filter { .. }
+        | AstNode::For { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. }
+        | AstNode::If { .. } => Err(MinilateError::RenderError {
+            message: format!("Invalid condition node: {:?}", condition),
+        }),
+    }
+}
+
+/// Evaluates a condition like [`evaluate_condition`], but returns `None` when the outcome
+/// depends on variables missing from `context`, rather than treating them as false
+///
+/// Missing variables that can't change the outcome are ignored, e.g. `a && b` is known to
+/// be false as soon as either `a` or `b` is false.
+pub fn evaluate_known_condition<'a>(
+    condition: &'a AstNode<'a>,
+    context: &Context<'a>,
+) -> Option<bool> {
+    match condition {
+        AstNode::Not { condition } => evaluate_known_condition(condition, context).map(|r| !r),
+        AstNode::And { left, right } => match (
+            evaluate_known_condition(left, context),
+            evaluate_known_condition(right, context),
+        ) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None,
+        },
+        AstNode::Or { left, right } => match (
+            evaluate_known_condition(left, context),
+            evaluate_known_condition(right, context),
+        ) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        },
+        AstNode::Xor { left, right } => Some(
+            evaluate_known_condition(left, context)? != evaluate_known_condition(right, context)?,
+        ),
+        AstNode::Variable { .. }
+        | AstNode::Compare { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::Filter { .. }
+        | AstNode::For { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::If { .. }
+        | AstNode::Repeat { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. } => {
+            let mut uses = Vec::new();
+            collect_condition_variables(condition, &mut uses, false);
+            let mut missing = Vec::new();
+            require_variables(&uses, &mut missing, context);
+            if missing.is_empty() {
+                evaluate_condition(condition, context).ok()
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Picks the operand an [`AstNode::Coalesce`] stands for: its `value`, unless that is a
+/// variable that is missing or has no data, in which case its `fallback`
+fn coalesced<'n, 'a>(
+    value: &'n AstNode<'a>,
+    fallback: &'n AstNode<'a>,
+    context: &Context<'_>,
+) -> &'n AstNode<'a> {
+    // A grouped fallback, as in `(a ?? b) ?? c`, is resolved first
+    if let AstNode::Coalesce {
+        value: inner,
+        fallback: inner_fallback,
+    } = value
+    {
+        return coalesced(coalesced(inner, inner_fallback, context), fallback, context);
+    }
+
+    let missing = matches!(
+        value,
+        AstNode::Variable { name } if context.get(name).and_then(Variable::data).is_none()
+    );
+    if missing { fallback } else { value }
+}
+
+/// Resolves an operand of an `==` or `!=` [`AstNode::Compare`] to the string data it is
+/// compared by
+///
+/// Like a missing variable evaluating to false, a missing variable (or one without
+/// data) compares as an empty string.
+fn comparison_operand<'n>(
+    operand: &'n AstNode<'_>,
+    context: &'n Context<'_>,
+) -> MinilateResult<&'n str> {
+    match operand {
+        AstNode::Variable { name } => Ok(context
+            .get(name)
+            .and_then(|variable| variable.data())
+            .unwrap_or_default()),
+        AstNode::StringLiteral { value } | AstNode::NumberLiteral { value } => Ok(value),
+        // Compares like the data of a Boolean variable with the same value
+        AstNode::BoolLiteral { value } => Ok(bool_data(*value)),
+        AstNode::Coalesce { value, fallback } => {
+            comparison_operand(coalesced(value, fallback, context), context)
+        }
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::Filter { .. }
+        | AstNode::For { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::If { .. }
+        | AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. } => Err(MinilateError::RenderError {
+            message: format!("Invalid comparison operand: {:?}", operand),
+        }),
+    }
+}
+
+/// Resolves an operand of a relational (`<`, `>`, `<=`, `>=`) [`AstNode::Compare`] to
+/// the number it is compared by
+///
+/// Unlike equality comparisons, a missing variable is an error rather than silently
+/// comparing as false, as is any operand whose data isn't a number.
+fn numeric_operand(operand: &AstNode<'_>, context: &Context<'_>) -> MinilateResult<f64> {
+    match operand {
+        AstNode::Coalesce { value, fallback } => {
+            numeric_operand(coalesced(value, fallback, context), context)
+        }
+        AstNode::Variable { name } => {
+            let variable = context
+                .get(name)
+                .ok_or_else(|| MinilateError::MissingVariable {
+                    variable_name: name.to_string(),
+                    template_name: None,
+                })?;
+            let data = variable
+                .data()
+                .ok_or_else(|| MinilateError::MissingVariableData {
+                    variable_name: name.to_string(),
+                    template_name: None,
+                })?;
+            data.trim()
+                .parse()
+                .map_err(|_| MinilateError::TypeMismatch {
+                    variable_name: name.to_string(),
+                    template_name: None,
+                    expected: VariableTy::Number,
+                    found: variable.ty(),
+                })
+        }
+        AstNode::StringLiteral { value } | AstNode::NumberLiteral { value } => {
+            value.parse().map_err(|_| MinilateError::TypeMismatch {
+                variable_name: format!("{value:?}"),
+                template_name: None,
+                expected: VariableTy::Number,
+                found: VariableTy::String,
             })
         }
+        AstNode::BoolLiteral { value } => Err(MinilateError::TypeMismatch {
+            variable_name: value.to_string(),
+            template_name: None,
+            expected: VariableTy::Number,
+            found: VariableTy::Boolean,
+        }),
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::Filter { .. }
+        | AstNode::For { .. }
+        | AstNode::Ternary { .. }
+        | AstNode::If { .. }
+        | AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. } => Err(MinilateError::RenderError {
+            message: format!("Invalid comparison operand: {:?}", operand),
+        }),
+    }
+}
+
+/// Resolves a for-loop iterable to the name of the variable it iterates over
+///
+/// Plain variables resolve to their own name. Ternaries evaluate their condition
+/// against the context and resolve whichever branch was selected. Whether the
+/// selected variable actually exists is left to the caller, so a missing iterable
+/// behaves exactly as it would without the ternary.
+pub fn resolve_iterable<'a>(
+    iterable: &AstNode<'a>,
+    context: &Context<'a>,
+) -> MinilateResult<&'a str> {
+    match iterable {
+        AstNode::Variable { name } => Ok(name),
+        AstNode::Ternary {
+            condition,
+            if_true,
+            if_false,
+        } => {
+            if evaluate_condition(condition, context)? {
+                resolve_iterable(if_true, context)
+            } else {
+                resolve_iterable(if_false, context)
+            }
+        }
+        AstNode::Root(_)
+        | AstNode::Constant { .. }
+        | AstNode::Filter { .. }
+        | AstNode::For { .. }
+        | AstNode::If { .. }
+        | AstNode::Not { .. }
+        | AstNode::And { .. }
+        | AstNode::Or { .. }
+        | AstNode::Xor { .. }
+        | AstNode::Compare { .. }
+        | AstNode::StringLiteral { .. }
+        | AstNode::Coalesce { .. }
+        | AstNode::BoolLiteral { .. }
+        | AstNode::NumberLiteral { .. }
+        | AstNode::TemplateInclude { .. }
+        | AstNode::DynamicInclude { .. }
+        | AstNode::DebugDump
+        | AstNode::Set { .. }
+        | AstNode::Alias { .. }
+        | AstNode::Break
+        | AstNode::Continue
+        | AstNode::DebugOnly { .. }
+        | AstNode::Repeat { .. } => Err(MinilateError::RenderError {
+            message: format!("Invalid iterable node: {:?}", iterable),
+        }),
     }
 }
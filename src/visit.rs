@@ -0,0 +1,264 @@
+//! Generic traversal of the template AST.
+//!
+//! Tools built on top of Minilate (linters, documentation generators, editor
+//! integrations, ...) often need to inspect a parsed template without caring about
+//! every kind of node. The [`Visitor`] trait has a `visit_*` method for each
+//! [`AstNode`] variant, and [`walk`] dispatches a node to the matching method. Every
+//! method has a default implementation that simply walks the node's children, so a
+//! visitor only needs to override the variants it is interested in.
+//!
+//! When overriding a method for a node with children, call [`walk`] on the children
+//! to continue the traversal below that node, or leave it out to skip them.
+//!
+//! # Example
+//!
+//! ```rust
+//! use minilate::{Template, Visitor, walk};
+//!
+//! #[derive(Default)]
+//! struct Includes<'a>(Vec<&'a str>);
+//!
+//! impl<'a> Visitor<'a> for Includes<'a> {
+//!     fn visit_template_include(&mut self, template_name: &'a str) {
+//!         self.0.push(template_name);
+//!     }
+//! }
+//!
+//! let template =
+//!     Template::new("{{<< header }}{{% if admin %}}{{<< admin_panel }}{{% endif %}}").unwrap();
+//!
+//! let mut includes = Includes::default();
+//! walk(template.ast(), &mut includes);
+//! assert_eq!(includes.0, vec!["header", "admin_panel"]);
+//! ```
+
+use crate::ast::{AstNode, CompareOp};
+
+/// Visits the nodes of a template AST, see the [module documentation](self) for details.
+///
+/// Each method is called by [`walk`] for the corresponding [`AstNode`] variant, with the
+/// fields of that variant as arguments.
+pub trait Visitor<'a> {
+    /// Called for [`AstNode::Root`], walks each child in order by default.
+    fn visit_root(&mut self, children: &[AstNode<'a>]) {
+        walk_all(children, self);
+    }
+
+    /// Called for [`AstNode::Constant`].
+    fn visit_constant(&mut self, _data: &str) {}
+
+    /// Called for [`AstNode::Variable`].
+    fn visit_variable(&mut self, _name: &'a str) {}
+
+    /// Called for [`AstNode::Filter`], walks the filtered value by default.
+    fn visit_filter(&mut self, _name: &'a str, inner: &AstNode<'a>) {
+        walk(inner, self);
+    }
+
+    /// Called for [`AstNode::For`], walks the iterable, the body and then the empty
+    /// branch by default.
+    fn visit_for(
+        &mut self,
+        iterable: &AstNode<'a>,
+        _variable: &'a str,
+        body: &[AstNode<'a>],
+        empty_branch: Option<&AstNode<'a>>,
+    ) {
+        walk(iterable, self);
+        walk_all(body, self);
+        if let Some(empty_branch) = empty_branch {
+            walk(empty_branch, self);
+        }
+    }
+
+    /// Called for [`AstNode::Repeat`], walks the body by default.
+    fn visit_repeat(&mut self, _count: usize, body: &[AstNode<'a>]) {
+        walk_all(body, self);
+    }
+
+    /// Called for [`AstNode::Ternary`], walks the condition and then both values by
+    /// default.
+    fn visit_ternary(
+        &mut self,
+        condition: &AstNode<'a>,
+        if_true: &AstNode<'a>,
+        if_false: &AstNode<'a>,
+    ) {
+        walk(condition, self);
+        walk(if_true, self);
+        walk(if_false, self);
+    }
+
+    /// Called for [`AstNode::If`], walks the condition, the body and then the else
+    /// branch by default.
+    fn visit_if(
+        &mut self,
+        condition: &AstNode<'a>,
+        body: &[AstNode<'a>],
+        else_branch: Option<&AstNode<'a>>,
+    ) {
+        walk(condition, self);
+        walk_all(body, self);
+        if let Some(else_branch) = else_branch {
+            walk(else_branch, self);
+        }
+    }
+
+    /// Called for [`AstNode::Not`], walks the negated condition by default.
+    fn visit_not(&mut self, condition: &AstNode<'a>) {
+        walk(condition, self);
+    }
+
+    /// Called for [`AstNode::And`], walks both operands by default.
+    fn visit_and(&mut self, left: &AstNode<'a>, right: &AstNode<'a>) {
+        walk(left, self);
+        walk(right, self);
+    }
+
+    /// Called for [`AstNode::Or`], walks both operands by default.
+    fn visit_or(&mut self, left: &AstNode<'a>, right: &AstNode<'a>) {
+        walk(left, self);
+        walk(right, self);
+    }
+
+    /// Called for [`AstNode::Xor`], walks both operands by default.
+    fn visit_xor(&mut self, left: &AstNode<'a>, right: &AstNode<'a>) {
+        walk(left, self);
+        walk(right, self);
+    }
+
+    /// Called for [`AstNode::Compare`], walks both operands by default.
+    fn visit_compare(&mut self, left: &AstNode<'a>, _op: CompareOp, right: &AstNode<'a>) {
+        walk(left, self);
+        walk(right, self);
+    }
+
+    /// Called for [`AstNode::StringLiteral`].
+    fn visit_string_literal(&mut self, _value: &'a str) {}
+
+    /// Called for [`AstNode::NumberLiteral`].
+    fn visit_number_literal(&mut self, _value: &'a str) {}
+
+    /// Called for [`AstNode::BoolLiteral`].
+    fn visit_bool_literal(&mut self, _value: bool) {}
+
+    /// Called for [`AstNode::Coalesce`], walks the value and then the fallback by default.
+    fn visit_coalesce(&mut self, value: &AstNode<'a>, fallback: &AstNode<'a>) {
+        walk(value, self);
+        walk(fallback, self);
+    }
+
+    /// Called for [`AstNode::TemplateInclude`], before any of its bindings.
+    fn visit_template_include(&mut self, _template_name: &'a str) {}
+
+    /// Called for each `name=value` binding passed to an [`AstNode::TemplateInclude`],
+    /// walks the value by default.
+    fn visit_include_binding(&mut self, _name: &'a str, value: &AstNode<'a>) {
+        walk(value, self);
+    }
+
+    /// Called for [`AstNode::DynamicInclude`], walks the parts of the name by default.
+    fn visit_dynamic_include(&mut self, name: &[AstNode<'a>]) {
+        walk_all(name, self);
+    }
+
+    /// Called for [`AstNode::DebugDump`].
+    fn visit_debug_dump(&mut self) {}
+
+    /// Called for [`AstNode::DebugOnly`], walks the body by default.
+    fn visit_debug_only(&mut self, body: &[AstNode<'a>]) {
+        walk_all(body, self);
+    }
+
+    /// Called for [`AstNode::Break`].
+    fn visit_break(&mut self) {}
+
+    /// Called for [`AstNode::Continue`].
+    fn visit_continue(&mut self) {}
+
+    /// Called for [`AstNode::Set`], walks the assigned value by default.
+    fn visit_set(&mut self, _name: &'a str, value: &AstNode<'a>) {
+        walk(value, self);
+    }
+
+    /// Called for [`AstNode::Alias`].
+    fn visit_alias(&mut self, _name: &'a str, _target: &'a str) {}
+}
+
+/// Dispatches `node` to the [`Visitor`] method matching its variant.
+///
+/// # Example
+///
+/// ```
+/// use minilate::{Template, Visitor, walk};
+///
+/// struct Constants(usize);
+///
+/// impl<'a> Visitor<'a> for Constants {
+///     fn visit_constant(&mut self, _data: &str) {
+///         self.0 += 1;
+///     }
+/// }
+///
+/// let template = Template::new("Hello {{ name }}, welcome!").unwrap();
+/// let mut constants = Constants(0);
+/// walk(template.ast(), &mut constants);
+/// assert_eq!(constants.0, 2);
+/// ```
+pub fn walk<'a, V: Visitor<'a> + ?Sized>(node: &AstNode<'a>, visitor: &mut V) {
+    match node {
+        AstNode::Root(children) => visitor.visit_root(children),
+        AstNode::Constant { data, .. } => visitor.visit_constant(data),
+        AstNode::Variable { name } => visitor.visit_variable(name),
+        AstNode::Filter { name, inner, .. } => visitor.visit_filter(name, inner),
+        AstNode::For {
+            iterable,
+            variable,
+            value_variable: _,
+            body,
+            empty_branch,
+        } => visitor.visit_for(iterable, variable, body, empty_branch.as_deref()),
+        AstNode::Repeat { count, body } => visitor.visit_repeat(*count, body),
+        AstNode::Ternary {
+            condition,
+            if_true,
+            if_false,
+        } => visitor.visit_ternary(condition, if_true, if_false),
+        AstNode::If {
+            condition,
+            body,
+            else_branch,
+        } => visitor.visit_if(condition, body, else_branch.as_deref()),
+        AstNode::Not { condition } => visitor.visit_not(condition),
+        AstNode::And { left, right } => visitor.visit_and(left, right),
+        AstNode::Or { left, right } => visitor.visit_or(left, right),
+        AstNode::Xor { left, right } => visitor.visit_xor(left, right),
+        AstNode::Compare { left, op, right } => visitor.visit_compare(left, *op, right),
+        AstNode::StringLiteral { value } => visitor.visit_string_literal(value),
+        AstNode::NumberLiteral { value } => visitor.visit_number_literal(value),
+        AstNode::BoolLiteral { value } => visitor.visit_bool_literal(*value),
+        AstNode::Coalesce { value, fallback } => visitor.visit_coalesce(value, fallback),
+        AstNode::TemplateInclude {
+            template_name,
+            bindings,
+        } => {
+            visitor.visit_template_include(template_name);
+            for (name, value) in bindings {
+                visitor.visit_include_binding(name, value);
+            }
+        }
+        AstNode::DynamicInclude { name } => visitor.visit_dynamic_include(name),
+        AstNode::DebugDump => visitor.visit_debug_dump(),
+        AstNode::DebugOnly { body } => visitor.visit_debug_only(body),
+        AstNode::Set { name, value } => visitor.visit_set(name, value),
+        AstNode::Alias { name, target } => visitor.visit_alias(name, target),
+        AstNode::Break => visitor.visit_break(),
+        AstNode::Continue => visitor.visit_continue(),
+    }
+}
+
+fn walk_all<'a, V: Visitor<'a> + ?Sized>(nodes: &[AstNode<'a>], visitor: &mut V) {
+    for node in nodes {
+        walk(node, visitor);
+    }
+}
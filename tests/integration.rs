@@ -6,8 +6,17 @@
 
 mod fixtures;
 
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
 use fixtures::{generate_random_whitespace, generate_random_whitespace_at_least_one, get_engine};
-use minilate::{Context, MinilateError, MinilateInterface, VariableTy};
+use minilate::{
+    AstNode, Context, Escaper, InclusionCondition, LintWarning, MinilateEngine, MinilateError,
+    MinilateInterface, MissingPolicy, ParseErrorKind, RenderMode, SchemaError, Template, Variable,
+    VariableTy, Visitor, walk,
+};
 
 #[test]
 #[ntest::timeout(100)]
@@ -109,10 +118,12 @@ fn test_non_iterable_passed_to_loop() {
     match result {
         Err(MinilateError::TypeMismatch {
             variable_name,
+            template_name,
             expected,
             found,
         }) => {
             assert_eq!(variable_name, "items");
+            assert_eq!(template_name.as_deref(), Some("Loop Template"));
             assert_eq!(expected, VariableTy::Iterable);
             assert_eq!(found, VariableTy::String);
         }
@@ -327,21 +338,30 @@ fn test_boolean_conditions() {
 
 #[test]
 #[ntest::timeout(100)]
-fn test_variable_data_missing() {
+fn test_variable_data_empty() {
     let mut engine = get_engine();
     engine
-        .add_template("MissingData", "Hello, {{ name }}!")
+        .add_template("EmptyData", "Hello, {{ name }}!")
+        .unwrap();
+    engine
+        .add_template(
+            "EmptyCondition",
+            "{{% if name %}}Hi {{ name }}{{% else %}}Anonymous{{ name }}{{% endif %}}",
+        )
         .unwrap();
 
-    // Create a context with a variable with no data
+    // An empty variable is a value like any other, and renders as nothing
     let mut context = Context::new();
     context.insert("name", VariableTy::String.with_data(""));
 
-    let result = engine.render("MissingData", Some(&context));
-    assert!(matches!(
-        result,
-        Err(MinilateError::MissingVariableData { .. })
-    ));
+    assert_eq!(
+        engine.render("EmptyData", Some(&context)).unwrap(),
+        "Hello, !"
+    );
+    assert_eq!(
+        engine.render("EmptyCondition", Some(&context)).unwrap(),
+        "Anonymous"
+    );
 }
 
 #[test]
@@ -357,6 +377,86 @@ fn test_duplicate_template() {
     assert!(matches!(result, Err(MinilateError::TemplateExists { .. })));
 }
 
+#[test]
+#[ntest::timeout(100)]
+fn test_remove_template() {
+    let mut engine = get_engine();
+    engine.add_template("Header", "<h1>Title</h1>").unwrap();
+    engine
+        .add_template("Page", "{{<< Header }}<p>Body</p>")
+        .unwrap();
+
+    // Removing a template frees its name to be added again
+    engine.remove_template("Header").unwrap();
+    engine.add_template("Header", "<h1>New</h1>").unwrap();
+    assert_eq!(
+        engine.render("Page", None).unwrap(),
+        "<h1>New</h1><p>Body</p>"
+    );
+
+    // Templates including a removed template fail to render until it is replaced
+    engine.remove_template("Header").unwrap();
+    let result = engine.render("Page", None);
+    assert!(matches!(
+        result,
+        Err(MinilateError::MissingTemplate { ref template_name }) if template_name == "Header"
+    ));
+
+    let result = engine.remove_template("Header");
+    assert!(matches!(result, Err(MinilateError::MissingTemplate { .. })));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_or_fallback() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("fallback", "Missing page for {{ name }}")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Alice"))
+        .to_owned();
+    assert_eq!(
+        engine
+            .render_or("profile", "fallback", Some(&context))
+            .unwrap(),
+        "Missing page for Alice"
+    );
+
+    engine
+        .add_template("profile", "Profile of {{ name }}")
+        .unwrap();
+    assert_eq!(
+        engine
+            .render_or("profile", "fallback", Some(&context))
+            .unwrap(),
+        "Profile of Alice"
+    );
+
+    let result = engine.render_or("missing", "also_missing", None);
+    assert!(matches!(
+        result,
+        Err(MinilateError::MissingTemplate { ref template_name }) if template_name == "also_missing"
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_template_source() {
+    let mut engine = MinilateEngine::new();
+    let source = "// Greeting\n{{% if formal %}}  Dear {{ name }},{{% else %}}Hi {{ name | trim }}!{{% endif %}}\n";
+    engine.add_template("admin/greeting", source).unwrap();
+
+    assert_eq!(engine.template_source("admin/greeting"), Some(source));
+    assert_eq!(engine.template_source("admin\\greeting"), Some(source));
+    assert_eq!(engine.template_source("missing"), None);
+
+    // Reloading replaces the stored source
+    engine.reload_template("admin/greeting", "Hi!").unwrap();
+    assert_eq!(engine.template_source("admin/greeting"), Some("Hi!"));
+}
+
 #[test]
 #[ntest::timeout(100)]
 fn test_render_missing_template() {
@@ -557,6 +657,39 @@ fn test_conditional_template_referencing_for() {
     assert_eq!(rendered, "The team: Hello Alice! Hello Bob!");
 }
 
+#[test]
+#[ntest::timeout(100)]
+fn test_include_in_members_loop_uses_real_data() {
+    let mut engine = get_engine();
+    engine
+        .add_template("greeting", "Hello {{ name }}!")
+        .unwrap();
+    engine
+        .add_template(
+            "team",
+            "{{% for member in members %}}{{<< greeting }}{{% endfor %}}",
+        )
+        .unwrap();
+
+    // Included templates see exactly the current context, whatever the variables are named
+    let context = Context::new()
+        .insert("members", VariableTy::Iterable.with_data("Alice,Bob"))
+        .insert("name", VariableTy::String.with_data("Carol"))
+        .to_owned();
+    assert_eq!(
+        engine.render("team", Some(&context)).unwrap(),
+        "Hello Carol!Hello Carol!"
+    );
+
+    let context = Context::new()
+        .insert("members", VariableTy::Iterable.with_data("Alice"))
+        .to_owned();
+    assert!(matches!(
+        engine.render("team", Some(&context)),
+        Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "name"
+    ));
+}
+
 #[test]
 #[ntest::timeout(100)]
 fn test_rendering_with_include_nested() {
@@ -593,3 +726,3760 @@ fn test_rendering_with_include_nested() {
         "The team:\nHello John!\nHello Sarah!\nHello Patrick!\nIs the team lead."
     );
 }
+
+#[test]
+#[ntest::timeout(100)]
+fn test_add_template_bytes() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template_bytes("greeting", "Héllo {{ name }}!".as_bytes())
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("John"))
+        .to_owned();
+    let rendered = engine.render("greeting", Some(&context)).unwrap();
+    assert_eq!(rendered, "Héllo John!");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_add_template_bytes_invalid_utf8() {
+    let mut engine = MinilateEngine::new();
+
+    // 0xC3 starts a two-byte sequence, but 0x28 is not a valid continuation byte.
+    let result = engine.add_template_bytes("broken", b"Hello \xC3\x28 {{ name }}");
+    assert_eq!(
+        result,
+        Err(MinilateError::InvalidUtf8 {
+            template_name: "broken".to_string(),
+            valid_up_to: 6,
+        })
+    );
+
+    // Nothing should have been registered.
+    assert!(matches!(
+        engine.render("broken", None),
+        Err(MinilateError::MissingTemplate { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_rename_template() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("greeting", "Hello {{ name }}!")
+        .unwrap();
+
+    engine.rename_template("greeting", "welcome").unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("John"))
+        .to_owned();
+    assert_eq!(
+        engine.render("welcome", Some(&context)).unwrap(),
+        "Hello John!"
+    );
+    assert!(matches!(
+        engine.render("greeting", Some(&context)),
+        Err(MinilateError::MissingTemplate { .. })
+    ));
+
+    // The old name is free to be reused.
+    engine.add_template("greeting", "Hi!").unwrap();
+    assert_eq!(engine.render("greeting", None).unwrap(), "Hi!");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_rename_template_errors() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("a", "A").unwrap();
+    engine.add_template("b", "B").unwrap();
+
+    let result = engine.rename_template("missing", "c");
+    assert_eq!(
+        result,
+        Err(MinilateError::MissingTemplate {
+            template_name: "missing".to_string()
+        })
+    );
+
+    let result = engine.rename_template("a", "b");
+    assert_eq!(
+        result,
+        Err(MinilateError::TemplateExists {
+            template_name: "b".to_string()
+        })
+    );
+
+    // A missing template is reported even if the new name is taken
+    let result = engine.rename_template("missing", "b");
+    assert_eq!(
+        result,
+        Err(MinilateError::MissingTemplate {
+            template_name: "missing".to_string()
+        })
+    );
+
+    // A failed rename leaves both templates untouched.
+    assert_eq!(engine.render("a", None).unwrap(), "A");
+    assert_eq!(engine.render("b", None).unwrap(), "B");
+
+    // Renaming a template to its own name, however it's spelled, changes nothing
+    engine.rename_template("a", "a").unwrap();
+    engine.rename_template("a", "a.tmpl").unwrap();
+    assert_eq!(engine.render("a", None).unwrap(), "A");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_for_loop_empty_branch() {
+    let mut engine = get_engine();
+    engine
+        .add_template(
+            "Items",
+            "Items:{{% for item in items %}} {{ item }}{{% empty %}} Nothing useful{{% endfor %}}",
+        )
+        .unwrap();
+
+    // An empty iterable renders the empty branch
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data(""))
+        .to_owned();
+    assert_eq!(
+        engine.render("Items", Some(&context)).unwrap(),
+        "Items: Nothing useful"
+    );
+
+    // An iterable where every item is empty also renders the empty branch
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data(" , ,  "))
+        .to_owned();
+    assert_eq!(
+        engine.render("Items", Some(&context)).unwrap(),
+        "Items: Nothing useful"
+    );
+
+    // Any non-empty items render the body as usual
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data("a, b"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Items", Some(&context)).unwrap(),
+        "Items: a b"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_for_loop_empty_branch_context() {
+    let mut engine = get_engine();
+    engine
+        .add_template(
+            "Items",
+            "{{% for item in items %}}{{ item }}{{% empty %}}{{ fallback }}{{% endfor %}}",
+        )
+        .unwrap();
+
+    // Both the iterable and the variables of the empty branch are required
+    let variables = engine.context("Items", &Context::new());
+    assert!(variables.contains(&("fallback", VariableTy::String)));
+    assert!(variables.contains(&("items", VariableTy::Iterable)));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_would_change() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Status",
+            "{{ name }} is {{% if online %}}online{{% else %}}offline{{% endif %}}",
+        )
+        .unwrap();
+
+    let old = Context::new()
+        .insert("name", VariableTy::String.with_data("Alice"))
+        .insert("online", VariableTy::Boolean.with_data("true"))
+        .to_owned();
+
+    // Changing a variable the template doesn't use has no effect
+    let mut new = old.clone();
+    new.insert("email", VariableTy::String.with_data("alice@example.com"));
+    assert!(!engine.would_change("Status", &old, &new));
+
+    // Changing a used variable does
+    new.insert("online", VariableTy::Boolean.with_data("false"));
+    assert!(engine.would_change("Status", &old, &new));
+
+    // Going from a successful render to a failing one also counts
+    let missing = Context::new();
+    assert!(engine.would_change("Status", &old, &missing));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_include_with_path_separators() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("admin/header", "[header]").unwrap();
+    engine.add_template("admin\\footer", "[footer]").unwrap();
+    engine
+        .add_template(
+            "page",
+            "{{<< admin/header }} {{<< admin\\header }} {{<< admin/footer }}",
+        )
+        .unwrap();
+
+    assert_eq!(
+        engine.render("page", None).unwrap(),
+        "[header] [header] [footer]"
+    );
+
+    // Either separator resolves to the same registered template
+    assert_eq!(engine.render("admin\\header", None).unwrap(), "[header]");
+    assert!(matches!(
+        engine.add_template("admin\\header", "duplicate"),
+        Err(MinilateError::TemplateExists { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_trim_filter() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Trimmed", "[{{ name | trim }}]")
+        .unwrap();
+    engine.add_template("Untrimmed", "[{{ name }}]").unwrap();
+    engine
+        .add_template("Unknown", "[{{ name | shout }}]")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("  Alice \n"))
+        .to_owned();
+
+    assert_eq!(engine.render("Trimmed", Some(&context)).unwrap(), "[Alice]");
+    assert_eq!(
+        engine.render("Untrimmed", Some(&context)).unwrap(),
+        "[  Alice \n]"
+    );
+    assert!(matches!(
+        engine.render("Unknown", Some(&context)),
+        Err(MinilateError::RenderError { .. })
+    ));
+
+    // Filtered variables are still reported as required
+    assert_eq!(
+        engine.context("Trimmed", &Context::new()),
+        vec![("name", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_reload_template() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("greeting", "Hello, {{ name }}!")
+        .unwrap();
+    engine.add_template("page", "[{{<< greeting }}]").unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("World"))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap(),
+        "[Hello, World!]"
+    );
+
+    engine
+        .reload_template("greeting", "Goodbye, {{ name }}!")
+        .unwrap();
+    assert_eq!(
+        engine.render("greeting", Some(&context)).unwrap(),
+        "Goodbye, World!"
+    );
+    // Includes resolve to the reloaded content under the same name
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap(),
+        "[Goodbye, World!]"
+    );
+
+    // A parse failure leaves the previous content in place
+    assert!(matches!(
+        engine.reload_template("greeting", "{{ unclosed"),
+        Err(MinilateError::Parse(_))
+    ));
+    assert_eq!(
+        engine.render("greeting", Some(&context)).unwrap(),
+        "Goodbye, World!"
+    );
+
+    assert!(matches!(
+        engine.reload_template("missing", "content"),
+        Err(MinilateError::MissingTemplate { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_upsert_template() {
+    let mut engine = MinilateEngine::new();
+    assert!(
+        engine
+            .upsert_template("page", "[{{<< header }}]{{ body }}")
+            .unwrap()
+            .is_none()
+    );
+    engine.add_template("header", "{{ title }}").unwrap();
+    engine.add_template("footer", "<{{ note }}>").unwrap();
+
+    let context = Context::new()
+        .insert("title", VariableTy::String.with_data("Title"))
+        .insert("body", VariableTy::String.with_data("Body"))
+        .insert("note", VariableTy::String.with_data("Note"))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap(),
+        "[Title]Body"
+    );
+
+    // Replacing the content swaps one inclusion for another
+    let previous = engine
+        .upsert_template("page", "{{ body }}{{<< footer }}")
+        .unwrap()
+        .unwrap();
+    // The replaced template is returned as it was
+    assert_eq!(
+        previous.render(&context, Some(&engine)).unwrap(),
+        "[Title]Body"
+    );
+    assert_eq!(engine.render("page", Some(&context)).unwrap(), "Body<Note>");
+    assert_eq!(
+        engine.context("page", &Context::new()),
+        vec![("body", VariableTy::String), ("note", VariableTy::String)]
+    );
+
+    // Removing every inclusion
+    engine.upsert_template("page", "{{ body }}").unwrap();
+    assert_eq!(engine.render("page", Some(&context)).unwrap(), "Body");
+    assert_eq!(
+        engine.context("page", &Context::new()),
+        vec![("body", VariableTy::String)]
+    );
+
+    // A parse failure leaves the previous content in place
+    assert!(matches!(
+        engine.upsert_template("page", "{{ unclosed"),
+        Err(MinilateError::Parse(_))
+    ));
+    assert_eq!(engine.render("page", Some(&context)).unwrap(), "Body");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_for_loop_ternary_iterable() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Items",
+            "{{% for x in (admin ? all_items : public_items) %}}[{{ x }}]{{% endfor %}}",
+        )
+        .unwrap();
+
+    let mut context = Context::new()
+        .insert("admin", VariableTy::Boolean.with_data("true"))
+        .insert("all_items", VariableTy::Iterable.with_data("a, b, c"))
+        .insert("public_items", VariableTy::Iterable.with_data("a"))
+        .to_owned();
+    assert_eq!(engine.render("Items", Some(&context)).unwrap(), "[a][b][c]");
+
+    context.insert("admin", VariableTy::Boolean.with_data("false"));
+    assert_eq!(engine.render("Items", Some(&context)).unwrap(), "[a]");
+
+    // The selected iterable must exist, just like a plain iterable
+    let context = Context::new()
+        .insert("admin", VariableTy::Boolean.with_data("false"))
+        .insert("all_items", VariableTy::Iterable.with_data("a, b, c"))
+        .to_owned();
+    assert!(matches!(
+        engine.render("Items", Some(&context)),
+        Err(MinilateError::MissingVariable { variable_name, .. }) if variable_name == "public_items"
+    ));
+
+    // Both candidates are reported as iterables, and the condition as a boolean
+    let mut variables = engine.context("Items", &Context::new());
+    variables.sort();
+    assert_eq!(
+        variables,
+        vec![
+            ("admin", VariableTy::Boolean),
+            ("all_items", VariableTy::Iterable),
+            ("public_items", VariableTy::Iterable),
+            ("x", VariableTy::String),
+        ]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_escapers() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Quoted", "say \"{{ message }}\"")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("message", VariableTy::String.with_data("it's a \"test\"\n"))
+        .to_owned();
+
+    // No escaping by default
+    assert_eq!(
+        engine.render("Quoted", Some(&context)).unwrap(),
+        "say \"it's a \"test\"\n\""
+    );
+
+    engine.set_escaper(Escaper::Json);
+    assert_eq!(
+        engine.render("Quoted", Some(&context)).unwrap(),
+        r#"say "it's a \"test\"\n""#
+    );
+
+    engine.set_escaper(Escaper::Shell);
+    assert_eq!(
+        engine.render("Quoted", Some(&context)).unwrap(),
+        "say \"'it'\\''s a \"test\"\n'\""
+    );
+
+    engine.set_escaper(Escaper::Html);
+    assert_eq!(
+        engine.render("Quoted", Some(&context)).unwrap(),
+        "say \"it&#39;s a &quot;test&quot;\n\""
+    );
+
+    engine.set_escaper(Escaper::Custom(|value| value.replace('"', "''")));
+    assert_eq!(
+        engine.render("Quoted", Some(&context)).unwrap(),
+        "say \"it's a ''test''\n\""
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_escaper_applies_after_filters_and_in_includes() {
+    let mut engine = MinilateEngine::new();
+    engine.set_escaper(Escaper::Shell);
+    engine.add_template("Arg", "{{ path | trim }}").unwrap();
+    engine.add_template("Command", "ls {{<< Arg }}").unwrap();
+
+    let context = Context::new()
+        .insert("path", VariableTy::String.with_data("  my dir  "))
+        .to_owned();
+
+    assert_eq!(
+        engine.render("Command", Some(&context)).unwrap(),
+        "ls 'my dir'"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_subtree() {
+    let template = Template::new(
+        "Header\n{{% if logged_in %}}Welcome back, {{ name }}!{{% else %}}Please log in.{{% endif %}}",
+    )
+    .unwrap();
+
+    // Render the body of the if in isolation, regardless of the condition
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Alice"))
+        .to_owned();
+    assert_eq!(
+        template
+            .render_subtree::<MinilateEngine>(&[1, 1], &context, None)
+            .unwrap(),
+        "Alice"
+    );
+
+    // The else branch follows the body
+    assert_eq!(
+        template
+            .render_subtree::<MinilateEngine>(&[1, 3], &context, None)
+            .unwrap(),
+        "Please log in."
+    );
+
+    // The whole if node still evaluates its condition
+    assert_eq!(
+        template
+            .render_subtree::<MinilateEngine>(&[1], &context, None)
+            .unwrap(),
+        "Please log in."
+    );
+
+    // An empty path renders everything
+    assert_eq!(
+        template
+            .render_subtree::<MinilateEngine>(&[], &context, None)
+            .unwrap(),
+        template.render::<MinilateEngine>(&context, None).unwrap()
+    );
+
+    assert!(matches!(
+        template.render_subtree::<MinilateEngine>(&[1, 4], &context, None),
+        Err(MinilateError::RenderError { .. })
+    ));
+    assert!(matches!(
+        template.render_subtree::<MinilateEngine>(&[0, 0], &context, None),
+        Err(MinilateError::RenderError { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_annotated() {
+    let template =
+        Template::new("Users:\n{{% for user in users %}}\n- {{ user | trim }}{{% endfor %}}")
+            .unwrap();
+
+    let context = Context::new()
+        .insert("users", VariableTy::Iterable.with_data("alice, bob"))
+        .to_owned();
+
+    assert_eq!(
+        template
+            .render_annotated::<MinilateEngine>(&context, None)
+            .unwrap(),
+        "Users:\n\n- <!--3:6-->alice<!--/3:6-->\n- <!--3:6-->bob<!--/3:6-->"
+    );
+
+    // Plain rendering is unaffected
+    assert_eq!(
+        template.render::<MinilateEngine>(&context, None).unwrap(),
+        "Users:\n\n- alice\n- bob"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_add_templates_atomic() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("existing", "Existing").unwrap();
+
+    // One bad template means none of the batch is added
+    let result = engine.add_templates_atomic([
+        ("header", "Header"),
+        ("body", "{{% if open %}}never closed"),
+        ("footer", "Footer"),
+    ]);
+    assert!(matches!(result, Err(MinilateError::Parse(_))));
+    for name in ["header", "body", "footer"] {
+        assert!(matches!(
+            engine.render(name, None),
+            Err(MinilateError::MissingTemplate { .. })
+        ));
+    }
+
+    // Name clashes, with the engine or within the batch, are also rejected up front
+    assert!(matches!(
+        engine.add_templates_atomic([("header", "Header"), ("existing", "Again")]),
+        Err(MinilateError::TemplateExists { template_name }) if template_name == "existing"
+    ));
+    assert!(matches!(
+        engine.add_templates_atomic([("header", "Header"), ("header", "Again")]),
+        Err(MinilateError::TemplateExists { template_name }) if template_name == "header"
+    ));
+    assert!(matches!(
+        engine.render("header", None),
+        Err(MinilateError::MissingTemplate { .. })
+    ));
+
+    engine
+        .add_templates_atomic([
+            ("header", "Header"),
+            ("body", "{{<< header }} Body"),
+            ("footer", "Footer"),
+        ])
+        .unwrap();
+    assert_eq!(engine.render("body", None).unwrap(), "Header Body");
+    assert_eq!(engine.render("existing", None).unwrap(), "Existing");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_dynamic_include() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("partials/admin", "Admin panel for {{ name }}")
+        .unwrap();
+    engine
+        .add_template("partials/guest", "Welcome, guest")
+        .unwrap();
+    engine
+        .add_template("page", "[{{<< \"partials/{{ kind }}\" }}]")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("kind", VariableTy::String.with_data("admin"))
+        .insert("name", VariableTy::String.with_data("Alice"))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap(),
+        "[Admin panel for Alice]"
+    );
+
+    let context = Context::new()
+        .insert("kind", VariableTy::String.with_data("guest"))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap(),
+        "[Welcome, guest]"
+    );
+
+    let context = Context::new()
+        .insert("kind", VariableTy::String.with_data("missing"))
+        .to_owned();
+    assert!(matches!(
+        engine.render("page", Some(&context)),
+        Err(MinilateError::MissingTemplate { template_name }) if template_name == "partials/missing"
+    ));
+
+    // Without `kind` we can't tell which template is included
+    assert_eq!(
+        engine.context("page", &Context::new()),
+        vec![("kind", VariableTy::String)]
+    );
+
+    // Once it is known, the included template's variables are required too
+    let context = Context::new()
+        .insert("kind", VariableTy::String.with_data("admin"))
+        .to_owned();
+    assert_eq!(
+        engine.context("page", &context),
+        vec![("name", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_constant_only_template() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Static", "Just some text,\nwith no tags.")
+        .unwrap();
+    engine.add_template("Empty", "").unwrap();
+
+    // Context is irrelevant for constant-only templates
+    let context = Context::new()
+        .insert("tag", VariableTy::String.with_data("unused"))
+        .to_owned();
+    for context in [None, Some(&context)] {
+        assert_eq!(
+            engine.render("Static", context).unwrap(),
+            "Just some text,\nwith no tags."
+        );
+        assert_eq!(engine.render("Empty", context).unwrap(), "");
+    }
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_cow() {
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("World"))
+        .to_owned();
+
+    let constant = Template::new("Hello, everyone!").unwrap();
+    let output = constant
+        .render_cow::<MinilateEngine>(&context, None)
+        .unwrap();
+    assert!(matches!(output, Cow::Borrowed("Hello, everyone!")));
+
+    let dynamic = Template::new("Hello, {{ name }}!").unwrap();
+    let output = dynamic
+        .render_cow::<MinilateEngine>(&context, None)
+        .unwrap();
+    assert!(matches!(output, Cow::Owned(ref s) if s == "Hello, World!"));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_debug_directive() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Debug",
+            "{{% for item in items %}}{{% if item %}}{{% debug %}}{{% endif %}}{{% endfor %}}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Alice"))
+        .insert("items", VariableTy::Iterable.with_data("a"))
+        .to_owned();
+
+    // Debug directives are stripped unless debugging is enabled
+    assert_eq!(engine.render("Debug", Some(&context)).unwrap(), "");
+
+    engine.set_debug(true);
+    let output = engine.render("Debug", Some(&context)).unwrap();
+    assert!(output.contains("name: String = \"Alice\"\n"));
+    assert!(output.contains("items: Iterable = \"a\"\n"));
+    // Loop variables are in scope too
+    assert!(output.contains("item: String = \"a\"\n"));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_include_sees_loop_variables() {
+    let mut engine = get_engine();
+
+    engine
+        .add_template("row", "[{{ person }}: {{ role }}]")
+        .unwrap();
+    engine.add_template("cell", "<{{ x }}>").unwrap();
+    engine
+        .add_template(
+            "table",
+            "{{% for person in people %}}{{<< row }}{{% endfor %}} {{% for x in xs %}}{{<< cell }}{{% endfor %}}",
+        )
+        .unwrap();
+
+    // Loop variables are provided by the loops, only the outer variables are required
+    let context = Context::new()
+        .insert("people", VariableTy::Iterable.with_data("Alice, Bob"))
+        .insert("xs", VariableTy::Iterable.with_data("1,2"))
+        .to_owned();
+    let mut vars = engine.context("table", &context);
+    vars.sort();
+    assert_eq!(vars, vec![("role", VariableTy::String)]);
+
+    let context = context
+        .clone()
+        .insert("role", VariableTy::String.with_data("dev"))
+        .to_owned();
+    let rendered = engine.render("table", Some(&context)).unwrap();
+    assert_eq!(rendered, "[Alice: dev][Bob: dev] <1><2>");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_include_outside_loop_still_requires_variables() {
+    let mut engine = get_engine();
+
+    engine
+        .add_template("greeting", "Hello {{ name }}!")
+        .unwrap();
+    engine
+        .add_template(
+            "group_greeting",
+            "{{% for name in names %}}{{<< greeting }}{{% endfor %}}{{<< greeting }}",
+        )
+        .unwrap();
+
+    // The loop binds `name` for the first include, but not for the second
+    let context = Context::new()
+        .insert("names", VariableTy::Iterable.with_data("John,Sarah"))
+        .to_owned();
+    let vars = engine.context("group_greeting", &context);
+    assert_eq!(vars, vec![("name", VariableTy::String)]);
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_visitor_counts_variables() {
+    #[derive(Default)]
+    struct VariableCounter<'a> {
+        names: Vec<&'a str>,
+    }
+
+    impl<'a> Visitor<'a> for VariableCounter<'a> {
+        fn visit_variable(&mut self, name: &'a str) {
+            self.names.push(name);
+        }
+    }
+
+    let template = Template::new(
+        "{{ greeting | trim }}{{% for item in (admin ? all : public) %}}{{ item }}{{% empty %}}{{ fallback }}{{% endfor %}}{{% if a && !b %}}{{ c }}{{% endif %}}{{<< \"partials/{{ kind }}\" }}",
+    )
+    .unwrap();
+
+    let mut counter = VariableCounter::default();
+    walk(template.ast(), &mut counter);
+    assert_eq!(
+        counter.names,
+        vec![
+            "greeting", "admin", "all", "public", "item", "fallback", "a", "b", "c", "kind"
+        ]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_conditional_inclusions_report_conditions() {
+    let template = Template::new(
+        "{{<< header }}{{% if polite %}}{{<< thanks }}{{% else if rude %}}{{<< sorry }}{{% endif %}}{{% for x in xs %}}{{% empty %}}{{<< nothing }}{{% endfor %}}",
+    )
+    .unwrap();
+
+    let polite = AstNode::Variable { name: "polite" };
+    let rude = AstNode::Variable { name: "rude" };
+    let xs = AstNode::Variable { name: "xs" };
+    assert_eq!(
+        template.find_conditional_inclusions(),
+        vec![
+            ("thanks", vec![InclusionCondition::When(&polite)]),
+            (
+                "sorry",
+                vec![
+                    InclusionCondition::Unless(&polite),
+                    InclusionCondition::When(&rude)
+                ]
+            ),
+            ("nothing", vec![InclusionCondition::Empty(&xs)]),
+        ]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_loop_variable_scope() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "After",
+            "{{% for x in items %}}{{ x }}{{% endfor %}}{{ x }}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Else",
+            "{{% for x in items %}}{{% if flag %}}{{ x }}{{% else %}}-{{% endif %}}{{% endfor %}}{{% if flag %}}{{% else %}}{{ x }}{{% endif %}}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data("a,b"))
+        .insert("flag", VariableTy::Boolean.with_data("false"))
+        .to_owned();
+
+    // The last iteration's value must not leak past the end of the loop
+    let result = engine.render("After", Some(&context));
+    assert!(
+        matches!(result, Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "x"),
+        "expected x to be out of scope, got {result:?}"
+    );
+    let result = engine.render("Else", Some(&context));
+    assert!(
+        matches!(result, Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "x"),
+        "expected x to be out of scope, got {result:?}"
+    );
+
+    // An outer variable shadowed by the loop keeps its own value after the loop
+    let context = context
+        .clone()
+        .insert("x", VariableTy::String.with_data("outer"))
+        .to_owned();
+    assert_eq!(engine.render("After", Some(&context)).unwrap(), "abouter");
+    assert_eq!(engine.render("Else", Some(&context)).unwrap(), "--outer");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_context_from_kv_pairs() {
+    let context = Context::from_kv_pairs(
+        [
+            "name=Alice",
+            "admin=true",
+            "guest=false",
+            "roles=dev, ops",
+            "empty=",
+        ]
+        .map(|arg| {
+            let (name, value) = arg.split_once('=').unwrap();
+            (name.to_string(), value.to_string())
+        }),
+    );
+
+    let ty = |name| context.get(name).unwrap().ty();
+    assert_eq!(ty("name"), VariableTy::String);
+    assert_eq!(ty("admin"), VariableTy::Boolean);
+    assert_eq!(ty("guest"), VariableTy::Boolean);
+    assert_eq!(ty("roles"), VariableTy::Iterable);
+    assert_eq!(ty("empty"), VariableTy::String);
+
+    // The inferred types match what the engine expects when rendering
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Cli",
+            "{{ name }}{{% if admin && !guest %}} (admin){{% endif %}}:{{% for role in roles %}} {{ role }}{{% endfor %}}",
+        )
+        .unwrap();
+    assert_eq!(
+        engine.render("Cli", Some(&context)).unwrap(),
+        "Alice (admin): dev ops"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_variable_usage() {
+    let template = Template::new(
+        "Hi {{ name }}{{% if active %}}, welcome back {{ name | trim }}{{% endif %}}!{{% for tag in tags %}} #{{ tag }}{{% endfor %}}",
+    )
+    .unwrap();
+
+    let usage = template.variable_usage();
+    assert_eq!(usage.len(), 4);
+    assert_eq!(usage["name"], 2);
+    assert_eq!(usage["active"], 1);
+    assert_eq!(usage["tags"], 1);
+    assert_eq!(usage["tag"], 1);
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_bool_labels() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Flags", "{{ a }}/{{ b }}/{{ name }}")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("a", VariableTy::Boolean.with_data("true"))
+        .insert("b", VariableTy::Boolean.with_data("false"))
+        .insert("name", VariableTy::String.with_data("true"))
+        .to_owned();
+
+    // Booleans render verbatim by default
+    assert_eq!(
+        engine.render("Flags", Some(&context)).unwrap(),
+        "true/false/true"
+    );
+
+    // Only Boolean variables are relabelled
+    engine.set_bool_labels("Yes", "No");
+    assert_eq!(
+        engine.render("Flags", Some(&context)).unwrap(),
+        "Yes/No/true"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_self_include_rejected() {
+    let mut engine = MinilateEngine::new();
+
+    let result = engine.add_template("a", "Loop: {{% if again %}}{{<< a }}{{% endif %}}");
+    assert_eq!(
+        result,
+        Err(MinilateError::SelfInclude {
+            template_name: "a".to_string()
+        })
+    );
+    assert!(matches!(
+        engine.render("a", None),
+        Err(MinilateError::MissingTemplate { .. })
+    ));
+
+    // Path separators are normalized before comparing names
+    let result = engine.add_template("admin/page", "{{<< admin\\page }}");
+    assert!(matches!(result, Err(MinilateError::SelfInclude { .. })));
+
+    // Including other templates is fine, as is reloading into a self include
+    engine.add_template("b", "{{<< c }}").unwrap();
+    let result = engine.reload_template("b", "{{<< b }}");
+    assert!(matches!(result, Err(MinilateError::SelfInclude { .. })));
+    let result = engine.add_templates_atomic([("d", "ok"), ("e", "{{<< e }}")]);
+    assert!(matches!(result, Err(MinilateError::SelfInclude { .. })));
+    assert!(matches!(
+        engine.render("d", None),
+        Err(MinilateError::MissingTemplate { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_xor_operator() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Xor", "{{% if a ^^ b %}}yes{{% else %}}no{{% endif %}}")
+        .unwrap();
+
+    for (a, b, expected) in [
+        ("false", "false", "no"),
+        ("false", "true", "yes"),
+        ("true", "false", "yes"),
+        ("true", "true", "no"),
+    ] {
+        let context = Context::new()
+            .insert("a", VariableTy::Boolean.with_data(a))
+            .insert("b", VariableTy::Boolean.with_data(b))
+            .to_owned();
+        assert_eq!(
+            engine.render("Xor", Some(&context)).unwrap(),
+            expected,
+            "{a} ^^ {b}"
+        );
+    }
+
+    // Both operands are reported as booleans
+    let mut vars = engine.context("Xor", &Context::new());
+    vars.sort();
+    assert_eq!(
+        vars,
+        vec![("a", VariableTy::Boolean), ("b", VariableTy::Boolean)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_to_file() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Generated", "Hello, {{ name }}!\n")
+        .unwrap();
+
+    let dir = std::env::temp_dir().join(format!("minilate-render-to-file-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("output.txt");
+    std::fs::write(&path, "old contents").unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("World"))
+        .to_owned();
+    engine
+        .render_to_file("Generated", Some(&context), &path)
+        .unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "Hello, World!\n");
+
+    // A failed render leaves the existing file untouched
+    let result = engine.render_to_file("Generated", None, &path);
+    assert!(matches!(result, Err(MinilateError::MissingVariable { .. })));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "Hello, World!\n");
+
+    // Only the output file is left behind
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    // IO failures are reported with the path
+    let missing = dir.join("missing").join("output.txt");
+    let result = engine.render_to_file("Generated", Some(&context), &missing);
+    assert!(
+        matches!(result, Err(MinilateError::Io { ref path, .. }) if *path == missing.display().to_string())
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_dotted_fallback() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Profile",
+            "{{ user.profile.name }} <{{ user.profile.email }}>",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("user.name", VariableTy::String.with_data("Alice"))
+        .insert("name", VariableTy::String.with_data("Nobody"))
+        .insert("email", VariableTy::String.with_data("alice@example.com"))
+        .to_owned();
+
+    // Only exact names are looked up by default
+    assert!(matches!(
+        engine.render("Profile", Some(&context)),
+        Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "user.profile.name"
+    ));
+
+    // The longest name present is used
+    engine.set_dotted_fallback(true);
+    assert_eq!(
+        engine.render("Profile", Some(&context)).unwrap(),
+        "Alice <alice@example.com>"
+    );
+
+    // The full name still takes precedence
+    let context = context
+        .clone()
+        .insert("user.profile.name", VariableTy::String.with_data("Al"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Profile", Some(&context)).unwrap(),
+        "Al <alice@example.com>"
+    );
+
+    // Missing variables are reported by their full name
+    assert!(matches!(
+        engine.render("Profile", Some(&Context::new())),
+        Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "user.profile.name"
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_validate_all() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("header", "# {{ title }}").unwrap();
+    engine
+        .add_template(
+            "body",
+            "{{<< header }}\n{{% for item in items %}}- {{ item }}\n{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template("footer", "Written by {{ author }}")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("title", VariableTy::String.with_data("Notes"))
+        .insert("items", VariableTy::Iterable.with_data("a,b"))
+        .to_owned();
+
+    assert_eq!(
+        engine.validate_all(&context),
+        Err(vec![(
+            "footer".to_string(),
+            MinilateError::MissingVariable {
+                variable_name: "author".to_string(),
+                template_name: Some("footer".to_string()),
+            }
+        )])
+    );
+
+    let context = context
+        .clone()
+        .insert("author", VariableTy::String.with_data("Me"))
+        .to_owned();
+    assert_eq!(engine.validate_all(&context), Ok(()));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_include_trim_markers() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("item", "<li>{{ item }}</li>").unwrap();
+    engine
+        .add_template(
+            "plain",
+            "<ul>\n{{% for item in items %}}\n    {{<< item }}\n{{% endfor %}}\n</ul>",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "trimmed",
+            "<ul>\n{{% for item in items %}}\n    {{<<- item -}}\n{{% endfor %}}\n</ul>",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data("a,b"))
+        .to_owned();
+
+    // Without trim markers the surrounding whitespace is kept
+    assert_eq!(
+        engine.render("plain", Some(&context)).unwrap(),
+        "<ul>\n\n    <li>a</li>\n\n    <li>b</li>\n\n</ul>"
+    );
+
+    // With trim markers all whitespace on either side of the include is removed
+    assert_eq!(
+        engine.render("trimmed", Some(&context)).unwrap(),
+        "<ul>\n<li>a</li><li>b</li>\n</ul>"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_trim_markers() {
+    let mut engine = get_engine();
+    engine
+        .add_template(
+            "list",
+            "Items:\n{{% for item in items -%}}\n- {{ item }}\n{{% endfor -%}}\n{{% if done -%}}\nDone\n{{% endif -%}}\n",
+        )
+        .unwrap();
+    engine
+        .add_template("greeting", "Hello,  \n  {{- name }}!")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data("a,b"))
+        .insert("done", VariableTy::Boolean.with_data("true"))
+        .insert("name", VariableTy::String.with_data("Alice"))
+        .to_owned();
+
+    // A trailing marker trims the rest of its line, so each tag can sit on its own line
+    assert_eq!(
+        engine.render("list", Some(&context)).unwrap(),
+        "Items:\n- a\n- b\nDone\n"
+    );
+
+    // A leading marker trims all preceding whitespace, including newlines
+    assert_eq!(
+        engine.render("greeting", Some(&context)).unwrap(),
+        "Hello,Alice!"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_string_literals() {
+    let template = Template::new(
+        "{{% if status == \"active\" || (role ?? \"guest\") != \"admin\" %}}{{<< header }}{{% endif %}}\
+         {{% for item in (mode == \"all\" ? items : featured) %}}{{ item }}{{% endfor %}}\
+         {{<< \"cards/{{ kind }}.tmpl\" }}{{ name }}",
+    )
+    .unwrap();
+    assert_eq!(
+        template.string_literals(),
+        vec![
+            "active", "guest", "admin", "header", "all", "cards/", ".tmpl"
+        ]
+    );
+
+    assert!(
+        Template::new("{{ name }}")
+            .unwrap()
+            .string_literals()
+            .is_empty()
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_lint_unused_loop_variable() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("unused", "{{% for x in items %}}static{{% endfor %}}")
+        .unwrap();
+    engine
+        .add_template(
+            "used",
+            "{{% for x in items %}}{{% if x %}}yes{{% endif %}}{{% endfor %}}",
+        )
+        .unwrap();
+    engine.add_template("row", "<td>{{ x }}</td>").unwrap();
+    engine
+        .add_template(
+            "included",
+            "{{% for x in items %}}{{<< row }}{{% endfor %}}",
+        )
+        .unwrap();
+
+    assert_eq!(
+        engine.lint("unused").unwrap(),
+        vec![LintWarning::UnusedLoopVariable {
+            variable: "x".to_string()
+        }]
+    );
+    assert_eq!(engine.lint("used").unwrap(), vec![]);
+    // Included templates see the loop variable, so using it there counts
+    assert_eq!(engine.lint("included").unwrap(), vec![]);
+    assert_eq!(
+        engine.lint("missing"),
+        Err(MinilateError::MissingTemplate {
+            template_name: "missing".to_string()
+        })
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_equality_comparisons() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Status",
+            r#"{{% if status == "active" && role != owner %}}yes{{% else %}}no{{% endif %}}"#,
+        )
+        .unwrap();
+
+    for (status, role, expected) in [
+        ("active", "admin", "yes"),
+        ("inactive", "admin", "no"),
+        ("active", "root", "no"),
+    ] {
+        let context = Context::new()
+            .insert("status", VariableTy::String.with_data(status))
+            .insert("role", VariableTy::String.with_data(role))
+            .insert("owner", VariableTy::String.with_data("root"))
+            .to_owned();
+        assert_eq!(
+            engine.render("Status", Some(&context)).unwrap(),
+            expected,
+            "{status}, {role}"
+        );
+    }
+
+    // Missing variables compare as empty strings
+    engine
+        .add_template("Empty", r#"{{% if missing == "" %}}empty{{% endif %}}"#)
+        .unwrap();
+    assert_eq!(engine.render("Empty", None).unwrap(), "empty");
+
+    // Compared variables are reported as strings
+    let mut vars = engine.context("Status", &Context::new());
+    vars.sort();
+    assert_eq!(
+        vars,
+        vec![
+            ("owner", VariableTy::String),
+            ("role", VariableTy::String),
+            ("status", VariableTy::String)
+        ]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_context_apply() {
+    let mut engine = get_engine();
+    engine
+        .add_template(
+            "Greeting",
+            "Hello {{ name }}{{% if title %}}, {{ title }}{{% endif %}}",
+        )
+        .unwrap();
+
+    for (has_title, expected) in [(true, "Hello Alice, Dr"), (false, "Hello Alice")] {
+        let context = Context::new()
+            .apply(|ctx| {
+                ctx.insert("name", VariableTy::String.with_data("Alice"));
+            })
+            .apply(|ctx| {
+                if has_title {
+                    ctx.insert("title", VariableTy::String.with_data("Dr"));
+                }
+            });
+        assert_eq!(engine.render("Greeting", Some(&context)).unwrap(), expected);
+    }
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_relational_comparisons() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Age",
+            "{{% if age >= 18 %}}adult{{% else if age > limit %}}teen{{% else %}}child{{% endif %}} ({{ age }})",
+        )
+        .unwrap();
+
+    for (age, expected) in [
+        ("18", "adult (18)"),
+        ("18.0", "adult (18)"),
+        ("15", "teen (15)"),
+        ("12.5", "child (12.5)"),
+    ] {
+        let context = Context::new()
+            .insert("age", VariableTy::Number.with_data(age))
+            .insert("limit", VariableTy::Number.with_data("12.5"))
+            .to_owned();
+        // The number is rendered as it was given, less any zero fraction
+        assert_eq!(engine.render("Age", Some(&context)).unwrap(), expected);
+    }
+
+    // Unlike other conditions, a missing variable is an error
+    assert_eq!(
+        engine.render("Age", Some(&Context::new())),
+        Err(MinilateError::MissingVariable {
+            variable_name: "age".to_string(),
+            template_name: Some("Age".to_string()),
+        })
+    );
+
+    let context = Context::new()
+        .insert("age", VariableTy::String.with_data("old"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Age", Some(&context)),
+        Err(MinilateError::TypeMismatch {
+            variable_name: "age".to_string(),
+            template_name: Some("Age".to_string()),
+            expected: VariableTy::Number,
+            found: VariableTy::String
+        })
+    );
+
+    // Compared variables are reported as numbers
+    let mut vars = engine.context("Age", &Context::new());
+    vars.sort();
+    assert_eq!(
+        vars,
+        vec![("age", VariableTy::Number), ("limit", VariableTy::Number)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_literal_conditions() {
+    let mut engine = MinilateEngine::new();
+    for (name, condition, expected) in [
+        ("string", r#""true""#, "yes"),
+        ("empty_string", r#""""#, "no"),
+        ("zero", "0", "no"),
+        ("number", "2.5", "yes"),
+        ("negated", "!0", "yes"),
+    ] {
+        engine
+            .add_template(
+                name,
+                format!("{{{{% if {condition} %}}}}yes{{{{% else %}}}}no{{{{% endif %}}}}"),
+            )
+            .unwrap();
+        assert_eq!(engine.render(name, None).unwrap(), expected, "{condition}");
+    }
+
+    // Literals are never reported as required context variables
+    engine
+        .add_template(
+            "compare",
+            r#"{{% if "admin" == role && 1 %}}{{ role }}{{% endif %}}"#,
+        )
+        .unwrap();
+    assert_eq!(
+        engine.context("compare", &Context::new()),
+        vec![("role", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_ifdebug_block() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Page",
+            "<p>{{ name }}</p>{{% ifdebug %}}<!-- rendered for {{ name }} -->{{% endifdebug %}}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Alice"))
+        .to_owned();
+
+    // The block is stripped unless debugging is enabled
+    assert_eq!(
+        engine.render("Page", Some(&context)).unwrap(),
+        "<p>Alice</p>"
+    );
+
+    engine.set_debug(true);
+    assert_eq!(
+        engine.render("Page", Some(&context)).unwrap(),
+        "<p>Alice</p><!-- rendered for Alice -->"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_repeat_block() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Stars",
+            "{{% repeat 3 %}}[{{ loop.index }}{{ suffix }}]{{% endrepeat %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Nothing",
+            "a{{% repeat 0 %}}{{ missing }}{{% endrepeat %}}b",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("suffix", VariableTy::String.with_data("*"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Stars", Some(&context)).unwrap(),
+        "[1*][2*][3*]"
+    );
+    // The body is never rendered, so its variables aren't needed
+    assert_eq!(engine.render("Nothing", None).unwrap(), "ab");
+
+    // `loop.index` is provided by the block
+    assert_eq!(
+        engine.context("Stars", &Context::new()),
+        vec![("suffix", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_loop_metadata() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "List",
+            "{{% for x in items %}}{{ loop.index }}:{{ x }}{{% if !loop.last %}},{{% endif %}}{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Grid",
+            "{{% for row in rows %}}{{% if loop.first %}}[{{% endif %}}{{% for col in cols %}}{{ loop.index0 }}/{{ loop.length }}{{% endfor %}}<{{ loop.index }}>{{% endfor %}}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data("a, b, c"))
+        .insert("rows", VariableTy::Iterable.with_data("r1,r2"))
+        .insert("cols", VariableTy::Iterable.with_data("c1,c2,c3"))
+        .to_owned();
+    assert_eq!(
+        engine.render("List", Some(&context)).unwrap(),
+        "1:a,2:b,3:c"
+    );
+    // The inner loop's metadata doesn't leak into the outer loop
+    assert_eq!(
+        engine.render("Grid", Some(&context)).unwrap(),
+        "[0/31/32/3<1>0/31/32/3<2>"
+    );
+
+    // The metadata is provided by the loop, so only the loop's own variables are reported
+    assert_eq!(
+        engine.context("List", &Context::new()),
+        vec![("items", VariableTy::Iterable), ("x", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_contains_condition() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Tags",
+            r#"{{% if tags contains "rust" %}}rust{{% else %}}other{{% endif %}}"#,
+        )
+        .unwrap();
+
+    let rust = Context::new()
+        .insert("tags", VariableTy::Iterable.with_data("go, rust, zig"))
+        .to_owned();
+    assert_eq!(engine.render("Tags", Some(&rust)).unwrap(), "rust");
+
+    let absent = Context::new()
+        .insert("tags", VariableTy::Iterable.with_data("go, rusty"))
+        .to_owned();
+    assert_eq!(engine.render("Tags", Some(&absent)).unwrap(), "other");
+
+    // A missing iterable contains nothing
+    assert_eq!(engine.render("Tags", None).unwrap(), "other");
+
+    assert_eq!(
+        engine.context("Tags", &Context::new()),
+        vec![("tags", VariableTy::Iterable)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_include_cycle() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("a", "a{{<< b }}").unwrap();
+    engine.add_template("b", "b{{<< a }}").unwrap();
+
+    assert_eq!(
+        engine.render("a", None).unwrap_err(),
+        MinilateError::RecursionLimit {
+            chain: vec!["a".to_string(), "b".to_string(), "a".to_string()]
+        }
+    );
+    assert_eq!(
+        engine.render("b", None).unwrap_err().to_string(),
+        "Template include cycle: b -> a -> b"
+    );
+
+    // A template including itself through a dynamic include is caught too
+    engine
+        .add_template(
+            "page",
+            r#"{{% if nested %}}{{<< "{{ name }}" }}{{% endif %}}"#,
+        )
+        .unwrap();
+    let context = Context::new()
+        .insert("nested", VariableTy::Boolean.with_data("true"))
+        .insert("name", VariableTy::String.with_data("page"))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap_err(),
+        MinilateError::RecursionLimit {
+            chain: vec!["page".to_string(), "page".to_string()]
+        }
+    );
+
+    // Including the same template more than once without a cycle is fine
+    engine.add_template("leaf", "-").unwrap();
+    engine
+        .add_template("twice", "{{<< leaf }}{{<< leaf }}")
+        .unwrap();
+    assert_eq!(engine.render("twice", None).unwrap(), "--");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_include_trailing_separators() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("partial", "[{{ name }}]").unwrap();
+    engine
+        .add_template("page", "{{<< partial/ }}{{<< partial. }}")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Ada"))
+        .to_owned();
+    assert_eq!(engine.render("page", Some(&context)).unwrap(), "[Ada][Ada]");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_transitive_variables() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "page",
+            "{{ title }}{{% if show_body %}}{{<< body }}{{% endif %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "body",
+            "{{% for post in posts %}}{{<< post }}{{% empty %}}{{ empty_note }}{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "post",
+            "{{% if post_count > 1 %}}{{ author }}{{% else %}}{{<< page }}{{% endif %}}",
+        )
+        .unwrap();
+
+    // Every nested variable is reported, though no branch leading to them is taken
+    assert_eq!(
+        engine.transitive_variables("page"),
+        vec![
+            ("author".to_string(), VariableTy::String),
+            ("empty_note".to_string(), VariableTy::String),
+            ("post_count".to_string(), VariableTy::Number),
+            ("posts".to_string(), VariableTy::Iterable),
+            ("show_body".to_string(), VariableTy::Boolean),
+            ("title".to_string(), VariableTy::String),
+        ]
+    );
+    assert_eq!(
+        engine.context("page", &Context::new()),
+        vec![
+            ("show_body", VariableTy::Boolean),
+            ("title", VariableTy::String)
+        ]
+    );
+    assert!(engine.transitive_variables("missing").is_empty());
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_condition_fallback() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Status",
+            r#"{{% if (status ?? "pending") == "pending" %}}waiting{{% else %}}done{{% endif %}}"#,
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Retries",
+            "{{% if retries ?? 3 > 2 %}}many{{% else %}}few{{% endif %}}",
+        )
+        .unwrap();
+
+    let render = |name: &str, status: Option<&str>| {
+        let mut context = Context::new();
+        if let Some(status) = status {
+            context.insert("status", VariableTy::String.with_data(status));
+            context.insert("retries", VariableTy::Number.with_data(status));
+        }
+        engine.render(name, Some(&context))
+    };
+    assert_eq!(render("Status", Some("pending")).unwrap(), "waiting");
+    assert_eq!(render("Status", Some("shipped")).unwrap(), "done");
+    assert_eq!(render("Status", None).unwrap(), "waiting");
+    // An empty value is present, so the fallback isn't used
+    assert_eq!(render("Status", Some("")).unwrap(), "done");
+
+    assert_eq!(render("Retries", Some("1")).unwrap(), "few");
+    assert_eq!(render("Retries", None).unwrap(), "many");
+
+    assert_eq!(
+        engine.context("Retries", &Context::new()),
+        vec![("retries", VariableTy::Number)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_to_shared_buffer() {
+    struct FailingWriter;
+
+    impl std::fmt::Write for FailingWriter {
+        fn write_str(&mut self, _: &str) -> std::fmt::Result {
+            Err(std::fmt::Error)
+        }
+    }
+
+    let mut engine = MinilateEngine::new();
+    engine.add_template("header", "// generated\n").unwrap();
+    engine
+        .add_template("item", "{{<< header }}const {{ name }}: u8 = 1;\n")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("A"))
+        .to_owned();
+    let mut output = String::new();
+    for name in ["header", "item"] {
+        engine
+            .get_template(name)
+            .unwrap()
+            .render_to(&context, Some(&engine), &mut output)
+            .unwrap();
+    }
+    assert_eq!(output, "// generated\n// generated\nconst A: u8 = 1;\n");
+
+    let result =
+        engine
+            .get_template("item")
+            .unwrap()
+            .render_to(&context, Some(&engine), &mut FailingWriter);
+    assert!(matches!(result, Err(MinilateError::RenderError { .. })));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_missing_named_placeholder() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Preview",
+            "Dear {{ name }},{{% if vip %}} VIP{{% endif %}} {{ user.email | trim }} {{<< footer }}",
+        )
+        .unwrap();
+    engine.add_template("footer", "-- {{ sender }}").unwrap();
+
+    let context = Context::new()
+        .insert("sender", VariableTy::String.with_data("Bob"))
+        .to_owned();
+    assert!(matches!(
+        engine.render("Preview", Some(&context)),
+        Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "name"
+    ));
+
+    engine.set_missing_policy(MissingPolicy::NamedPlaceholder("⟪{name}⟫".to_string()));
+    // Missing conditions are still just false
+    assert_eq!(
+        engine.render("Preview", Some(&context)).unwrap(),
+        "Dear ⟪name⟫, ⟪user.email⟫ -- Bob"
+    );
+    assert_eq!(
+        engine.render("Preview", None).unwrap(),
+        "Dear ⟪name⟫, ⟪user.email⟫ -- ⟪sender⟫"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_bool_literal_conditions() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Forced",
+            "{{% if true %}}on{{% endif %}}{{% if false %}}off{{% endif %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Combined",
+            "{{% if debug || true %}}a{{% endif %}}{{% if debug && true %}}b{{% endif %}}{{% if debug == false %}}c{{% endif %}}",
+        )
+        .unwrap();
+
+    assert_eq!(engine.render("Forced", None).unwrap(), "on");
+    assert_eq!(engine.context("Forced", &Context::new()), vec![]);
+
+    let debug = |value: &'static str| {
+        Context::new()
+            .insert("debug", VariableTy::Boolean.with_data(value))
+            .to_owned()
+    };
+    assert_eq!(
+        engine.render("Combined", Some(&debug("true"))).unwrap(),
+        "ab"
+    );
+    assert_eq!(
+        engine.render("Combined", Some(&debug("false"))).unwrap(),
+        "ac"
+    );
+    assert_eq!(engine.render("Combined", None).unwrap(), "a");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_key_value_for_loop() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Settings",
+            "{{% for key, value in settings %}}{{ loop.index }}. {{ key }}: {{ value }}\n{{% empty %}}none{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "List",
+            "{{% for item in items %}}[{{ item }}]{{% endfor %}}",
+        )
+        .unwrap();
+
+    let mut context = Context::new();
+    context.insert(
+        "settings",
+        VariableTy::Map.with_data("theme = dark, lang=en, beta"),
+    );
+    assert_eq!(
+        engine.render("Settings", Some(&context)).unwrap(),
+        "1. theme: dark\n2. lang: en\n3. beta: \n"
+    );
+    assert_eq!(
+        engine.context("Settings", &Context::new()),
+        vec![
+            ("key", VariableTy::String),
+            ("settings", VariableTy::Map),
+            ("value", VariableTy::String)
+        ]
+    );
+
+    context.insert("settings", VariableTy::Map.with_data(""));
+    assert_eq!(engine.render("Settings", Some(&context)).unwrap(), "none");
+
+    // Two loop variables need a map, while a single one still needs an iterable
+    context.insert("settings", VariableTy::Iterable.with_data("a, b"));
+    assert_eq!(
+        engine.render("Settings", Some(&context)).unwrap_err(),
+        MinilateError::TypeMismatch {
+            variable_name: "settings".to_string(),
+            template_name: Some("Settings".to_string()),
+            expected: VariableTy::Map,
+            found: VariableTy::Iterable,
+        }
+    );
+    context.insert("items", VariableTy::Iterable.with_data("a=1, b"));
+    assert_eq!(engine.render("List", Some(&context)).unwrap(), "[a=1][b]");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_filter_table() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Builtins",
+            "{{ name | upper }} {{ name | lower }} [{{ name | trim }}]",
+        )
+        .unwrap();
+    engine
+        .add_template("Chained", "{{ name | trim | shout | lower }}")
+        .unwrap();
+    engine
+        .add_template("Unknown", "{{ name | reverse }}")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data(" Ada "))
+        .to_owned();
+
+    assert_eq!(
+        engine.render("Builtins", Some(&context)).unwrap(),
+        " ADA   ada  [Ada]"
+    );
+    assert_eq!(
+        engine.render("Unknown", Some(&context)).unwrap_err(),
+        MinilateError::RenderError {
+            message: "Unknown filter 'reverse'".to_string()
+        }
+    );
+
+    engine.register_filter(
+        "shout",
+        Box::new(|value| format!("{value}!").to_uppercase()),
+    );
+    engine.register_filter("reverse", Box::new(|value| value.chars().rev().collect()));
+    assert_eq!(engine.render("Chained", Some(&context)).unwrap(), "ada!");
+    assert_eq!(engine.render("Unknown", Some(&context)).unwrap(), " adA ");
+
+    // Registered filters replace built-ins of the same name
+    engine.register_filter("upper", Box::new(|value| format!("<{value}>")));
+    assert_eq!(
+        engine.render("Builtins", Some(&context)).unwrap(),
+        "< Ada >  ada  [Ada]"
+    );
+
+    // The filtered variable is still reported as required
+    assert_eq!(
+        engine.context("Chained", &Context::new()),
+        vec![("name", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_type_mismatches() {
+    let template = Template::new(
+        "{{% for tag in tags %}}{{ tag }}{{% endfor %}}\
+         {{% if age >= 18 %}}adult{{% endif %}}\
+         {{% for k, v in settings %}}{{ k }}{{% endfor %}}\
+         {{% for tag in tags %}}{{% if tag < 3 %}}small{{% endif %}}{{% endfor %}}\
+         {{% if name == 3 %}}{{ count }}{{% endif %}}",
+    )
+    .unwrap();
+
+    let mut context = Context::new();
+    context.insert("tags", VariableTy::String.with_data("a, b"));
+    context.insert("age", VariableTy::String.with_data("eighteen"));
+    context.insert("settings", VariableTy::Map.with_data("a=1"));
+    context.insert("name", VariableTy::Iterable.with_data("x"));
+
+    // Both mismatches are reported, rather than just the first a render hits
+    assert_eq!(
+        template.type_mismatches(&context),
+        vec![
+            MinilateError::TypeMismatch {
+                variable_name: "tags".to_string(),
+                template_name: None,
+                expected: VariableTy::Iterable,
+                found: VariableTy::String,
+            },
+            MinilateError::TypeMismatch {
+                variable_name: "age".to_string(),
+                template_name: None,
+                expected: VariableTy::Number,
+                found: VariableTy::String,
+            },
+        ]
+    );
+    assert_eq!(
+        template
+            .render(&context, None::<&MinilateEngine>)
+            .unwrap_err(),
+        template.type_mismatches(&context)[0]
+    );
+
+    context.insert("tags", VariableTy::Iterable.with_data("1, 2"));
+    context.insert("age", VariableTy::Number.with_data("21"));
+    context.insert("settings", VariableTy::Iterable.with_data("a=1"));
+    assert_eq!(
+        template.type_mismatches(&context),
+        vec![MinilateError::TypeMismatch {
+            variable_name: "settings".to_string(),
+            template_name: None,
+            expected: VariableTy::Map,
+            found: VariableTy::Iterable,
+        }]
+    );
+
+    // Missing variables aren't mismatches
+    assert_eq!(template.type_mismatches(&Context::new()), vec![]);
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_default_filter() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Greeting", r#"Hi {{ nickname | default:"anon" }}!"#)
+        .unwrap();
+    engine
+        .add_template("Shouted", r#"{{ nickname | default:"anon" | upper }}"#)
+        .unwrap();
+    engine
+        .add_template("Bare", "[{{ nickname | default }}]")
+        .unwrap();
+    engine
+        .add_template("Argument", r#"{{ nickname | trim:"x" }}"#)
+        .unwrap();
+
+    assert_eq!(engine.render("Greeting", None).unwrap(), "Hi anon!");
+    assert_eq!(engine.render("Shouted", None).unwrap(), "ANON");
+    assert_eq!(engine.render("Bare", None).unwrap(), "[]");
+
+    let context = Context::new()
+        .insert("nickname", VariableTy::String.with_data("ada"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Greeting", Some(&context)).unwrap(),
+        "Hi ada!"
+    );
+    assert_eq!(engine.render("Shouted", Some(&context)).unwrap(), "ADA");
+    assert_eq!(
+        engine.render("Argument", Some(&context)).unwrap_err(),
+        MinilateError::RenderError {
+            message: "Filter 'trim' doesn't take an argument".to_string()
+        }
+    );
+
+    // The variable is still reported, so it can be supplied
+    assert_eq!(
+        engine.context("Greeting", &Context::new()),
+        vec![("nickname", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_preserve_unknown_directives() {
+    let source = "{{% extends \"base.html\" %}}{{% block body %}}Hi {{ name }}{{% endblock %}}";
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Ada"))
+        .to_owned();
+
+    let mut engine = MinilateEngine::new();
+    assert!(matches!(
+        engine.add_template("Page", source),
+        Err(MinilateError::Parse(_))
+    ));
+
+    engine.set_preserve_unknown_directives(true);
+    engine.add_template("Page", source).unwrap();
+    assert_eq!(
+        engine.render("Page", Some(&context)).unwrap(),
+        "{{% extends \"base.html\" %}}{{% block body %}}Hi Ada{{% endblock %}}"
+    );
+
+    // Minilate's own directives are still processed, and its misplaced closing tags rejected
+    engine
+        .upsert_template(
+            "Page",
+            "{{% if name %}}{{% trans %}}{{ name }}{{% endif %}}",
+        )
+        .unwrap();
+    assert_eq!(
+        engine.render("Page", Some(&context)).unwrap(),
+        "{{% trans %}}Ada"
+    );
+    assert!(matches!(
+        engine.add_template("Stray", "{{% endfor %}}"),
+        Err(MinilateError::Parse(_))
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_iterable_index_access() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("First", "{{ items.0 }}|{{ items.2 | upper }}")
+        .unwrap();
+    engine.add_template("OutOfRange", "{{ items.3 }}").unwrap();
+
+    let mut context = Context::new();
+    context.insert(
+        "items",
+        VariableTy::Iterable.with_data("apple, banana, cherry"),
+    );
+    assert_eq!(
+        engine.render("First", Some(&context)).unwrap(),
+        "apple|CHERRY"
+    );
+    assert_eq!(
+        engine.render("OutOfRange", Some(&context)).unwrap_err(),
+        MinilateError::RenderError {
+            message: "Index 3 is out of bounds for 'items', which has 3 items".to_string()
+        }
+    );
+
+    context.insert("items", VariableTy::Iterable.with_data(""));
+    assert_eq!(
+        engine.render("First", Some(&context)).unwrap_err(),
+        MinilateError::RenderError {
+            message: "Index 0 is out of bounds for 'items', which has 0 items".to_string()
+        }
+    );
+
+    // A variable with the exact name takes precedence, and non-iterables can't be indexed
+    context.insert("items.0", VariableTy::String.with_data("exact"));
+    context.insert("items", VariableTy::Iterable.with_data("a, b, c"));
+    assert_eq!(engine.render("First", Some(&context)).unwrap(), "exact|C");
+    context.insert("items", VariableTy::String.with_data("a, b, c"));
+    assert_eq!(
+        engine.render("OutOfRange", Some(&context)).unwrap_err(),
+        MinilateError::MissingVariable {
+            variable_name: "items.3".to_string(),
+            template_name: Some("OutOfRange".to_string()),
+        }
+    );
+
+    // The iterable is reported as required, rather than each element
+    assert_eq!(
+        engine.context("First", &Context::new()),
+        vec![("items", VariableTy::Iterable)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_case_insensitive_vars() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Profile",
+            "{{ UserName }}{{% if IsAdmin %}} (admin){{% endif %}}:{{% for Tag in TAGS %}} {{ tag }}{{% endfor %}}",
+        )
+        .unwrap();
+
+    let mut context = Context::new();
+    context.insert("username", VariableTy::String.with_data("ada"));
+    context.insert("isadmin", VariableTy::Boolean.with_data("true"));
+    context.insert("tags", VariableTy::Iterable.with_data("a, b"));
+
+    assert_eq!(
+        engine.render("Profile", Some(&context)).unwrap_err(),
+        MinilateError::MissingVariable {
+            variable_name: "UserName".to_string(),
+            template_name: Some("Profile".to_string()),
+        }
+    );
+
+    engine.set_case_insensitive_vars(true);
+    assert_eq!(
+        engine.render("Profile", Some(&context)).unwrap(),
+        "ada (admin): a b"
+    );
+    engine
+        .add_template("Greeting", "{{ USERNAME }}{{% if isAdmin %}}!{{% endif %}}")
+        .unwrap();
+    assert_eq!(engine.context("Greeting", &context), vec![]);
+
+    // An exact match wins, and otherwise the first name in byte order
+    context.insert("USERNAME", VariableTy::String.with_data("upper"));
+    assert_eq!(
+        engine.render("Profile", Some(&context)).unwrap(),
+        "upper (admin): a b"
+    );
+    context.insert("UserName", VariableTy::String.with_data("exact"));
+    assert_eq!(
+        engine.render("Profile", Some(&context)).unwrap(),
+        "exact (admin): a b"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_iterable_separator() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Addresses",
+            "{{% for address in addresses %}}<{{ address }}>{{% empty %}}none{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Lookup",
+            "{{ addresses.1 }}{{% if addresses contains \"Elm Rd, Shelbyville\" %}}!{{% endif %}}",
+        )
+        .unwrap();
+
+    let mut context = Context::new();
+    context.insert(
+        "addresses",
+        VariableTy::Iterable.with_data("1 Main St, Springfield | Elm Rd, Shelbyville"),
+    );
+
+    assert_eq!(
+        engine.render("Addresses", Some(&context)).unwrap(),
+        "<1 Main St><Springfield | Elm Rd><Shelbyville>"
+    );
+
+    engine.set_iterable_separator("|");
+    assert_eq!(
+        engine.render("Addresses", Some(&context)).unwrap(),
+        "<1 Main St, Springfield><Elm Rd, Shelbyville>"
+    );
+    assert_eq!(
+        engine.render("Lookup", Some(&context)).unwrap(),
+        "Elm Rd, Shelbyville!"
+    );
+
+    context.insert("addresses", VariableTy::Iterable.with_data(" | "));
+    assert_eq!(engine.render("Addresses", Some(&context)).unwrap(), "none");
+
+    // An empty separator restores the default
+    engine.set_iterable_separator("");
+    context.insert("addresses", VariableTy::Iterable.with_data("a|b, c"));
+    assert_eq!(
+        engine.render("Addresses", Some(&context)).unwrap(),
+        "<a|b><c>"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_escaped_delimiters_round_trip() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Raw", "\\{{% raw %}}{{ body }}\\{{% endraw %}}")
+        .unwrap();
+    engine
+        .add_template("Mustache", "\\{{x\\}} = {{ x }}")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("body", VariableTy::String.with_data("text"))
+        .insert("x", VariableTy::String.with_data("1"))
+        .to_owned();
+
+    assert_eq!(
+        engine.render("Raw", Some(&context)).unwrap(),
+        "{{% raw %}}text{{% endraw %}}"
+    );
+    assert_eq!(
+        engine.render("Mustache", Some(&context)).unwrap(),
+        "{{x}} = 1"
+    );
+    assert_eq!(
+        engine.context("Raw", &Context::new()),
+        vec![("body", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_capture() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("Header", "# {{ title }}\n").unwrap();
+    engine.add_template("Footer", "\n-- {{ author }}").unwrap();
+    engine
+        .add_template(
+            "Page",
+            "{{<< Header }}{{% for tag in tags %}}[{{ tag }}]{{% endfor %}}\
+             {{% if draft %}}{{ reviewer }}{{% endif %}}{{<< Footer }}{{<< Footer }}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("title", VariableTy::String.with_data("News"))
+        .insert("tags", VariableTy::Iterable.with_data("a, b"))
+        .insert("draft", VariableTy::Boolean.with_data("false"))
+        .insert("author", VariableTy::String.with_data("Ada"))
+        .to_owned();
+
+    let doc = engine.render_capture("Page", &context).unwrap();
+    let expected = "# News\n[a][b]\n-- Ada\n-- Ada";
+    assert_eq!(doc.output, expected);
+    assert_eq!(doc.len(), expected.len());
+    assert!(!doc.is_empty());
+    assert_eq!(doc.variables, ["author", "draft", "tags", "title"]);
+    assert_eq!(doc.includes, ["Header", "Footer"]);
+    assert_eq!(doc.output, engine.render("Page", Some(&context)).unwrap());
+
+    assert!(matches!(
+        engine.render_capture("Missing", &context),
+        Err(MinilateError::MissingTemplate { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_compound_condition_guarded_include() {
+    let mut engine = get_engine();
+    engine
+        .add_template("greeting", "Hello {{ name }}!")
+        .unwrap();
+    engine
+        .add_template(
+            "form",
+            "Greetings{{% if polite && known %}} {{<< greeting }}{{% endif %}}.",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "nested",
+            "{{% if polite %}}{{% if known || guest %}}{{<< greeting }}{{% endif %}}{{% endif %}}",
+        )
+        .unwrap();
+
+    let flags = |polite: &'static str, known: &'static str| {
+        Context::new()
+            .insert("polite", VariableTy::Boolean.with_data(polite))
+            .insert("known", VariableTy::Boolean.with_data(known))
+            .to_owned()
+    };
+
+    // Nothing known yet, so only the condition's variables are needed
+    assert_eq!(
+        engine.context("form", &Context::new()),
+        vec![
+            ("known", VariableTy::Boolean),
+            ("polite", VariableTy::Boolean)
+        ]
+    );
+    // One half of the condition set isn't enough to need the include
+    assert_eq!(
+        engine.context(
+            "form",
+            Context::new().insert("polite", VariableTy::Boolean.with_data("true"))
+        ),
+        vec![("known", VariableTy::Boolean)]
+    );
+    assert!(engine.context("form", &flags("true", "false")).is_empty());
+    assert!(engine.context("form", &flags("false", "true")).is_empty());
+    assert_eq!(
+        engine.context("form", &flags("true", "true")),
+        vec![("name", VariableTy::String)]
+    );
+
+    // Nested conditions must all hold, though `guest` may still be needed to decide them
+    assert_eq!(
+        engine.context("nested", &flags("false", "true")),
+        vec![("guest", VariableTy::Boolean)]
+    );
+    assert_eq!(
+        engine.context("nested", &flags("true", "false")),
+        vec![("guest", VariableTy::Boolean)]
+    );
+    assert_eq!(
+        engine.context("nested", &flags("true", "true")),
+        vec![("guest", VariableTy::Boolean), ("name", VariableTy::String)]
+    );
+
+    // An undecided condition takes neither branch, including the else branch
+    engine
+        .add_template(
+            "fallback",
+            "{{% if polite && known %}}Hi{{% else %}}{{<< greeting }}{{% endif %}}",
+        )
+        .unwrap();
+    assert_eq!(
+        engine.context(
+            "fallback",
+            Context::new().insert("polite", VariableTy::Boolean.with_data("true"))
+        ),
+        vec![("known", VariableTy::Boolean)]
+    );
+    assert!(
+        engine
+            .context("fallback", &flags("true", "true"))
+            .is_empty()
+    );
+    assert_eq!(
+        engine.context("fallback", &flags("true", "false")),
+        vec![("name", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_diff_render() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "report",
+            "Report\n======\n{{% for line in lines %}}- {{ line }}\n{{% endfor %}}Total: {{ total }}\n",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert(
+            "lines",
+            VariableTy::Iterable.with_data("a, b, c, d, e, f, g, h, i, j"),
+        )
+        .insert("total", VariableTy::Number.with_data("10"))
+        .to_owned();
+    let expected = "Report\n======\n- a\n- b\n- c\n- d\n- e\n- f\n- g\n- h\n- i\n- j\nTotal: 10\n";
+    engine
+        .diff_render("report", Some(&context), expected)
+        .unwrap();
+
+    // Each differing line is highlighted, with up to three lines of context in its hunk
+    let wrong = "Report\n======\n- a\n- B\n- c\n- d\n- e\n- f\n- g\n- h\n- i\n- j\nTotal: 9\n";
+    assert_eq!(
+        engine
+            .diff_render("report", Some(&context), wrong)
+            .unwrap_err(),
+        "--- expected\n+++ actual\n\
+         @@ -1,7 +1,7 @@\n Report\n ======\n - a\n-- B\n+- b\n - c\n - d\n - e\n\
+         @@ -10,5 +10,5 @@\n - h\n - i\n - j\n-Total: 9\n+Total: 10\n \n"
+    );
+
+    // Render errors are reported as they are
+    let error = engine.diff_render("report", None, expected).unwrap_err();
+    assert!(error.contains("lines"), "{error}");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_raw_block() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Docs",
+            "Write {{% raw %}}{{ x }}{{% endraw %}} to output x, which is {{ x }}.",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("x", VariableTy::String.with_data("1"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Docs", Some(&context)).unwrap(),
+        "Write {{ x }} to output x, which is 1."
+    );
+    // Only variables outside the raw block are needed
+    assert_eq!(
+        engine.context("Docs", &Context::new()),
+        vec![("x", VariableTy::String)]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_with_cancel() {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    // Cancel from within the render, after the tenth item, as a timeout firing would
+    let mut engine = MinilateEngine::new();
+    let filter_cancel = Arc::clone(&cancel);
+    let filter_calls = Arc::clone(&calls);
+    engine.register_filter(
+        "tick",
+        Box::new(move |value| {
+            if filter_calls.fetch_add(1, Ordering::Relaxed) == 9 {
+                filter_cancel.store(true, Ordering::Relaxed);
+            }
+            value.to_string()
+        }),
+    );
+    engine
+        .add_template(
+            "List",
+            "{{% for item in items %}}{{ item | tick }},{{% endfor %}}",
+        )
+        .unwrap();
+
+    let items = (0..10_000)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data(items))
+        .to_owned();
+
+    let result = engine.render_with_cancel("List", Some(&context), &cancel);
+    assert!(matches!(result, Err(MinilateError::Cancelled)));
+    assert_eq!(calls.load(Ordering::Relaxed), 10);
+
+    // Without the flag set, the whole loop renders
+    cancel.store(false, Ordering::Relaxed);
+    calls.store(100, Ordering::Relaxed);
+    let output = engine
+        .render_with_cancel("List", Some(&context), &cancel)
+        .unwrap();
+    assert_eq!(output.matches(',').count(), 10_000);
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_template_names() {
+    struct Includes<'a>(Vec<&'a str>);
+
+    impl<'a> Visitor<'a> for Includes<'a> {
+        fn visit_template_include(&mut self, template_name: &'a str) {
+            self.0.push(template_name);
+        }
+    }
+
+    let mut engine = MinilateEngine::new();
+    assert!(engine.template_names().is_empty());
+
+    engine
+        .add_template("page", "{{<< header }}{{<< admin\\footer }}")
+        .unwrap();
+    engine.add_template("header", "# Title").unwrap();
+    engine.add_template("admin/footer", "-- Admin").unwrap();
+    engine.add_template("about", "{{<< missing }}").unwrap();
+
+    assert_eq!(
+        engine.template_names(),
+        ["about", "admin/footer", "header", "page"]
+    );
+    assert!(engine.has_template("admin\\footer"));
+    assert!(!engine.has_template("footer"));
+
+    // Every include can be checked up front, before anything is rendered
+    let unresolved = |name: &str| {
+        let mut includes = Includes(Vec::new());
+        walk(engine.get_template(name).unwrap().ast(), &mut includes);
+        includes
+            .0
+            .into_iter()
+            .filter(|include| !engine.has_template(include))
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+    assert!(unresolved("page").is_empty());
+    assert_eq!(unresolved("about"), ["missing"]);
+
+    engine.remove_template("header").unwrap();
+    assert_eq!(engine.template_names(), ["about", "admin/footer", "page"]);
+    assert!(!engine.has_template("header"));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_alias() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Profile",
+            "{{% alias name = user.profile.display_name %}}\
+             {{% if name %}}Hello, {{ name | upper }}!{{% else %}}Hello, stranger!{{% endif %}}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert(
+            "user.profile.display_name",
+            VariableTy::String.with_data("Ada"),
+        )
+        .to_owned();
+    assert_eq!(
+        engine.render("Profile", Some(&context)).unwrap(),
+        "Hello, ADA!"
+    );
+    assert_eq!(
+        engine
+            .render(
+                "Profile",
+                Some(Context::new().insert(
+                    "user.profile.display_name",
+                    VariableTy::String.with_data("")
+                ))
+            )
+            .unwrap(),
+        "Hello, stranger!"
+    );
+    // The context is reported in terms of the real variables
+    let names: Vec<_> = engine
+        .context("Profile", &Context::new())
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, ["user.profile.display_name"]);
+
+    // Aliases are resolved while rendering, so each use is annotated where it's written
+    let template = Template::new("{{% alias s = user.name %}}\nHi {{ s }} and {{ s }}").unwrap();
+    let context = Context::new()
+        .insert("user.name", VariableTy::String.with_data("Ada"))
+        .to_owned();
+    assert_eq!(
+        template
+            .render_annotated::<MinilateEngine>(&context, None)
+            .unwrap(),
+        "\nHi <!--2:7-->Ada<!--/2:7--> and <!--2:19-->Ada<!--/2:19-->"
+    );
+
+    // Aliases end with their block, may stand for other aliases, and are hidden by loop
+    // variables and assigned variables of the same name
+    engine
+        .add_template(
+            "Scopes",
+            "{{% if a %}}{{% alias a = b.c %}}[{{ a }}]{{% endif %}}({{ a }})\
+             {{% alias x = y.z %}}{{% alias w = x %}}\
+             {{% for x in xs %}}<{{ x }}{{ w }}>{{% endfor %}}\
+             {{% set x = \"set\" %}}{{ x }}{{ w }}",
+        )
+        .unwrap();
+    let context = Context::new()
+        .insert("a", VariableTy::String.with_data("A"))
+        .insert("b.c", VariableTy::String.with_data("BC"))
+        .insert("y.z", VariableTy::String.with_data("YZ"))
+        .insert("xs", VariableTy::Iterable.with_data("1,2"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Scopes", Some(&context)).unwrap(),
+        "[BC](A)<1YZ><2YZ>setYZ"
+    );
+    let names: Vec<_> = engine
+        .context("Scopes", &Context::new())
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, ["a", "b.c", "xs", "y.z"]);
+
+    // Included templates don't see the aliases of the template including them
+    engine.add_template("Inner", "{{ n }}").unwrap();
+    engine
+        .add_template("Outer", "{{% alias n = user.name %}}{{ n }}/{{<< Inner }}")
+        .unwrap();
+    let context = Context::new()
+        .insert("user.name", VariableTy::String.with_data("Ada"))
+        .insert("n", VariableTy::String.with_data("inner"))
+        .to_owned();
+    assert_eq!(engine.render("Outer", Some(&context)).unwrap(), "Ada/inner");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_validate() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "page",
+            "{{<< header }}{{% if footer %}}{{<< missing.tmpl }}{{% endif %}}",
+        )
+        .unwrap();
+    engine.add_template("header", "# Title").unwrap();
+
+    let err = engine.validate().unwrap_err();
+    assert_eq!(
+        err,
+        MinilateError::MissingIncludes {
+            includes: vec![("page".to_string(), "missing.tmpl".to_string())]
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Included templates not found: missing.tmpl (in page)"
+    );
+
+    engine.add_template("missing.tmpl", "Bye").unwrap();
+    assert_eq!(engine.validate(), Ok(()));
+
+    // Cycles are caught without rendering, even behind conditions
+    engine
+        .add_template("a", "{{% for x in xs %}}{{<< b }}{{% endfor %}}")
+        .unwrap();
+    engine
+        .add_template("b", "{{% if y %}}{{<< a }}{{% endif %}}")
+        .unwrap();
+    assert_eq!(
+        engine.validate(),
+        Err(MinilateError::RecursionLimit {
+            chain: vec!["a".to_string(), "b".to_string(), "a".to_string()]
+        })
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_context_iter() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("footer", "{{ year }} {{ owner }}")
+        .unwrap();
+    engine
+        .add_template(
+            "page",
+            "{{ title }}{{% for post in posts %}}{{ post }}{{% endfor %}}\
+             {{% if owner %}}{{ title }}{{% endif %}}{{<< footer }}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("year", VariableTy::Number.with_data("2024"))
+        .to_owned();
+    let mut iterated = engine.context_iter("page", &context).collect::<Vec<_>>();
+    let names = iterated
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<HashSet<_>>();
+    assert_eq!(
+        names.len(),
+        iterated.len(),
+        "variables are yielded once each"
+    );
+
+    iterated.sort_by_key(|(name, _)| *name);
+    assert_eq!(iterated, engine.context("page", &context));
+
+    assert_eq!(engine.context_iter("missing", &context).count(), 0);
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_add_templates_from_dir() {
+    let dir = std::env::temp_dir().join(format!("minilate-from-dir-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("header.tmpl"), "# {{ title }}\n").unwrap();
+    std::fs::write(dir.join("page.tmpl"), "{{<< header.tmpl }}{{ body }}").unwrap();
+    std::fs::write(dir.join("notes.txt"), "{{ not a template").unwrap();
+
+    let mut engine = MinilateEngine::new();
+    engine.add_templates_from_dir(&dir).unwrap();
+    assert_eq!(engine.template_names(), ["header", "page"]);
+    assert_eq!(engine.validate(), Ok(()));
+
+    let context = Context::new()
+        .insert("title", VariableTy::String.with_data("News"))
+        .insert("body", VariableTy::String.with_data("Nothing yet."))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap(),
+        "# News\nNothing yet."
+    );
+
+    // Loading the same templates again clashes with the existing names
+    let err = engine.add_templates_from_dir(&dir).unwrap_err();
+    let header = dir.join("header.tmpl").display().to_string();
+    assert!(matches!(
+        err,
+        MinilateError::TemplateFile { ref path, ref error }
+            if *path == header && matches!(**error, MinilateError::TemplateExists { .. })
+    ));
+
+    // Syntax errors name the file, and nothing is added
+    std::fs::write(dir.join("broken.tmpl"), "{{ unclosed").unwrap();
+    let mut engine = MinilateEngine::new();
+    let err = engine.add_templates_from_dir(&dir).unwrap_err();
+    let broken = dir.join("broken.tmpl").display().to_string();
+    assert!(err.to_string().starts_with(&format!("{broken}: ")));
+    assert!(matches!(
+        err,
+        MinilateError::TemplateFile { ref error, .. } if matches!(**error, MinilateError::Parse(_))
+    ));
+    assert!(engine.template_names().is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(matches!(
+        engine.add_templates_from_dir(&dir),
+        Err(MinilateError::Io { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_max_template_length() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("before", "x".repeat(64)).unwrap();
+    engine.set_max_template_length(Some(32));
+
+    let err = engine
+        .add_template("long", format!("{{{{ name }}}}{}", "x".repeat(32)))
+        .unwrap_err();
+    let MinilateError::Parse(err) = err else {
+        panic!("expected a parse error, got {err:?}");
+    };
+    assert_eq!(
+        err.kind,
+        ParseErrorKind::InputTooLong {
+            length: 42,
+            max_length: 32
+        }
+    );
+    assert_eq!((err.line, err.column), (1, 1));
+
+    // Templates at the limit, and those added before it was set, are fine
+    engine.add_template("exact", "x".repeat(32)).unwrap();
+    assert_eq!(engine.render("before", None).unwrap().len(), 64);
+
+    engine.set_max_template_length(None);
+    engine.add_template("long", "x".repeat(1024)).unwrap();
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_context_accessors() {
+    let mut context = Context::new();
+    assert!(context.is_empty());
+    assert_eq!(context.len(), 0);
+
+    context
+        .insert("user", VariableTy::String.with_data("bob"))
+        .insert("token", VariableTy::String.with_data("secret"))
+        .insert("admin", VariableTy::Boolean.with_data("false"));
+    assert_eq!(context.len(), 3);
+    assert!(!context.is_empty());
+
+    // Iteration is ordered by name
+    let names = context.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(names, ["admin", "token", "user"]);
+
+    let removed = context.remove("token").unwrap();
+    assert_eq!(removed.data(), Some("secret"));
+    assert!(context.remove("token").is_none());
+    assert!(!context.contains("token"));
+    assert_eq!(
+        context
+            .iter()
+            .map(|(name, variable)| (name, variable.data()))
+            .collect::<Vec<_>>(),
+        [("admin", Some("false")), ("user", Some("bob"))]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_layered() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Page",
+            "{{ site }}: {{% if logged_in %}}Hi {{ user }}{{% else %}}Hi guest{{% endif %}}\
+             {{% for link in links %}} [{{ link }}]{{% endfor %}}",
+        )
+        .unwrap();
+
+    let request = Context::new()
+        .insert("logged_in", VariableTy::Boolean.with_data("true"))
+        .to_owned();
+    let session = Context::new()
+        .insert("user", VariableTy::String.with_data("ada"))
+        .insert("logged_in", VariableTy::Boolean.with_data("false"))
+        .to_owned();
+    let global = Context::new()
+        .insert("site", VariableTy::String.with_data("Minilate"))
+        .insert("user", VariableTy::String.with_data("nobody"))
+        .insert("links", VariableTy::Iterable.with_data("home, docs"))
+        .to_owned();
+
+    // `user` comes from the second layer, as the first lacks it
+    assert_eq!(
+        engine
+            .render_layered("Page", &[&request, &session, &global])
+            .unwrap(),
+        "Minilate: Hi ada [home] [docs]"
+    );
+    assert_eq!(
+        engine.render_layered("Page", &[&session, &global]).unwrap(),
+        "Minilate: Hi guest [home] [docs]"
+    );
+    assert!(matches!(
+        engine.render_layered("Page", &[&request, &session]),
+        Err(MinilateError::MissingVariable { .. })
+    ));
+
+    // Names are matched regardless of case in every layer when enabled
+    let mut engine = MinilateEngine::new();
+    engine.set_case_insensitive_vars(true);
+    engine.add_template("Shout", "{{ SITE }}").unwrap();
+    assert_eq!(
+        engine
+            .render_layered("Shout", &[&request, &global])
+            .unwrap(),
+        "Minilate"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+#[cfg(feature = "unicode")]
+fn test_truncate_graphemes_filter() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template("Status", r#"{{ status | truncate_graphemes:"3" }}"#)
+        .unwrap();
+
+    // A thumbs up with a skin tone modifier is two code points, but one grapheme
+    let render = |status: &'static str| {
+        let context = Context::new()
+            .insert("status", VariableTy::String.with_data(status))
+            .to_owned();
+        engine.render("Status", Some(&context)).unwrap()
+    };
+    assert_eq!(render("ok 👍🏽 thanks"), "ok ");
+    assert_eq!(render("👍🏽👍🏽👍🏽👍🏽"), "👍🏽👍🏽👍🏽");
+    assert_eq!(render("👨‍👩‍👧 e\u{301}!"), "👨‍👩‍👧 e\u{301}");
+    assert_eq!(render("hi"), "hi");
+
+    engine
+        .add_template("Invalid", r#"{{ status | truncate_graphemes:"many" }}"#)
+        .unwrap();
+    engine
+        .add_template("Missing", "{{ status | truncate_graphemes }}")
+        .unwrap();
+    let context = Context::new()
+        .insert("status", VariableTy::String.with_data("hello"))
+        .to_owned();
+    for name in ["Invalid", "Missing"] {
+        assert!(matches!(
+            engine.render(name, Some(&context)),
+            Err(MinilateError::RenderError { .. })
+        ));
+    }
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_renderers() {
+    let mut engine = MinilateEngine::new();
+    engine.set_renderer("price", |v| format!("${}", v.data().unwrap_or("0")));
+    engine.set_type_renderer(VariableTy::Number, |v| {
+        format!("#{}", v.data().unwrap_or_default())
+    });
+    engine.set_escaper(Escaper::Html);
+    engine
+        .add_template(
+            "Item",
+            "{{ name }}: {{ price }} x{{ quantity }} {{ price | upper }}\
+             {{% if price == \"4.50\" %}} (sale){{% endif %}}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Tea & cake"))
+        .insert("price", VariableTy::Number.with_data("4.50"))
+        .insert("quantity", VariableTy::Number.with_data("2"))
+        .to_owned();
+    // The name's renderer wins over the type's, filters and conditions see the data, and
+    // output is still escaped
+    assert_eq!(
+        engine.render("Item", Some(&context)).unwrap(),
+        "Tea &amp; cake: $4.50 x#2 4.50 (sale)"
+    );
+
+    // Missing variables are still reported
+    assert!(matches!(
+        engine.render("Item", Some(&Context::new())),
+        Err(MinilateError::MissingVariable { .. })
+    ));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_structured_list_loop() {
+    let item = |name: &'static str, price: Option<&'static str>, tags: &'static str| {
+        let mut item = Context::new();
+        item.insert("name", VariableTy::String.with_data(name));
+        item.insert("tags", VariableTy::Iterable.with_data(tags));
+        if let Some(price) = price {
+            item.insert("price", VariableTy::Number.with_data(price));
+        }
+        item
+    };
+
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Cart",
+            "{{% for item in items %}}{{ loop.index }}. {{ item.name }}\
+             {{% if item.price %}} ${{ item.price }}{{% endif %}}\
+             {{% for tag in item.tags %}} #{{ tag }}{{% endfor %}}\n\
+             {{% empty %}}Empty cart{{% endfor %}}\
+             {{% if items %}}Total: {{ total }}{{% endif %}}",
+        )
+        .unwrap();
+
+    // The second item has no price, so the first item's price mustn't leak into it
+    let context = Context::new()
+        .insert(
+            "items",
+            Variable::list([
+                item("Tea", Some("4"), "hot, drink"),
+                item("Mug", None, "gift"),
+            ]),
+        )
+        .insert("total", VariableTy::Number.with_data("4"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Cart", Some(&context)).unwrap(),
+        "1. Tea $4 #hot #drink\n2. Mug #gift\nTotal: 4"
+    );
+    assert_eq!(context.get("items").unwrap().items().len(), 2);
+
+    let empty = Context::new()
+        .insert("items", Variable::list([]))
+        .to_owned();
+    assert_eq!(engine.render("Cart", Some(&empty)).unwrap(), "Empty cart");
+
+    // Lists are iterables for type checking, but not maps
+    let template = Template::new("{{% for item in items %}}{{ item.name }}{{% endfor %}}").unwrap();
+    assert!(template.type_mismatches(&context).is_empty());
+    let template = Template::new("{{% for k, v in items %}}{{ k }}{{% endfor %}}").unwrap();
+    assert_eq!(
+        template
+            .render::<MinilateEngine>(&context, None)
+            .unwrap_err(),
+        MinilateError::TypeMismatch {
+            variable_name: "items".to_string(),
+            template_name: None,
+            expected: VariableTy::Map,
+            found: VariableTy::List,
+        }
+    );
+
+    // Scalar iterables work as before
+    let scalars = Context::new()
+        .insert("items", VariableTy::Iterable.with_data("a, b"))
+        .to_owned();
+    let template = Template::new("{{% for item in items %}}[{{ item }}]{{% endfor %}}").unwrap();
+    assert_eq!(
+        template.render::<MinilateEngine>(&scalars, None).unwrap(),
+        "[a][b]"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_validate_against() {
+    let schema: HashMap<String, VariableTy> = [
+        ("title", VariableTy::String),
+        ("age", VariableTy::Number),
+        ("people", VariableTy::List),
+        ("settings", VariableTy::Map),
+        ("footer", VariableTy::String),
+    ]
+    .into_iter()
+    .map(|(name, ty)| (name.to_string(), ty))
+    .collect();
+
+    // Loop variables, their fields and loop metadata needn't be declared
+    let template = Template::new(
+        "{{ title }}{{% if age >= 18 %}}adult{{% endif %}}\
+         {{% for person in people %}}{{ loop.index }}. {{ person.name }}{{% endfor %}}\
+         {{% for key, value in settings %}}{{ key }}={{ value }}{{% endfor %}}{{ footer }}",
+    )
+    .unwrap();
+    assert_eq!(template.validate_against(&schema), Ok(()));
+
+    // Variables missing from the schema, each reported once
+    let template =
+        Template::new("{{ title }}{{ subtitle }}{{% if subtitle %}}!{{% endif %}}").unwrap();
+    let errors = template.validate_against(&schema).unwrap_err();
+    assert_eq!(
+        errors[0],
+        SchemaError::UndeclaredVariable {
+            variable: "subtitle".to_string()
+        }
+    );
+    assert_eq!(
+        errors[0].to_string(),
+        "Variable is not declared in the schema: subtitle"
+    );
+
+    // Schema entries the template never uses, sorted after the other errors
+    assert_eq!(
+        &errors[1..],
+        [
+            SchemaError::UnusedVariable {
+                variable: "age".to_string()
+            },
+            SchemaError::UnusedVariable {
+                variable: "footer".to_string()
+            },
+            SchemaError::UnusedVariable {
+                variable: "people".to_string()
+            },
+            SchemaError::UnusedVariable {
+                variable: "settings".to_string()
+            },
+        ]
+    );
+
+    // Variables used as a type the schema doesn't give them
+    let template = Template::new(
+        "{{% for t in title %}}{{ t }}{{% endfor %}}{{% for k, v in people %}}{{ k }}{{% endfor %}}\
+         {{% if footer > 3 %}}{{% endif %}}{{ age }}{{ settings }}",
+    )
+    .unwrap();
+    assert_eq!(
+        template.validate_against(&schema),
+        Err(vec![
+            SchemaError::TypeMismatch {
+                variable: "title".to_string(),
+                expected: VariableTy::Iterable,
+                declared: VariableTy::String,
+            },
+            SchemaError::TypeMismatch {
+                variable: "people".to_string(),
+                expected: VariableTy::Map,
+                declared: VariableTy::List,
+            },
+            SchemaError::TypeMismatch {
+                variable: "footer".to_string(),
+                expected: VariableTy::Number,
+                declared: VariableTy::String,
+            },
+        ])
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_dump_ast() {
+    let template = Template::new(
+        "{{% for user in users %}}\
+         {{% if user.admin && !(user.name == \"root\") %}}{{ user.name | upper }}\
+         {{% else %}}{{% for k, v in user.roles %}}{{ k }}{{% endfor %}}{{% endif %}}\
+         {{% empty %}}Nobody{{% endfor %}}{{<< footer }}",
+    )
+    .unwrap();
+
+    let expected = [
+        "Root",
+        "  For user",
+        "    in:",
+        "      Variable users",
+        "    body:",
+        "      If",
+        "        condition:",
+        "          And",
+        "            Variable user.admin",
+        "            Not",
+        "              Compare ==",
+        "                Variable user.name",
+        "                StringLiteral \"root\"",
+        "        body:",
+        "          Filter upper",
+        "            Variable user.name",
+        "        else:",
+        "          Root",
+        "            For k, v",
+        "              in:",
+        "                Variable user.roles",
+        "              body:",
+        "                Variable k",
+        "    empty:",
+        "      Root",
+        "        Constant \"Nobody\"",
+        "  TemplateInclude footer",
+    ];
+    assert_eq!(template.dump_ast(), format!("{}\n", expected.join("\n")));
+
+    // The dump is deterministic
+    assert_eq!(template.dump_ast(), template.dump_ast());
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_collecting_parse_errors() {
+    let source = "Dear {{ name,\nYour order {{ order_id is ready.\n{{ footer }}";
+
+    // The single-error constructor stops at the first mistake
+    let Err(MinilateError::Parse(first)) = Template::new(source) else {
+        panic!("the template is invalid");
+    };
+
+    let Err(errors) = Template::new_collecting_errors(source) else {
+        panic!("the template is invalid");
+    };
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0], first);
+    assert_eq!((errors[0].line, errors[0].column), (1, 13));
+    assert_eq!((errors[1].line, errors[1].column), (2, 24));
+    for error in &errors {
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::Expected { ref description } if description.starts_with("'}}'")
+        ));
+    }
+
+    // An error inside a block doesn't make its closing tag look misplaced, though a
+    // stray closing tag elsewhere is still reported
+    let source = "{{% if a %}}x {{ \n {{% else %}}y{{% endif %}} {{ oops\n{{% endfor %}}";
+    let Err(errors) = Template::new_collecting_errors(source) else {
+        panic!("the template is invalid");
+    };
+    assert_eq!(errors.len(), 3, "unexpected errors: {errors:?}");
+    assert_eq!((errors[0].line, errors[0].column), (2, 2));
+    assert!(matches!(errors[1].kind, ParseErrorKind::Expected { .. }));
+    assert_eq!(errors[1].line, 3);
+    assert_eq!(
+        errors[2].kind,
+        ParseErrorKind::UnknownKeyword {
+            keyword: "endfor".to_string()
+        }
+    );
+
+    let Err(errors) = Template::new_collecting_errors("{{% if a %}}x {{ \n {{% endif %}} {{ oops")
+    else {
+        panic!("the template is invalid");
+    };
+    assert_eq!(errors.len(), 2, "unexpected errors: {errors:?}");
+    assert!(
+        errors
+            .iter()
+            .all(|error| !matches!(error.kind, ParseErrorKind::UnknownKeyword { .. }))
+    );
+
+    // Valid templates parse as usual
+    let template = Template::new_collecting_errors("Hello, {{ name }}!").unwrap();
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Ada"))
+        .to_owned();
+    assert_eq!(
+        template.render::<MinilateEngine>(&context, None).unwrap(),
+        "Hello, Ada!"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_parse_error_source_snippet() {
+    let Err(MinilateError::Parse(error)) = Template::new("Hello!\n\nDear {{ name,\nBye") else {
+        panic!("the template is invalid");
+    };
+    assert_eq!((error.line, error.column), (3, 13));
+    assert_eq!(error.source_line.as_deref(), Some("Dear {{ name,"));
+    assert_eq!(
+        error.to_string(),
+        "Parse error at line 3, column 13: Expected '}}', found ',\nBye'\n\
+         3 | Dear {{ name,\n\
+         \x20 |             ^"
+    );
+
+    // The caret is placed by characters rather than bytes, and keeps tabs for alignment
+    let Err(MinilateError::Parse(error)) = Template::new("\t🦀 {{% unknown %}}\r\n") else {
+        panic!("the template is invalid");
+    };
+    assert_eq!(error.source_line.as_deref(), Some("\t🦀 {{% unknown %}}"));
+    let snippet = error.to_string();
+    let caret_line = snippet.lines().last().unwrap();
+    assert_eq!(caret_line, format!("  | \t{}^", " ".repeat(13)));
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_errors_name_their_template() {
+    let mut engine = MinilateEngine::new();
+    engine.add_template("page", "{{<< layout }}").unwrap();
+    engine
+        .add_template("layout", "{{ title }}{{<< sidebar.tmpl }}")
+        .unwrap();
+    engine
+        .add_template(
+            "sidebar",
+            "{{% for link in links %}}{{ link }}{{% endfor %}}",
+        )
+        .unwrap();
+
+    // The innermost template is named, rather than the one rendered
+    let context = Context::new()
+        .insert("title", VariableTy::String.with_data("Home"))
+        .to_owned();
+    let error = engine.render("page", Some(&context)).unwrap_err();
+    assert_eq!(
+        error,
+        MinilateError::MissingVariable {
+            variable_name: "links".to_string(),
+            template_name: Some("sidebar".to_string()),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "Variable not found: links (in template sidebar)"
+    );
+
+    let context = Context::new()
+        .insert("links", VariableTy::String.with_data("a"))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap_err(),
+        MinilateError::MissingVariable {
+            variable_name: "title".to_string(),
+            template_name: Some("layout".to_string()),
+        }
+    );
+
+    let context = context
+        .clone()
+        .insert("title", VariableTy::Boolean.with_data("true"))
+        .to_owned();
+    assert_eq!(
+        engine.render("page", Some(&context)).unwrap_err(),
+        MinilateError::TypeMismatch {
+            variable_name: "links".to_string(),
+            template_name: Some("sidebar".to_string()),
+            expected: VariableTy::Iterable,
+            found: VariableTy::String,
+        }
+    );
+
+    // Templates outside an engine have no name to report
+    let template = Template::new("{{ title }}").unwrap();
+    let error = template
+        .render::<MinilateEngine>(&Context::new(), None)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Variable not found: title");
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_render_modes() {
+    let template = "Hi {{ name }}, you have {{ count }} messages{{% if admin %}}!{{% endif %}}";
+    // `count` has no data and `name` is missing entirely
+    let context = Context::new()
+        .insert("admin", VariableTy::Boolean.with_data("true"))
+        .insert("count", Variable::list([]))
+        .to_owned();
+
+    let mut strict = MinilateEngine::new().with_mode(RenderMode::Strict);
+    strict.add_template("inbox", template).unwrap();
+    assert!(matches!(
+        strict.render("inbox", Some(&context)),
+        Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "name"
+    ));
+
+    // Strict is the default
+    let mut default = MinilateEngine::new();
+    default.add_template("inbox", template).unwrap();
+    assert_eq!(
+        default.render("inbox", Some(&context)),
+        strict.render("inbox", Some(&context))
+    );
+
+    let mut lenient = MinilateEngine::new().with_mode(RenderMode::Lenient);
+    lenient.add_template("inbox", template).unwrap();
+    assert_eq!(
+        lenient.render("inbox", Some(&context)).unwrap(),
+        "Hi , you have  messages!"
+    );
+
+    // Conditions are unaffected, so a missing variable there is still falsy
+    let context = Context::new()
+        .insert("name", VariableTy::String.with_data("Ada"))
+        .insert("count", VariableTy::Number.with_data("3"))
+        .to_owned();
+    assert_eq!(
+        lenient.render("inbox", Some(&context)).unwrap(),
+        "Hi Ada, you have 3 messages"
+    );
+    assert_eq!(
+        strict.render("inbox", Some(&context)).unwrap(),
+        "Hi Ada, you have 3 messages"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_safe_filter() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "post",
+            "<p class=\"comment\">{{ comment }}</p>{{ signature | safe }}{{ name | safe | upper }}",
+        )
+        .unwrap();
+    let context = Context::new()
+        .insert(
+            "comment",
+            VariableTy::String.with_data("<script>alert('hi')</script>"),
+        )
+        .insert("signature", VariableTy::String.with_data("<em>Ada</em>"))
+        .insert("name", VariableTy::String.with_data("<b>"))
+        .to_owned();
+
+    // Without an escaper, everything passes through
+    assert_eq!(
+        engine.render("post", Some(&context)).unwrap(),
+        "<p class=\"comment\"><script>alert('hi')</script></p><em>Ada</em><B>"
+    );
+
+    // Variables are escaped, but the template's own HTML and `safe` values are not
+    engine.set_escaper(Escaper::Html);
+    assert_eq!(
+        engine.render("post", Some(&context)).unwrap(),
+        "<p class=\"comment\">&lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt;</p><em>Ada</em><B>"
+    );
+
+    engine
+        .add_template("bad", "{{ comment | safe:\"x\" }}")
+        .unwrap();
+    assert!(matches!(
+        engine.render("bad", Some(&context)),
+        Err(MinilateError::RenderError { .. })
+    ));
+
+    // A registered filter of the same name replaces the built-in, so doesn't bypass escaping
+    engine.register_filter("safe", Box::new(|value| value.to_string()));
+    assert!(
+        engine
+            .render("post", Some(&context))
+            .unwrap()
+            .ends_with("&lt;em&gt;Ada&lt;/em&gt;&lt;B&gt;")
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_nested_loop_scopes() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Nested",
+            "{{% for x in outer %}}{{ x }}[{{% for x in inner %}}{{ x }}{{<< Cell }}{{% endfor %}}{{ x }}{{ loop.index }}]{{% endfor %}}{{ x }}",
+        )
+        .unwrap();
+    engine
+        .add_template("Cell", "({{ title }}{{ loop.index }})")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("outer", VariableTy::Iterable.with_data("a,b"))
+        .insert("inner", VariableTy::Iterable.with_data("1,2"))
+        .insert("title", VariableTy::String.with_data("t"))
+        .insert("x", VariableTy::String.with_data("top"))
+        .to_owned();
+
+    // Each loop shadows the one around it, and sees the context's variables through it,
+    // including from an included template
+    assert_eq!(
+        engine.render("Nested", Some(&context)).unwrap(),
+        "a[1(t1)2(t2)a1]b[1(t1)2(t2)b2]top"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_required_variables_are_cached_per_template() {
+    let templates = [
+        (
+            "page",
+            "{{ title }} {{ items.0 }} {{% if admin && !banned %}}{{<< admin }}{{% endif %}}\
+             {{% for item in items %}}{{ loop.index }}{{ item }}{{ suffix }}{{% endfor %}}\
+             {{% if (count ?? 0) > limit %}}{{ LOOP.index }}{{% endif %}}",
+        ),
+        ("admin", "{{ admin_name }} {{ title }}"),
+    ];
+    let new_engine = || {
+        let mut engine = MinilateEngine::new();
+        engine.set_case_insensitive_vars(true);
+        for (name, content) in templates {
+            engine.add_template(name, content).unwrap();
+        }
+        engine
+    };
+
+    let contexts = [
+        Context::new(),
+        Context::new()
+            .insert("title", VariableTy::String.with_data("Home"))
+            .insert("items.0", VariableTy::String.with_data("first"))
+            .to_owned(),
+        Context::new()
+            .insert("admin", VariableTy::Boolean.with_data("true"))
+            .insert("banned", VariableTy::Boolean.with_data("false"))
+            .insert("items", VariableTy::Iterable.with_data("a,b"))
+            .insert("loop.index", VariableTy::Number.with_data("1"))
+            .to_owned(),
+        Context::new()
+            .insert("Title", VariableTy::String.with_data("Home"))
+            .insert("LIMIT", VariableTy::Number.with_data("3"))
+            .to_owned(),
+    ];
+
+    // The same engine reuses each template's analysis, which must give the same result as
+    // a fresh engine analysing it for the first time
+    let cached = new_engine();
+    for _ in 0..2 {
+        for context in &contexts {
+            let uncached = new_engine();
+            assert_eq!(
+                cached.context("page", context),
+                uncached.context("page", context)
+            );
+        }
+    }
+
+    assert_eq!(
+        cached.context("page", &contexts[2]),
+        vec![
+            ("admin_name", VariableTy::String),
+            ("count", VariableTy::Number),
+            ("item", VariableTy::String),
+            ("limit", VariableTy::Number),
+            ("suffix", VariableTy::String),
+            ("title", VariableTy::String),
+        ]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_number_variables() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Count",
+            "{{ count }}: {{% if count %}}some{{% else %}}none{{% endif %}}",
+        )
+        .unwrap();
+    engine
+        .add_template("Any", "{{% if count %}}some{{% else %}}none{{% endif %}}")
+        .unwrap();
+
+    let render_template = |name: &str, count: &str| {
+        let context = Context::new()
+            .insert("count", VariableTy::Number.with_data(count))
+            .to_owned();
+        engine.render(name, Some(&context))
+    };
+    let render = |count: &str| render_template("Count", count);
+
+    // Zero is falsy however it's written, and only a zero fraction is dropped
+    assert_eq!(render("5").unwrap(), "5: some");
+    assert_eq!(render("5.0").unwrap(), "5: some");
+    assert_eq!(render("0").unwrap(), "0: none");
+    assert_eq!(render("0.00").unwrap(), "0: none");
+    assert_eq!(render("-3").unwrap(), "-3: some");
+    assert_eq!(render("-3.0").unwrap(), "-3: some");
+    assert_eq!(render("2.50").unwrap(), "2.50: some");
+    assert_eq!(render("-0.25").unwrap(), "-0.25: some");
+    assert_eq!(render("1e3").unwrap(), "1e3: some");
+
+    // Negative zero is output without its sign
+    assert_eq!(render("-0").unwrap(), "0: none");
+    assert_eq!(render("-0.0").unwrap(), "0: none");
+
+    // Data that isn't a number fails to render rather than being output as text, or
+    // tested as if it were zero or non-zero
+    for count in ["five", "", "inf", "NaN"] {
+        for name in ["Count", "Any"] {
+            assert!(
+                matches!(render_template(name, count), Err(MinilateError::RenderError { ref message }) if message.contains("isn't a number")),
+                "expected {count:?} to be rejected by {name}"
+            );
+        }
+    }
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_set_variables() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Page",
+            "{{% set heading = title | trim | upper %}}{{ heading }}/{{ heading }}\
+             {{% set shown = visible %}}{{% if shown %}} shown{{% endif %}}\
+             {{% set count = 3 %}}{{% if count > 2 %}} many{{% endif %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Loop",
+            "{{% for item in items %}}{{% set label = item | upper %}}{{ label }}{{<< Label }} \
+             {{% endfor %}}{{ label }}",
+        )
+        .unwrap();
+    engine.add_template("Label", "({{ label }})").unwrap();
+
+    let context = Context::new()
+        .insert("title", VariableTy::String.with_data("  report "))
+        .insert("visible", VariableTy::Boolean.with_data("false"))
+        .insert("items", VariableTy::Iterable.with_data("a,b"))
+        .to_owned();
+
+    // A plain variable keeps its type, so `false` stays falsy
+    assert_eq!(
+        engine.render("Page", Some(&context)).unwrap(),
+        "REPORT/REPORT many"
+    );
+
+    // Each iteration assigns its own value, seen by includes, which ends with the loop
+    let result = engine.render("Loop", Some(&context));
+    assert!(
+        matches!(result, Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "label"),
+        "expected label to be out of scope, got {result:?}"
+    );
+    let context = context
+        .clone()
+        .insert("label", VariableTy::String.with_data("outer"))
+        .to_owned();
+    assert_eq!(
+        engine.render("Loop", Some(&context)).unwrap(),
+        "A(A) B(B) outer"
+    );
+
+    // Assigned variables aren't required from the context, but the values they're
+    // assigned from are
+    assert_eq!(
+        engine.context("Page", &Context::new()),
+        vec![
+            ("title", VariableTy::String),
+            ("visible", VariableTy::String),
+        ]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_loop_control() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "Break",
+            "{{% for item in items %}}{{% if item == \"stop\" %}}{{% break %}}{{% endif %}}\
+             {{ item }} {{% endfor %}}done",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Continue",
+            "{{% for item in items %}}{{% if item == \"stop\" %}}{{% continue %}}{{% endif %}}\
+             {{ item }}{{% if !loop.last %}} {{% endif %}}{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Nested",
+            "{{% for row in rows %}}{{% for item in items %}}{{% if item == \"stop\" %}}\
+             {{% break %}}{{% endif %}}{{ row }}{{ item }} {{% endfor %}}{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Repeat",
+            "{{% for row in rows %}}{{% repeat 3 %}}{{% if loop.index == \"2\" %}}{{% break %}}\
+             {{% endif %}}{{ row }}.{{ loop.index }} {{% endrepeat %}}{{% endfor %}}\
+             {{% repeat 3 %}}{{% if loop.first %}}{{% continue %}}{{% endif %}}{{ loop.index }}\
+             {{% endrepeat %}}",
+        )
+        .unwrap();
+
+    let context = Context::new()
+        .insert("items", VariableTy::Iterable.with_data("a,stop,b"))
+        .insert("rows", VariableTy::Iterable.with_data("1,2"))
+        .to_owned();
+
+    assert_eq!(engine.render("Break", Some(&context)).unwrap(), "a done");
+    assert_eq!(engine.render("Continue", Some(&context)).unwrap(), "a b");
+    // A break only leaves the innermost loop
+    assert_eq!(engine.render("Nested", Some(&context)).unwrap(), "1a 2a ");
+    // Repeat blocks are loops too, so a break or continue inside one targets the repeat
+    assert_eq!(
+        engine.render("Repeat", Some(&context)).unwrap(),
+        "1.1 2.1 23"
+    );
+
+    let err = engine.add_template("Outside", "{{% break %}}").unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("'break' can only be used inside a for loop or repeat block"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_include_bindings() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "greeting",
+            "{{% if polite %}}Dear {{ title }} {{% endif %}}{{ name }}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Page",
+            "{{<< greeting name=full_name, polite=true, title=\"Dr\" }}",
+        )
+        .unwrap();
+    engine
+        .add_template(
+            "Loop",
+            "{{% for user in users %}}{{<< greeting name=user, polite=false }} {{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template("signature", "{{ name }}, {{ title }}")
+        .unwrap();
+    engine
+        .add_template("Leaky", "{{<< signature name=full_name }}")
+        .unwrap();
+
+    let context = Context::new()
+        .insert("full_name", VariableTy::String.with_data("Ada Lovelace"))
+        .insert("users", VariableTy::Iterable.with_data("ada,alan"))
+        .insert("polite", VariableTy::Boolean.with_data("true"))
+        .insert("title", VariableTy::String.with_data("Prof"))
+        .to_owned();
+
+    // Literal and variable bindings, which hide the caller's variables of the same name
+    assert_eq!(
+        engine.render("Page", Some(&context)).unwrap(),
+        "Dear Dr Ada Lovelace"
+    );
+    assert_eq!(engine.render("Loop", Some(&context)).unwrap(), "ada alan ");
+
+    // The included template only sees the variables passed to it
+    let result = engine.render("Leaky", Some(&context));
+    assert!(
+        matches!(result, Err(MinilateError::MissingVariable { ref variable_name, .. }) if variable_name == "title"),
+        "expected title to be missing, got {result:?}"
+    );
+
+    // The included template's needs are required under the names they're bound from
+    assert_eq!(
+        engine.context("Page", &Context::new()),
+        vec![("full_name", VariableTy::String)]
+    );
+    assert_eq!(
+        engine.context("Leaky", &Context::new()),
+        vec![
+            ("full_name", VariableTy::String),
+            ("title", VariableTy::String),
+        ]
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_list_loop_include_variables() {
+    let mut engine = MinilateEngine::new();
+    engine
+        .add_template(
+            "People",
+            "{{% for p in people %}}{{<< card }}{{% endfor %}}",
+        )
+        .unwrap();
+    engine
+        .add_template("card", "{{ p.name }} ({{ company }})")
+        .unwrap();
+
+    let person = |name: &str| {
+        Context::new()
+            .insert("name", VariableTy::String.with_data(name.to_string()))
+            .to_owned()
+    };
+    let context = Context::new()
+        .insert("people", Variable::list([person("Ada"), person("Grace")]))
+        .to_owned();
+
+    // The include sees each item's fields, but still needs the company
+    assert_eq!(
+        engine.context("People", &context),
+        vec![("company", VariableTy::String)]
+    );
+    assert_eq!(
+        engine.context_iter("People", &context).collect::<Vec<_>>(),
+        vec![("company", VariableTy::String)]
+    );
+
+    let context = context
+        .clone()
+        .insert("company", VariableTy::String.with_data("Acme"))
+        .to_owned();
+    assert!(engine.context("People", &context).is_empty());
+    assert_eq!(
+        engine.render("People", Some(&context)).unwrap(),
+        "Ada (Acme)Grace (Acme)"
+    );
+}
+
+#[test]
+#[ntest::timeout(100)]
+fn test_engine_is_send_and_sync() {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<MinilateEngine>();
+    assert_send_sync::<Template>();
+}
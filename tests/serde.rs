@@ -6,7 +6,9 @@
 
 #[cfg(feature = "serde")]
 mod serde_tests {
-    use minilate::{Context, MinilateEngine, MinilateInterface, Template, VariableTy};
+    use minilate::{
+        Context, MinilateEngine, MinilateError, MinilateInterface, Template, Variable, VariableTy,
+    };
 
     #[test]
     #[ntest::timeout(100)]
@@ -42,6 +44,44 @@ mod serde_tests {
         );
     }
 
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_list_serialization() {
+        let mut item = Context::new();
+        item.insert("name", VariableTy::String.with_data("Tea"));
+        let list = Variable::list([item]);
+
+        let serialized = serde_json::to_string(&list).unwrap();
+        let deserialized: Variable = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, list);
+        assert_eq!(deserialized.ty(), VariableTy::List);
+        assert_eq!(
+            deserialized.items()[0].get("name").unwrap().data(),
+            Some("Tea")
+        );
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_variable_without_data() {
+        // Variables without data can only be built by deserializing them
+        let context: Context =
+            serde_json::from_str(r#"{"data":{"name":{"ty":"String","data":null}}}"#).unwrap();
+        assert_eq!(context.get("name").unwrap().data(), None);
+
+        let mut engine = MinilateEngine::new();
+        engine
+            .add_template("greeting", "Hello, {{ name }}!")
+            .unwrap();
+        assert_eq!(
+            engine.render("greeting", Some(&context)).unwrap_err(),
+            MinilateError::MissingVariableData {
+                variable_name: "name".to_string(),
+                template_name: Some("greeting".to_string()),
+            }
+        );
+    }
+
     #[test]
     #[ntest::timeout(100)]
     fn test_context_serialization() {
@@ -126,4 +166,118 @@ mod serde_tests {
             deserialized.render("list", Some(&context2)).unwrap()
         );
     }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_context_schema() {
+        let mut engine = MinilateEngine::new();
+        engine
+            .add_template(
+                "profile",
+                "{{ name }}{{% if active %}}{{% for tag in tags %}}#{{% endfor %}}{{% endif %}}",
+            )
+            .unwrap();
+
+        assert_eq!(
+            engine.context_schema("profile"),
+            serde_json::json!({
+                "name": "String",
+                "active": "Boolean",
+                "tags": "Iterable",
+            })
+        );
+        assert_eq!(engine.context_schema("missing"), serde_json::json!({}));
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_template_to_ast_json() {
+        let template = Template::new(
+            "{{% if show %}}{{ name | trim }}{{% endif %}}{{% for x in xs %}}{{<< row }}{{% endfor %}}",
+        )
+        .unwrap();
+
+        let json = template.to_ast_json();
+        let nodes = json["Root"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+
+        let if_node = &nodes[0]["If"];
+        assert_eq!(
+            if_node["condition"],
+            serde_json::json!({ "Variable": { "name": "show" } })
+        );
+        assert_eq!(if_node["body"][0]["Filter"]["name"], "trim");
+        assert_eq!(if_node["else_branch"], serde_json::Value::Null);
+
+        let for_node = &nodes[1]["For"];
+        assert_eq!(for_node["variable"], "x");
+        assert_eq!(
+            for_node["iterable"],
+            serde_json::json!({ "Variable": { "name": "xs" } })
+        );
+        assert_eq!(
+            for_node["body"][0],
+            serde_json::json!({ "TemplateInclude": { "template_name": "row" } })
+        );
+
+        // The JSON round-trips back into the same AST
+        let serialized = json.to_string();
+        let ast: minilate::AstNode<'_> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(&ast, template.ast());
+    }
+
+    #[test]
+    #[ntest::timeout(100)]
+    fn test_context_from_json() {
+        let json = serde_json::json!({
+            "title": "Report",
+            "draft": false,
+            "count": 3,
+            "ratio": 0.5,
+            "tags": ["a", "b", 1, true],
+            "empty": [],
+            "missing": null,
+            "user": { "name": "Alice", "profile": { "age": 30 } },
+            "teams": [{ "name": "core" }, { "name": "web" }],
+            "matrix": [[1, 2], [3]],
+            "partial": ["a", null]
+        });
+        let context = Context::from_json(&json);
+
+        let variables = [
+            ("count", VariableTy::Number, "3"),
+            ("draft", VariableTy::Boolean, "false"),
+            ("empty", VariableTy::Iterable, ""),
+            ("ratio", VariableTy::Number, "0.5"),
+            ("tags", VariableTy::Iterable, "a,b,1,true"),
+            ("title", VariableTy::String, "Report"),
+            ("user.name", VariableTy::String, "Alice"),
+            ("user.profile.age", VariableTy::Number, "30"),
+        ];
+        for (name, ty, data) in variables {
+            let variable = context.get(name).unwrap();
+            assert_eq!((variable.ty(), variable.data()), (ty, Some(data)), "{name}");
+        }
+        // Values the flat model can't represent are skipped
+        for name in ["missing", "teams", "matrix", "partial", "user"] {
+            assert!(!context.contains(name), "{name}");
+        }
+
+        let mut engine = MinilateEngine::new();
+        engine
+            .add_template(
+                "summary",
+                "{{ user.name }}: {{% for tag in tags %}}[{{ tag }}]{{% endfor %}}{{% if count > 2 %}}!{{% endif %}}",
+            )
+            .unwrap();
+        assert_eq!(
+            engine.render("summary", Some(&context)).unwrap(),
+            "Alice: [a][b][1][true]!"
+        );
+
+        assert_eq!(
+            Context::from_json(&serde_json::json!(["not", "an", "object"])),
+            Context::new()
+        );
+    }
 }